@@ -1,5 +1,12 @@
 use serde::{Deserialize, Serialize};
-use webviewbuilder_win::{ReceiveWebviewMessage, WebViewBuilder};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+use webviewbuilder_win::{
+    Accelerator, AcceleratorAction, AcceleratorPolicy, BoundsMode, DownloadDecision, DownloadEvent,
+    DownloadRequest, Favicon, LoadingEvent, NavigationFailed, ProcessFailure,
+    ReceiveWebviewMessage, Rect, Scheduler, WebViewBuilder, WebViewEnvironment, WebViewManager,
+    WebViewSender, WindowStatePersistence,
+};
 use winit::event::{Event, WindowEvent};
 use winit::{
     dpi::LogicalSize,
@@ -12,6 +19,8 @@ use winit::{
 enum MsgFromWebView {
     HelloToServer,
     OpenOptionalWindow,
+    GoBack,
+    SelectItem { item: String },
 }
 
 impl ReceiveWebviewMessage<AppEvent> for MsgFromWebView {
@@ -24,40 +33,83 @@ impl ReceiveWebviewMessage<AppEvent> for MsgFromWebView {
 #[serde(tag = "type")]
 enum MsgToWebView {
     HelloToWebview,
+    ShowItem { item: String },
 }
 
 #[derive(Clone, Eq, PartialEq, Debug)]
 enum AppEvent {
     WindowMsg(MsgFromWebView),
+    ToWeb3(MsgToWebView),
+    ToggleFullscreen,
+    OpenDevTools,
+    CaptureTrace,
+    SourceChanged(String, bool),
+    Loading(LoadingEvent),
+    NavigationFailed(NavigationFailed),
+    Download(DownloadEvent),
+    Favicon(Favicon),
+    ProcessFailed(ProcessFailure),
+    Web3Ready,
+}
+
+// Virtual key codes
+const VK_F5: u32 = 0x74;
+const VK_F11: u32 = 0x7A;
+const VK_I: u32 = 0x49;
+const VK_T: u32 = 0x54;
+
+// Downloads go to the temp dir
+fn save_download(request: &DownloadRequest) -> DownloadDecision {
+    let dir = std::env::temp_dir().join("webview-example-downloads");
+    DownloadDecision::SaveAs(dir.join(&request.suggested_filename))
 }
 
 fn main() {
     let event_loop = EventLoop::<AppEvent>::with_user_event();
     let proxy = event_loop.create_proxy();
 
+    // All the webviews share one browser process group
+    let env = WebViewEnvironment::new().unwrap();
+
+    // Windows reopen where the user left them
+    let window_states = WindowStatePersistence::new("window_states.json");
+
+    // Wakes the event loop for the timed features of the webviews
+    let scheduler = Scheduler::new();
+
     // Example of webview that does not need message passing
     let web1 = WebViewBuilder::new()
-        .webview_init(|w| {
-            w.navigate_to_string(
-                r#"
-                    <html>
-                    <title>Foo</title>
-                    <body>
-                    <h2>WebView2 - No communication</h2>
-                    "#,
-            )
-        })
+        .with_environment(&env)
+        .persist_window_state(&window_states, "web1")
+        .scheduler(&scheduler)
+        .loading_events(AppEvent::Loading)
+        .on_favicon_changed(AppEvent::Favicon)
+        .favicon_window_icon(true)
+        .html(
+            r#"
+                <html>
+                <title>Foo</title>
+                <link rel="icon" href="data:image/svg+xml,<svg xmlns='http://www.w3.org/2000/svg' viewBox='0 0 16 16'><circle cx='8' cy='8' r='7' fill='teal'/></svg>">
+                <body>
+                <h2>WebView2 - No communication</h2>
+                "#,
+        )
         .build(&event_loop)
         .unwrap();
 
     // Example of webview that exist only optionally (like preference dialog)
     let mut webopt = WebViewBuilder::new()
+        .with_environment(&env)
+        .background_color(0, 128, 0, 255)
+        .frameless()
+        .optional_keep_alive()
         .webview_init(|w| {
             w.navigate_to_string(
                 r#"
-                    <html style="background: green;">
+                    <html>
                     <title>Optional Window</title>
                     <body>
+                    <header style="-webkit-app-region: drag">Drag the window from here, Alt+F4 closes it</header>
                     <h2>This exists only while it's open</h2>
                     "#,
             )
@@ -66,23 +118,39 @@ fn main() {
 
     // Example of webview that has one-way communication
     let web2 = WebViewBuilder::new()
+        .with_environment(&env)
         .msg_from_webview::<MsgFromWebView>()
         .webview_init(|w| {
             w.navigate_to_string(
-                r#"
+                r##"
                     <html>
                     <title>Foo</title>
                     <body>
                     <h2>WebView2 - One sided communication</h2>
                     <button type="button" onclick='window.chrome.webview.postMessage(JSON.stringify({"type": "OpenOptionalWindow"}));'>Open Optional Window</button>
-                "#,
+                    <button type="button" onclick='window.chrome.webview.postMessage(JSON.stringify({"type": "GoBack"}));'>Back</button>
+                    <a href="#second">Go to #second</a>
+                    <a href="https://unreachable.invalid/">Go to an unreachable host</a>
+                    <a href="data:text/plain,Hello%20from%20the%20webview" download="hello.txt">Download hello.txt</a>
+                "##,
             )
         })
+        .on_navigation_failed(AppEvent::NavigationFailed)
+        .downloads(
+            std::env::temp_dir().join("webview-example-staging"),
+            save_download,
+            AppEvent::Download,
+        )
         .build(&event_loop)
         .unwrap();
 
     // Example of webview that has two-way communication
     let web3 = WebViewBuilder::new()
+        .with_environment(&env)
+        .scheduler(&scheduler)
+        .auto_recover(true)
+        .on_process_failure(AppEvent::ProcessFailed)
+        .on_ready(AppEvent::Web3Ready)
         .msg_from_webview::<MsgFromWebView>()
         .msg_to_webview::<MsgToWebView>()
         .webview_init(|w| {
@@ -105,12 +173,32 @@ fn main() {
                 "#,
             )
         })
+        .notify_source_changed(|url, new_document| {
+            AppEvent::SourceChanged(url.to_owned(), new_document)
+        })
         // Optionally give window builder
         .window_builder(
             WindowBuilder::new()
                 .with_resizable(false)
                 .with_inner_size(LogicalSize::new(600, 600)),
         )
+        // Block the reload, and handle the fullscreen key in the event loop
+        .accelerators(
+            AcceleratorPolicy::new()
+                .key(Accelerator::new(VK_F5), AcceleratorAction::Handled)
+                .key(
+                    Accelerator::new(VK_F11),
+                    AcceleratorAction::ForwardToEventLoop(AppEvent::ToggleFullscreen),
+                )
+                .key(
+                    Accelerator::new(VK_I).ctrl().shift(),
+                    AcceleratorAction::ForwardToEventLoop(AppEvent::OpenDevTools),
+                )
+                .key(
+                    Accelerator::new(VK_T).ctrl().shift(),
+                    AcceleratorAction::ForwardToEventLoop(AppEvent::CaptureTrace),
+                ),
+        )
         // Give some settings
         .settings(|settings| {
             settings.put_is_status_bar_enabled(false)?;
@@ -121,20 +209,108 @@ fn main() {
         .build(&event_loop)
         .unwrap();
 
+    // Example of two webviews sharing a window, the host passes the
+    // selection of the left pane to the right pane, and the items can be
+    // dragged from the left pane to the right pane
+    let panes_window = Rc::new(
+        WindowBuilder::new()
+            .with_title("Two panes")
+            .with_inner_size(LogicalSize::new(800, 500))
+            .build(&event_loop)
+            .unwrap(),
+    );
+    let left_pane = WebViewBuilder::new()
+        .with_environment(&env)
+        .msg_from_webview::<MsgFromWebView>()
+        .msg_to_webview::<MsgToWebView>()
+        .cross_window_drag(1024)
+        .bounds(BoundsMode::Custom(|size| Rect {
+            left: 0,
+            top: 0,
+            right: size.width as i32 / 3,
+            bottom: size.height as i32,
+        }))
+        .webview_init(|w| {
+            w.navigate_to_string(
+                r#"
+                    <html>
+                    <body>
+                    <h2>Items</h2>
+                    <script>
+                        const select = item => window.chrome.webview.postMessage(
+                            JSON.stringify({ "type": "SelectItem", item })
+                        );
+                    </script>
+                    <button type="button" onclick='select("Apples")'
+                        onmousedown='window.host.dnd.startDrag({ item: "Apples" })'>Apples</button>
+                    <button type="button" onclick='select("Oranges")'
+                        onmousedown='window.host.dnd.startDrag({ item: "Oranges" })'>Oranges</button>
+                "#,
+            )
+        })
+        .build_in_window(panes_window.clone(), &proxy)
+        .unwrap();
+    let right_pane = WebViewBuilder::new()
+        .with_environment(&env)
+        .msg_to_webview::<MsgToWebView>()
+        .cross_window_drag(1024)
+        .bounds(BoundsMode::Custom(|size| Rect {
+            left: size.width as i32 / 3,
+            top: 0,
+            right: size.width as i32,
+            bottom: size.height as i32,
+        }))
+        .focus_webview(false)
+        .webview_init(|w| {
+            w.navigate_to_string(
+                r#"
+                    <html style="background: #eee;">
+                    <body>
+                    <h2 id="item">Select an item</h2>
+                    <script>
+                        const show = item => document.getElementById("item").textContent = item;
+                        chrome.webview.addEventListener("message", e => {
+                            if (e.data.type === "ShowItem") {
+                                show(e.data.item);
+                            }
+                        });
+                        window.host.dnd.drop = payload => show(payload.item);
+                    </script>
+                "#,
+            )
+        })
+        .build_in_window(panes_window.clone(), &proxy)
+        .unwrap();
+    let mut panes = WebViewManager::new();
+    panes.add("left", left_pane);
+    panes.add("right", right_pane);
+
+    // Messages from a background thread go through the event loop
+    let sender = WebViewSender::new(event_loop.create_proxy(), AppEvent::ToWeb3);
+    std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_secs(2));
+        let _ = sender.send(MsgToWebView::HelloToWebview);
+    });
+
     event_loop.run(move |event, event_loop_target, control_flow| {
         *control_flow = ControlFlow::Wait;
 
         match event {
+            Event::NewEvents(_) => {
+                let _ = scheduler.run_due(Instant::now());
+            }
             Event::WindowEvent { event, window_id } => {
                 let _ = web1.handle_window_event(&event, &window_id);
                 let _ = web2.handle_window_event(&event, &window_id);
                 let _ = web3.handle_window_event(&event, &window_id);
                 let _ = webopt.handle_window_event(&event, &window_id);
+                let _ = panes.handle_window_event(&event, &window_id);
 
                 // Close the application if any of the webviews is closed
                 if web1.is_window(&window_id)
                     || web2.is_window(&window_id)
                     || web3.is_window(&window_id)
+                    || window_id == panes_window.id()
                 {
                     if let WindowEvent::CloseRequested = event {
                         *control_flow = ControlFlow::Exit
@@ -149,11 +325,102 @@ fn main() {
                     }
                     MsgFromWebView::OpenOptionalWindow => {
                         println!("Open the optional window!");
-                        webopt.show(&event_loop_target, &proxy)
+                        if let Err(err) = webopt.show(&event_loop_target, &proxy) {
+                            println!("Optional window failed: {}", err);
+                        }
+                    }
+                    MsgFromWebView::GoBack => {
+                        if let Ok(false) = web2.go_back() {
+                            println!("No page to go back to");
+                        }
+                    }
+                    MsgFromWebView::SelectItem { item } => {
+                        let _ = panes.send_to("right", MsgToWebView::ShowItem { item });
                     }
                 },
+                AppEvent::ToWeb3(m) => {
+                    let _ = web3.send_msg(m);
+                }
+                AppEvent::ToggleFullscreen => {
+                    println!("Toggle fullscreen!");
+                }
+                AppEvent::OpenDevTools => {
+                    let _ = web3.open_dev_tools();
+                }
+                AppEvent::CaptureTrace => {
+                    println!("Tracing for ten seconds");
+                    let path = std::env::temp_dir().join("webview-trace.json");
+                    let started =
+                        web3.capture_trace(Duration::from_secs(10), &path, &[], |result| {
+                            match result {
+                                Ok(stats) => {
+                                    println!("Trace of {} events written", stats.events);
+                                    let _ = std::process::Command::new("explorer")
+                                        .arg(format!("/select,{}", stats.path.display()))
+                                        .spawn();
+                                }
+                                Err(err) => println!("Trace failed: {}", err),
+                            }
+                        });
+                    if let Err(err) = started {
+                        println!("Trace was not started: {}", err);
+                    }
+                }
+                AppEvent::Loading(LoadingEvent::Started { .. }) => {
+                    println!("Loading started");
+                }
+                AppEvent::Loading(LoadingEvent::Completed { .. }) => {
+                    println!("Loading finished");
+                }
+                AppEvent::Loading(_) => (),
+                AppEvent::NavigationFailed(failed) => {
+                    println!("Navigation to {} failed: {:?}", failed.url, failed.status);
+                }
+                AppEvent::Download(DownloadEvent::Progress {
+                    received_bytes,
+                    total_bytes,
+                    ..
+                }) => {
+                    println!("Downloaded {} / {} bytes", received_bytes, total_bytes);
+                }
+                AppEvent::Download(DownloadEvent::Completed { path, .. }) => {
+                    println!("Downloaded to {}", path.display());
+                }
+                AppEvent::Download(event) => {
+                    println!("Download: {:?}", event);
+                }
+                AppEvent::Web3Ready => {
+                    let _ = web3.send_msg(MsgToWebView::HelloToWebview);
+                }
+                AppEvent::ProcessFailed(failure) => {
+                    println!("Process of the webview failed: {:?}", failure);
+                }
+                AppEvent::Favicon(favicon) => {
+                    println!(
+                        "Favicon {}x{}: {}",
+                        favicon.width, favicon.height, favicon.url
+                    );
+                }
+                AppEvent::SourceChanged(url, new_document) => {
+                    println!("Url changed: {} (new document: {})", url, new_document);
+                }
+            },
+            Event::MainEventsCleared => match panes.poll_drag() {
+                Ok(Some(drop)) => println!("Dropped from {} to {}", drop.from_tag, drop.to_tag),
+                Ok(None) => (),
+                Err(err) => println!("Drop failed: {}", err),
             },
             _ => (),
         }
+
+        // Saves the window states and ends the trace once their time is up
+        if *control_flow == ControlFlow::Wait {
+            *control_flow = scheduler.control_flow();
+        }
+
+        // The drag gives the event loop no events, follow it until the release
+        if panes.is_dragging() {
+            *control_flow = ControlFlow::WaitUntil(Instant::now() + Duration::from_millis(16));
+        }
     });
 }
@@ -0,0 +1,96 @@
+//! Policy for the browser shortcuts such as F5, F12 and Ctrl+P
+
+use winapi::um::winuser::{GetKeyState, VK_CONTROL, VK_MENU, VK_SHIFT};
+
+/// Virtual key code with the modifiers
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Accelerator {
+    pub key: u32,
+    pub ctrl: bool,
+    pub shift: bool,
+    pub alt: bool,
+}
+
+impl Accelerator {
+    /// Key without modifiers, e.g. `Accelerator::new(VK_F5 as u32)`
+    pub fn new(key: u32) -> Self {
+        Accelerator {
+            key,
+            ctrl: false,
+            shift: false,
+            alt: false,
+        }
+    }
+
+    pub fn ctrl(mut self) -> Self {
+        self.ctrl = true;
+        self
+    }
+
+    pub fn shift(mut self) -> Self {
+        self.shift = true;
+        self
+    }
+
+    pub fn alt(mut self) -> Self {
+        self.alt = true;
+        self
+    }
+
+    /// Key with the modifiers currently held down
+    pub(crate) fn pressed(key: u32) -> Self {
+        let is_down = |vk| unsafe { GetKeyState(vk) < 0 };
+        Accelerator {
+            key,
+            ctrl: is_down(VK_CONTROL),
+            shift: is_down(VK_SHIFT),
+            alt: is_down(VK_MENU),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum AcceleratorAction<T> {
+    /// Suppress the key
+    Handled,
+
+    /// Let the browser handle the key, e.g. F5 reloads
+    PassToBrowser,
+
+    /// Suppress the key and send the event to the event loop
+    ForwardToEventLoop(T),
+}
+
+/// Actions for the accelerator keys, keys not listed are passed to the
+/// browser
+///
+/// Key repeats and keys pressed while an input element has the focus follow
+/// the same policy.
+#[derive(Clone, Debug)]
+pub struct AcceleratorPolicy<T> {
+    keys: Vec<(Accelerator, AcceleratorAction<T>)>,
+}
+
+impl<T> AcceleratorPolicy<T> {
+    pub fn new() -> Self {
+        AcceleratorPolicy { keys: Vec::new() }
+    }
+
+    pub fn key(mut self, accelerator: Accelerator, action: AcceleratorAction<T>) -> Self {
+        self.keys.push((accelerator, action));
+        self
+    }
+
+    pub(crate) fn action(&self, accelerator: &Accelerator) -> Option<&AcceleratorAction<T>> {
+        self.keys
+            .iter()
+            .find(|(a, _)| a == accelerator)
+            .map(|(_, action)| action)
+    }
+}
+
+impl<T> Default for AcceleratorPolicy<T> {
+    fn default() -> Self {
+        AcceleratorPolicy::new()
+    }
+}
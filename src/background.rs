@@ -0,0 +1,148 @@
+//! Default background color of the controller
+//!
+//! This version of WebView2 has no `ICoreWebView2Controller2`, instead the
+//! runtime reads the default background color from an environment variable
+//! when the controller is created. The variable is process-wide, so while a
+//! controller with a color is being created the other creations wait, also
+//! the ones without a color, and the previous value is restored once it's
+//! created.
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::ffi::OsString;
+
+const VAR: &str = "WEBVIEW2_DEFAULT_BACKGROUND_COLOR";
+
+type Creation = Box<dyn FnOnce(Option<Override>)>;
+
+#[derive(Default)]
+struct Gate {
+    busy: bool,
+    waiting: VecDeque<(Option<(u8, u8, u8, u8)>, Creation)>,
+}
+
+thread_local! {
+    static GATE: RefCell<Gate> = RefCell::new(Gate::default());
+}
+
+/// Color in effect until dropped, e.g. once the controller is created or
+/// its creation failed
+pub(crate) struct Override {
+    previous: Option<OsString>,
+}
+
+impl Drop for Override {
+    fn drop(&mut self) {
+        match self.previous.take() {
+            Some(previous) => std::env::set_var(VAR, previous),
+            None => std::env::remove_var(VAR),
+        }
+        GATE.with(|gate| gate.borrow_mut().busy = false);
+
+        // The waiting ones until the next color takes the gate
+        loop {
+            let next = GATE.with(|gate| {
+                let mut gate = gate.borrow_mut();
+                if gate.busy {
+                    None
+                } else {
+                    gate.waiting.pop_front()
+                }
+            });
+            match next {
+                Some((color, create)) => start(color, create),
+                None => break,
+            }
+        }
+    }
+}
+
+/// Start creating the controller with the color, now or once the creation
+/// with another color is done
+///
+/// `create` gets the override to hold until its controller is created.
+pub(crate) fn create(
+    color: Option<(u8, u8, u8, u8)>,
+    create: impl FnOnce(Option<Override>) + 'static,
+) {
+    let create: Creation = Box::new(create);
+    let create = GATE.with(|gate| {
+        let mut gate = gate.borrow_mut();
+        if gate.busy {
+            gate.waiting.push_back((color, create));
+            None
+        } else {
+            Some(create)
+        }
+    });
+    if let Some(create) = create {
+        start(color, create);
+    }
+}
+
+fn start(color: Option<(u8, u8, u8, u8)>, create: Creation) {
+    let (r, g, b, a) = match color {
+        Some(color) => color,
+        None => return create(None),
+    };
+    GATE.with(|gate| gate.borrow_mut().busy = true);
+    let previous = std::env::var_os(VAR);
+    std::env::set_var(VAR, format!("{:02X}{:02X}{:02X}{:02X}", a, r, g, b));
+    create(Some(Override { previous }));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    // The variable is process-wide, one test touches it
+    #[test]
+    fn creations_wait_for_the_color_and_restore_it() {
+        std::env::set_var(VAR, "FF000000");
+        let held: Rc<RefCell<Vec<Override>>> = Rc::default();
+        let log: Rc<RefCell<Vec<(&str, Option<String>)>>> = Rc::default();
+        let record = |name: &'static str| {
+            let held = held.clone();
+            let log = log.clone();
+            move |o: Option<Override>| {
+                let var = std::env::var(VAR).ok();
+                log.borrow_mut().push((name, var));
+                held.borrow_mut().extend(o);
+            }
+        };
+
+        create(Some((1, 2, 3, 255)), record("red"));
+        create(None, record("plain"));
+        create(Some((4, 5, 6, 0)), record("transparent"));
+        create(None, record("plain after"));
+        assert_eq!(*log.borrow(), vec![("red", Some("FF010203".into()))]);
+
+        // Red is created, the next ones run until the transparent one
+        let red = held.borrow_mut().remove(0);
+        drop(red);
+        assert_eq!(
+            log.borrow()[1..],
+            [
+                ("plain", Some("FF000000".into())),
+                ("transparent", Some("00040506".into())),
+            ]
+        );
+
+        let transparent = held.borrow_mut().remove(0);
+        drop(transparent);
+        assert_eq!(log.borrow()[3], ("plain after", Some("FF000000".into())));
+        assert_eq!(std::env::var(VAR).ok().as_deref(), Some("FF000000"));
+
+        // Without the waiting ones nothing is queued
+        let ran = Rc::new(Cell::new(false));
+        let ran_ = ran.clone();
+        create(None, move |o| {
+            assert!(o.is_none());
+            ran_.set(true);
+        });
+        assert!(ran.get());
+        std::env::remove_var(VAR);
+    }
+}
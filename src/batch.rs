@@ -0,0 +1,233 @@
+//! Window events of a frame folded to one update of the controller, see
+//! `WebViewWrapper::handle_window_events`
+
+use crate::layout::LayoutTrigger;
+use crate::{Error, EventBatchOutcome};
+use winit::dpi::PhysicalSize;
+use winit::event::WindowEvent;
+use winit::window::Theme;
+
+/// Controller calls of the changes, the webview or a stub in the tests
+pub(crate) trait Dispatch {
+    fn moved(&mut self) -> Result<(), Error>;
+    fn resize(&mut self, trigger: LayoutTrigger, size: PhysicalSize<u32>) -> Result<(), Error>;
+    fn theme(&mut self, theme: Theme) -> Result<(), Error>;
+    fn focus(&mut self) -> Result<(), Error>;
+}
+
+#[derive(Default)]
+pub(crate) struct Batch {
+    pub outcome: EventBatchOutcome,
+    pub moved: bool,
+
+    // Only the last size counts
+    pub size: Option<(LayoutTrigger, PhysicalSize<u32>)>,
+    pub focused: Option<bool>,
+    pub theme: Option<Theme>,
+}
+
+impl Batch {
+    /// Event of the window of the webview
+    pub fn push(&mut self, event: &WindowEvent) {
+        self.outcome.events += 1;
+        match event {
+            WindowEvent::Moved(_) => self.moved = true,
+            WindowEvent::Focused(f) => self.focused = Some(*f),
+            WindowEvent::Resized(size) => self.size = Some((LayoutTrigger::Resized, *size)),
+            WindowEvent::ScaleFactorChanged { new_inner_size, .. } => {
+                self.size = Some((LayoutTrigger::ScaleFactorChanged, **new_inner_size));
+            }
+            WindowEvent::ThemeChanged(t) => self.theme = Some(*t),
+            WindowEvent::CloseRequested => self.outcome.close_requested = true,
+            _ => (),
+        }
+    }
+
+    /// Update the controller once for each kind of change
+    pub fn dispatch(&self, target: &mut impl Dispatch, focus_webview: bool) -> Result<(), Error> {
+        if self.moved {
+            target.moved()?;
+        }
+        if let Some((trigger, size)) = self.size {
+            target.resize(trigger, size)?;
+        }
+        if let Some(theme) = self.theme {
+            target.theme(theme)?;
+        }
+        if self.focused == Some(true) && focus_webview {
+            target.focus()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+    use winit::dpi::PhysicalPosition;
+
+    // Dragging the top left corner for a second at 60 Hz, both moves and
+    // resizes
+    fn resize_drag() -> Vec<WindowEvent<'static>> {
+        (0..60)
+            .flat_map(|i| {
+                vec![
+                    WindowEvent::Moved(PhysicalPosition::new(100 - i, 100 - i)),
+                    WindowEvent::Resized(PhysicalSize::new(800 + i as u32, 600 + i as u32)),
+                ]
+            })
+            .collect()
+    }
+
+    fn fold<'a>(events: impl IntoIterator<Item = &'a WindowEvent<'a>>) -> Batch {
+        let mut batch = Batch::default();
+        for event in events {
+            batch.push(event);
+        }
+        batch
+    }
+
+    /// Counts the controller calls, the bounds go through the same
+    /// `BoundsMode::rect` as in the webview
+    #[derive(Default)]
+    struct Stub {
+        calls: usize,
+        put_bounds: usize,
+        bounds: Option<crate::layout::Rect>,
+    }
+
+    impl Dispatch for Stub {
+        fn moved(&mut self) -> Result<(), Error> {
+            self.calls += 1;
+            Ok(())
+        }
+
+        fn resize(&mut self, _: LayoutTrigger, size: PhysicalSize<u32>) -> Result<(), Error> {
+            self.calls += 1;
+            self.put_bounds += 1;
+            self.bounds = Some(crate::BoundsMode::FillClient.rect(size));
+            Ok(())
+        }
+
+        fn theme(&mut self, _: Theme) -> Result<(), Error> {
+            self.calls += 1;
+            Ok(())
+        }
+
+        fn focus(&mut self) -> Result<(), Error> {
+            self.calls += 1;
+            Ok(())
+        }
+    }
+
+    // Number of the controller calls
+    fn updates(batch: &Batch) -> usize {
+        let mut stub = Stub::default();
+        batch.dispatch(&mut stub, true).unwrap();
+        stub.calls
+    }
+
+    #[test]
+    fn only_the_last_size_counts() {
+        let batch = fold(&resize_drag());
+        assert_eq!(batch.outcome.events, 120);
+        assert!(batch.moved);
+        assert_eq!(
+            batch.size,
+            Some((LayoutTrigger::Resized, PhysicalSize::new(859, 659)))
+        );
+        assert!(!batch.outcome.close_requested);
+    }
+
+    #[test]
+    fn resize_drag_is_two_updates_instead_of_one_per_event() {
+        let events = resize_drag();
+        let per_event: usize = events.iter().map(|e| updates(&fold(Some(e)))).sum();
+        assert_eq!(per_event, 120);
+        assert_eq!(updates(&fold(&events)), 2);
+    }
+
+    #[test]
+    fn scale_factor_change_after_resize_wins() {
+        let mut new_inner_size = PhysicalSize::new(1200, 900);
+        let events = [
+            WindowEvent::Resized(PhysicalSize::new(800, 600)),
+            WindowEvent::ScaleFactorChanged {
+                scale_factor: 1.5,
+                new_inner_size: &mut new_inner_size,
+            },
+        ];
+        assert_eq!(
+            fold(&events).size,
+            Some((
+                LayoutTrigger::ScaleFactorChanged,
+                PhysicalSize::new(1200, 900)
+            ))
+        );
+    }
+
+    #[test]
+    fn close_and_focus_are_kept() {
+        let events = [
+            WindowEvent::Focused(true),
+            WindowEvent::CloseRequested,
+            WindowEvent::Focused(false),
+        ];
+        let batch = fold(&events);
+        assert!(batch.outcome.close_requested);
+        assert_eq!(batch.focused, Some(false));
+        assert_eq!(updates(&batch), 0);
+    }
+
+    #[test]
+    fn batch_puts_the_bounds_of_the_last_size_once() {
+        let events = resize_drag();
+        let mut per_event = Stub::default();
+        for event in &events {
+            fold(Some(event)).dispatch(&mut per_event, true).unwrap();
+        }
+        let mut batched = Stub::default();
+        fold(&events).dispatch(&mut batched, true).unwrap();
+        assert_eq!(per_event.put_bounds, 60);
+        assert_eq!(batched.put_bounds, 1);
+        assert_eq!(per_event.bounds, batched.bounds);
+    }
+
+    // Dispatch through the stub, the controller calls cross to the browser
+    // process and cost more than this, the counts are what carry over
+    //
+    // cargo test --release -- --ignored --nocapture
+    #[test]
+    #[ignore]
+    fn benchmark_resize_drag() {
+        let events = resize_drag();
+        let rounds = 10_000;
+        let mut per_event = Stub::default();
+        let start = Instant::now();
+        for _ in 0..rounds {
+            for event in &events {
+                fold(Some(event)).dispatch(&mut per_event, true).unwrap();
+            }
+        }
+        let per_event_time = start.elapsed();
+        let mut batched = Stub::default();
+        let start = Instant::now();
+        for _ in 0..rounds {
+            fold(&events).dispatch(&mut batched, true).unwrap();
+        }
+        let batched_time = start.elapsed();
+        println!(
+            "{} events: per event {:?} with {} controller calls ({} put_bounds), \
+             batched {:?} with {} ({})",
+            events.len(),
+            per_event_time / rounds,
+            per_event.calls / rounds as usize,
+            per_event.put_bounds / rounds as usize,
+            batched_time / rounds,
+            batched.calls / rounds as usize,
+            batched.put_bounds / rounds as usize,
+        );
+        assert!(batched.calls < per_event.calls);
+    }
+}
@@ -0,0 +1,34 @@
+//! Capturing the page to an image file
+//!
+//! The capture uses the `CapturePreview` of the webview, it captures the
+//! visible part of the page to a memory stream that is then written to the
+//! file.
+
+use crate::Error;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+use webview2::CapturePreviewImageFormat;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum ImageFormat {
+    Png,
+    Jpeg,
+}
+
+impl From<ImageFormat> for CapturePreviewImageFormat {
+    fn from(format: ImageFormat) -> Self {
+        match format {
+            ImageFormat::Png => CapturePreviewImageFormat::PNG,
+            ImageFormat::Jpeg => CapturePreviewImageFormat::JPEG,
+        }
+    }
+}
+
+/// Write the captured image from the start of the stream to the file
+pub(crate) fn write(mut stream: webview2::Stream, path: &Path) -> Result<(), Error> {
+    stream.seek(SeekFrom::Start(0))?;
+    let mut bytes = Vec::new();
+    stream.read_to_end(&mut bytes)?;
+    std::fs::write(path, bytes)?;
+    Ok(())
+}
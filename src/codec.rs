@@ -0,0 +1,167 @@
+//! Format of the messages between the host and the page
+
+use crate::strict;
+use serde::de::DeserializeOwned;
+
+/// Encodes the `MsgToWebView` and decodes the `MsgFromWebView` messages,
+/// by default as JSON
+///
+/// The messages pass through as `serde_json::Value`, e.g. a MessagePack
+/// codec would encode the value to the bytes and those to base64. The errors
+/// of the other formats can be made with `serde::de::Error::custom`. The
+/// calls of `WebViewWrapper::call` and their replies use the codec too, only
+/// the messages of the built-in shims stay JSON.
+///
+/// The object keys of the values keep their order, the default JSON is the
+/// same as `serde_json::to_string` of the message.
+pub trait MessageCodec {
+    fn encode(&self, msg: serde_json::Value) -> Result<String, serde_json::Error>;
+    fn decode(&self, msg: &str) -> Result<serde_json::Value, serde_json::Error>;
+
+    /// Post the encoded messages with `post_web_message_as_json`, the page
+    /// gets them parsed, otherwise with `post_web_message_as_string`
+    fn is_json(&self) -> bool {
+        false
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default)]
+pub struct JsonCodec;
+
+/// Decode the message from the page, the failure goes to the
+/// `message_error` handler or else to the policy
+pub(crate) fn decode_forgiving<M: DeserializeOwned>(
+    codec: &dyn MessageCodec,
+    message: String,
+    message_error_fn: Option<fn(String, serde_json::Error)>,
+    policy: strict::Policy,
+) -> Option<M> {
+    let msg = codec.decode(&message).and_then(serde_json::from_value::<M>);
+    match msg {
+        Ok(msg) => Some(msg),
+        Err(err) => {
+            if let Some(message_error_fn) = message_error_fn {
+                message_error_fn(message, err);
+            } else {
+                policy.fail(
+                    &format!("Webview gave unparseable result: {:?}", message),
+                    &err,
+                );
+            }
+            None
+        }
+    }
+}
+
+impl MessageCodec for JsonCodec {
+    fn encode(&self, msg: serde_json::Value) -> Result<String, serde_json::Error> {
+        serde_json::to_string(&msg)
+    }
+
+    fn decode(&self, msg: &str) -> Result<serde_json::Value, serde_json::Error> {
+        serde_json::from_str(msg)
+    }
+
+    fn is_json(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::{Deserialize, Serialize};
+    use std::collections::BTreeMap;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    #[serde(tag = "type")]
+    enum Msg {
+        Update {
+            zebra: u32,
+            apple: String,
+            middle: Vec<f64>,
+            nested: Nested,
+        },
+        Empty,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Nested {
+        y: Option<bool>,
+        b: BTreeMap<String, i64>,
+    }
+
+    fn msg() -> Msg {
+        Msg::Update {
+            zebra: 1,
+            apple: "a\"b\u{e4}".into(),
+            middle: vec![1.5, 2.0],
+            nested: Nested {
+                y: None,
+                b: vec![("k".to_string(), -2)].into_iter().collect(),
+            },
+        }
+    }
+
+    #[test]
+    fn json_is_the_same_bytes_as_to_string() {
+        for m in &[msg(), Msg::Empty] {
+            let encoded = JsonCodec.encode(serde_json::to_value(m).unwrap()).unwrap();
+            assert_eq!(encoded, serde_json::to_string(m).unwrap());
+        }
+        assert_eq!(
+            JsonCodec
+                .encode(serde_json::to_value(&msg()).unwrap())
+                .unwrap(),
+            r#"{"type":"Update","zebra":1,"apple":"a\"bä","middle":[1.5,2.0],"nested":{"y":null,"b":{"k":-2}}}"#
+        );
+    }
+
+    #[test]
+    fn json_decodes_like_from_str() {
+        let json = serde_json::to_string(&msg()).unwrap();
+        let decoded: Msg = serde_json::from_value(JsonCodec.decode(&json).unwrap()).unwrap();
+        assert_eq!(decoded, serde_json::from_str::<Msg>(&json).unwrap());
+        assert!(JsonCodec.decode("{").is_err());
+    }
+
+    #[test]
+    fn strict_decode_panics_on_unparseable_message() {
+        let message = strict::panic_message(|| {
+            decode_forgiving::<Msg>(&JsonCodec, "{".into(), None, strict::Policy::new(true));
+        })
+        .unwrap();
+        assert!(message.starts_with("Webview gave unparseable result: \"{\""));
+    }
+
+    #[test]
+    fn lenient_decode_drops_unparseable_message() {
+        let policy = strict::Policy::new(false);
+        assert_eq!(
+            decode_forgiving::<Msg>(&JsonCodec, "{".into(), None, policy),
+            None
+        );
+        assert_eq!(
+            decode_forgiving::<Msg>(&JsonCodec, r#"{"type":"Empty"}"#.into(), None, policy),
+            Some(Msg::Empty)
+        );
+    }
+
+    #[test]
+    fn message_error_handler_gets_unparseable_message() {
+        static HANDLED: AtomicBool = AtomicBool::new(false);
+        fn handler(message: String, _: serde_json::Error) {
+            assert_eq!(message, r#"{"type":"Unknown"}"#);
+            HANDLED.store(true, Ordering::SeqCst);
+        }
+        let decoded = decode_forgiving::<Msg>(
+            &JsonCodec,
+            r#"{"type":"Unknown"}"#.into(),
+            Some(handler),
+            strict::Policy::new(true),
+        );
+        assert_eq!(decoded, None);
+        assert!(HANDLED.load(Ordering::SeqCst));
+    }
+}
@@ -0,0 +1,71 @@
+//! Blocking message boxes for the errors that leave the app without any UI
+//!
+//! Safe to call before the event loop runs and from the callbacks. These use
+//! `MessageBoxW`, the `TaskDialog` would need the common controls v6 manifest
+//! in the app.
+
+use crate::Error;
+use std::iter::once;
+use std::ptr::null_mut;
+use winapi::um::winuser::{
+    MessageBoxW, IDRETRY, IDYES, MB_ICONERROR, MB_ICONWARNING, MB_OK, MB_RETRYCANCEL, MB_YESNO,
+};
+
+/// Download page of the Evergreen WebView2 Runtime bootstrapper
+pub const RUNTIME_DOWNLOAD_URL: &str = "https://go.microsoft.com/fwlink/p/?LinkId=2124703";
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum RetryOrQuit {
+    Retry,
+    Quit,
+}
+
+/// Show the error, blocks until closed
+pub fn error_box(title: &str, body: &str) {
+    message_box(title, body, MB_OK | MB_ICONERROR);
+}
+
+/// Ask whether to download the runtime, see `RUNTIME_DOWNLOAD_URL`
+pub fn ask_install_runtime() -> bool {
+    message_box(
+        "WebView2 Runtime missing",
+        "This app needs the Microsoft Edge WebView2 Runtime, which is not installed. \
+         Do you want to download it now?",
+        MB_YESNO | MB_ICONWARNING,
+    ) == IDYES
+}
+
+/// Ask to retry or to quit with the default text of the error, see
+/// `ask_retry_or_quit_with` for own texts
+pub fn ask_retry_or_quit(err: &Error) -> RetryOrQuit {
+    ask_retry_or_quit_with("Error", &error_text(err))
+}
+
+pub fn ask_retry_or_quit_with(title: &str, body: &str) -> RetryOrQuit {
+    match message_box(title, body, MB_RETRYCANCEL | MB_ICONERROR) {
+        IDRETRY => RetryOrQuit::Retry,
+        _ => RetryOrQuit::Quit,
+    }
+}
+
+/// Default text for the error
+pub fn error_text(err: &Error) -> String {
+    match err.kind() {
+        Error::RuntimeNotInstalled => {
+            "The Microsoft Edge WebView2 Runtime is not installed.".to_owned()
+        }
+        Error::EnvironmentFailed => "The WebView2 Runtime could not be started.".to_owned(),
+        Error::WebView2Error(err) => format!(
+            "The WebView2 Runtime failed with the error 0x{:08X}.",
+            err.hresult() as u32
+        ),
+        Error::WindowBuildError(_) => "The window could not be created.".to_owned(),
+        err => format!("Unexpected error: {:?}", err),
+    }
+}
+
+fn message_box(title: &str, body: &str, flags: u32) -> i32 {
+    let title: Vec<u16> = title.encode_utf16().chain(once(0)).collect();
+    let body: Vec<u16> = body.encode_utf16().chain(once(0)).collect();
+    unsafe { MessageBoxW(null_mut(), body.as_ptr(), title.as_ptr(), flags) }
+}
@@ -0,0 +1,294 @@
+//! Drags of the app's items between the webviews of a `WebViewManager`, see
+//! `WebViewBuilder::cross_window_drag`
+//!
+//! This is the app's own protocol, not the OLE drag and drop: the payload is
+//! the page's JSON, no files or text, and the page draws its own ghost image.
+//! `window.host.dnd.startDrag(payload)` of the shim sends
+//! `{"__dnd": {"start": payload}}`. The webview of the drag holds the mouse
+//! capture, so the other windows don't get the release, instead
+//! `WebViewManager::poll_drag` reads the button and the cursor from the
+//! system, in physical pixels. Released over another webview of the manager
+//! the page of that webview gets `window.host.dnd.drop(payload)`, posted as
+//! `{"__dnd": {"drop": payload}}`. Escape, or the release outside the other
+//! webviews of the manager, cancels the drag.
+
+use crate::middleware::{InboundCtx, MessageMiddleware, MiddlewareAction};
+use crate::{drag, strict, Rect};
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::rc::Rc;
+use winapi::shared::windef::{HWND, POINT};
+use winapi::um::winuser::{GetAncestor, GetAsyncKeyState, GetCursorPos, WindowFromPoint};
+use winapi::um::winuser::{GA_ROOT, VK_ESCAPE};
+
+/// Drop of a drag on another webview of the `WebViewManager`, with their
+/// names in the manager
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct CrossWindowDrop {
+    pub from_tag: String,
+    pub to_tag: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct Envelope {
+    #[serde(rename = "__dnd")]
+    pub message: Message,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Message {
+    Start(serde_json::Value),
+    Drop(serde_json::Value),
+}
+
+pub(crate) const SCRIPT: &str = r#"(() => {
+    const host = window.host = window.host || {};
+    host.dnd = {
+        startDrag: payload => window.chrome.webview.postMessage(
+            JSON.stringify({ __dnd: { start: payload } })
+        ),
+        drop: () => {},
+    };
+    window.chrome.webview.addEventListener("message", e => {
+        const message = e.data && e.data.__dnd;
+        if (message && "drop" in message) {
+            window.host.dnd.drop(message.drop);
+        }
+    });
+})();"#;
+
+/// Drag in progress, from the named webview
+pub(crate) struct Drag {
+    pub from: String,
+    pub payload: serde_json::Value,
+}
+
+/// Button, Escape and cursor of the drag
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct Input {
+    pub button_down: bool,
+    pub escape: bool,
+
+    /// Screen position in physical pixels
+    pub cursor: (i32, i32),
+}
+
+impl Input {
+    pub fn read() -> Self {
+        let mut point = POINT { x: 0, y: 0 };
+        unsafe { GetCursorPos(&mut point) };
+        Input {
+            button_down: drag::primary_button_down(),
+            escape: unsafe { GetAsyncKeyState(VK_ESCAPE) as u16 & 0x8000 != 0 },
+            cursor: (point.x, point.y),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub(crate) enum Outcome {
+    Dragging,
+    Dropped(String),
+    Cancelled,
+}
+
+/// Next state of the drag, `target` is the webview under the cursor
+pub(crate) fn step(from: &str, input: &Input, target: Option<&str>) -> Outcome {
+    if input.escape {
+        return Outcome::Cancelled;
+    }
+    if input.button_down {
+        return Outcome::Dragging;
+    }
+    match target {
+        Some(target) if target != from => Outcome::Dropped(target.to_owned()),
+
+        // The page of the drag handles its own drops
+        _ => Outcome::Cancelled,
+    }
+}
+
+/// First of the rectangles containing the point, the rectangles are in
+/// screen coordinates
+pub(crate) fn hit<'a>(point: (i32, i32), targets: &[(&'a str, Rect)]) -> Option<&'a str> {
+    let (x, y) = point;
+    targets
+        .iter()
+        .find(|(_, r)| x >= r.left && x < r.right && y >= r.top && y < r.bottom)
+        .map(|(name, _)| *name)
+}
+
+/// Top-level window at the screen point, the windows below it are covered
+pub(crate) fn root_window_at(point: (i32, i32)) -> HWND {
+    let (x, y) = point;
+    unsafe { GetAncestor(WindowFromPoint(POINT { x, y }), GA_ROOT) }
+}
+
+/// Built-in middleware taking the starts of the drags
+pub(crate) struct DragStart {
+    pub started: Rc<RefCell<Option<serde_json::Value>>>,
+    pub max_bytes: usize,
+    pub policy: strict::Policy,
+}
+
+impl MessageMiddleware for DragStart {
+    fn inbound(&self, msg: InboundCtx) -> MiddlewareAction<InboundCtx> {
+        let payload = match serde_json::from_str::<Envelope>(&msg.message) {
+            Ok(Envelope {
+                message: Message::Start(payload),
+            }) => payload,
+            _ => return MiddlewareAction::Continue(msg),
+        };
+        if msg.message.len() > self.max_bytes {
+            self.policy
+                .fail("Drag payload was too large", &msg.message.len());
+            return MiddlewareAction::Consume;
+        }
+        *self.started.borrow_mut() = Some(payload);
+        MiddlewareAction::Consume
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn input(button_down: bool, escape: bool) -> Input {
+        Input {
+            button_down,
+            escape,
+            cursor: (0, 0),
+        }
+    }
+
+    fn rect(left: i32, top: i32, right: i32, bottom: i32) -> Rect {
+        Rect {
+            left,
+            top,
+            right,
+            bottom,
+        }
+    }
+
+    #[test]
+    fn held_button_keeps_dragging() {
+        assert_eq!(step("a", &input(true, false), Some("b")), Outcome::Dragging);
+    }
+
+    #[test]
+    fn release_over_another_webview_drops() {
+        assert_eq!(
+            step("a", &input(false, false), Some("b")),
+            Outcome::Dropped("b".into())
+        );
+    }
+
+    #[test]
+    fn release_outside_or_over_the_source_cancels() {
+        assert_eq!(step("a", &input(false, false), None), Outcome::Cancelled);
+        assert_eq!(
+            step("a", &input(false, false), Some("a")),
+            Outcome::Cancelled
+        );
+    }
+
+    #[test]
+    fn escape_cancels_while_held() {
+        assert_eq!(step("a", &input(true, true), Some("b")), Outcome::Cancelled);
+    }
+
+    #[test]
+    fn hit_excludes_the_right_and_bottom_edges() {
+        let targets = [
+            ("left", rect(0, 0, 100, 50)),
+            ("right", rect(100, 0, 300, 50)),
+        ];
+        assert_eq!(hit((0, 0), &targets), Some("left"));
+        assert_eq!(hit((99, 49), &targets), Some("left"));
+        assert_eq!(hit((100, 0), &targets), Some("right"));
+        assert_eq!(hit((300, 0), &targets), None);
+        assert_eq!(hit((50, 50), &targets), None);
+        assert_eq!(hit((-1, 10), &targets), None);
+    }
+
+    #[test]
+    fn hit_takes_the_first_of_the_overlapping() {
+        let targets = [
+            ("top", rect(0, 0, 100, 100)),
+            ("below", rect(50, 50, 150, 150)),
+        ];
+        assert_eq!(hit((75, 75), &targets), Some("top"));
+        assert_eq!(hit((125, 125), &targets), Some("below"));
+    }
+
+    #[test]
+    fn hit_on_another_monitor_left_of_the_primary() {
+        let targets = [("window", rect(-1920, 100, -1000, 700))];
+        assert_eq!(hit((-1500, 400), &targets), Some("window"));
+    }
+
+    #[test]
+    fn messages_of_the_shim() {
+        let drop = Envelope {
+            message: Message::Drop(json!({"id": 1})),
+        };
+        assert_eq!(
+            serde_json::to_string(&drop).unwrap(),
+            r#"{"__dnd":{"drop":{"id":1}}}"#
+        );
+        let start: Envelope = serde_json::from_str(r#"{"__dnd":{"start":[1,2]}}"#).unwrap();
+        assert_eq!(start.message, Message::Start(json!([1, 2])));
+    }
+
+    fn start(policy: strict::Policy) -> DragStart {
+        DragStart {
+            started: Rc::new(RefCell::new(None)),
+            max_bytes: 64,
+            policy,
+        }
+    }
+
+    fn message(payload: serde_json::Value) -> InboundCtx {
+        InboundCtx {
+            message: json!({ "__dnd": { "start": payload } }).to_string(),
+        }
+    }
+
+    #[test]
+    fn start_is_taken() {
+        let start = start(strict::Policy::new(true));
+        assert!(matches!(
+            start.inbound(message(json!({"id": 1}))),
+            MiddlewareAction::Consume
+        ));
+        assert_eq!(*start.started.borrow(), Some(json!({"id": 1})));
+        assert!(matches!(
+            start.inbound(InboundCtx {
+                message: r#"{"type":"Other"}"#.into()
+            }),
+            MiddlewareAction::Continue(_)
+        ));
+    }
+
+    #[test]
+    fn strict_too_large_payload_panics() {
+        let start = start(strict::Policy::new(true));
+        let message = strict::panic_message(|| {
+            start.inbound(message(json!("x".repeat(64))));
+        })
+        .unwrap();
+        assert!(message.starts_with("Drag payload was too large"));
+    }
+
+    #[test]
+    fn lenient_too_large_payload_is_dropped() {
+        let start = start(strict::Policy::new(false));
+        assert!(matches!(
+            start.inbound(message(json!("x".repeat(64)))),
+            MiddlewareAction::Consume
+        ));
+        assert_eq!(*start.started.borrow(), None);
+    }
+}
@@ -0,0 +1,407 @@
+//! Downloads of the page, see `WebViewBuilder::downloads`
+//!
+//! This version of WebView2 has no download events, the devtools protocol
+//! `Browser.setDownloadBehavior` saves the downloads to the staging folder
+//! under their ids instead of the browser's download UI. The
+//! `Browser.downloadWillBegin` and `Browser.downloadProgress` events report
+//! them, and the complete file is moved to the decided path.
+//!
+//! Without the decisions, `WebViewBuilder::download_folder` only sets the
+//! folder with the `allow` behavior, the files keep their suggested names.
+
+use crate::strict;
+use crate::Error;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use winit::event_loop::EventLoopProxy;
+
+/// Download the page is starting
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct DownloadRequest {
+    pub url: String,
+
+    /// File name from the response or the url, e.g. `report.pdf`
+    pub suggested_filename: String,
+}
+
+/// What to do with a download, see `WebViewBuilder::downloads`
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum DownloadDecision {
+    /// Save the file to the path, an existing file is replaced
+    SaveAs(PathBuf),
+    Cancel,
+
+    /// Cancel the download and report it with `DownloadEvent::Reported`, e.g.
+    /// for the host to download it with its own client
+    Report,
+}
+
+/// Progress of the downloads, the id tells the concurrent downloads apart
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum DownloadEvent {
+    Started {
+        id: String,
+        url: String,
+        path: PathBuf,
+    },
+
+    /// Total is zero when the size is not known
+    Progress {
+        id: String,
+        received_bytes: u64,
+        total_bytes: u64,
+    },
+    Completed {
+        id: String,
+        path: PathBuf,
+    },
+
+    /// Cancelled by the browser, e.g. the connection was lost
+    Canceled {
+        id: String,
+    },
+
+    /// Downloaded, but the file couldn't be moved to the path
+    Failed {
+        id: String,
+    },
+    Reported {
+        url: String,
+        suggested_filename: String,
+    },
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct BehaviorParams<'a> {
+    behavior: &'static str,
+    download_path: &'a Path,
+    events_enabled: bool,
+}
+
+#[derive(Deserialize)]
+struct ProtocolError {
+    code: i64,
+    message: String,
+}
+
+#[derive(Serialize)]
+struct CancelParams<'a> {
+    guid: &'a str,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct WillBegin {
+    guid: String,
+    url: String,
+    suggested_filename: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Progress {
+    guid: String,
+    total_bytes: f64,
+    received_bytes: f64,
+    state: String,
+}
+
+/// Take over the downloads of the webview
+pub(crate) fn register<E: 'static>(
+    webview: &webview2::WebView,
+    staging: &Path,
+    decide: fn(&DownloadRequest) -> DownloadDecision,
+    event: fn(DownloadEvent) -> E,
+    proxy: EventLoopProxy<E>,
+    policy: strict::Policy,
+) -> Result<(), Error> {
+    std::fs::create_dir_all(staging)?;
+
+    // Decided paths of the downloads in progress
+    let saving = Rc::new(RefCell::new(HashMap::<String, PathBuf>::new()));
+
+    let saving_ = saving.clone();
+    let proxy_ = proxy.clone();
+    webview
+        .get_dev_tools_protocol_event_receiver("Browser.downloadWillBegin")?
+        .add_dev_tools_protocol_event_received(move |webview, args| {
+            let result = (|| -> Result<(), Error> {
+                let begin: WillBegin = serde_json::from_str(&args.get_parameter_object_as_json()?)?;
+                let request = DownloadRequest {
+                    url: begin.url,
+                    suggested_filename: begin.suggested_filename,
+                };
+                let path = match decide(&request) {
+                    DownloadDecision::SaveAs(path) => path,
+                    decision => {
+                        let params = serde_json::to_string(&CancelParams { guid: &begin.guid })?;
+                        webview.call_dev_tools_protocol_method(
+                            "Browser.cancelDownload",
+                            &params,
+                            |_| Ok(()),
+                        )?;
+                        if decision == DownloadDecision::Report {
+                            let _ = proxy_.send_event(event(DownloadEvent::Reported {
+                                url: request.url,
+                                suggested_filename: request.suggested_filename,
+                            }));
+                        }
+                        return Ok(());
+                    }
+                };
+                saving_
+                    .borrow_mut()
+                    .insert(begin.guid.clone(), path.clone());
+                let _ = proxy_.send_event(event(DownloadEvent::Started {
+                    id: begin.guid,
+                    url: request.url,
+                    path,
+                }));
+                Ok(())
+            })();
+            policy.handler("Starting the download failed", result)
+        })?;
+
+    let staging_ = staging.to_owned();
+    webview
+        .get_dev_tools_protocol_event_receiver("Browser.downloadProgress")?
+        .add_dev_tools_protocol_event_received(move |_webview, args| {
+            let result = (|| -> Result<(), Error> {
+                let progress: Progress =
+                    serde_json::from_str(&args.get_parameter_object_as_json()?)?;
+
+                // Not decided to be saved, e.g. the cancelled ones
+                if !saving.borrow().contains_key(&progress.guid) {
+                    return Ok(());
+                }
+                let id = progress.guid;
+                let download_event = match progress.state.as_str() {
+                    "completed" => {
+                        let path = saving.borrow_mut().remove(&id).unwrap_or_default();
+                        completed(&staging_, id, path, policy)
+                    }
+                    "canceled" => {
+                        saving.borrow_mut().remove(&id);
+                        DownloadEvent::Canceled { id }
+                    }
+                    _ => DownloadEvent::Progress {
+                        id,
+                        received_bytes: progress.received_bytes as u64,
+                        total_bytes: progress.total_bytes as u64,
+                    },
+                };
+                let _ = proxy.send_event(event(download_event));
+                Ok(())
+            })();
+            policy.handler("Reporting the download failed", result)
+        })?;
+
+    set_behavior(webview, &staging_params(staging)?, policy)
+}
+
+/// Save the downloads of the webview to the folder without asking
+pub(crate) fn save_to(
+    webview: &webview2::WebView,
+    folder: &Path,
+    policy: strict::Policy,
+) -> Result<(), Error> {
+    std::fs::create_dir_all(folder)?;
+    set_behavior(webview, &folder_params(folder)?, policy)
+}
+
+/// Downloads named by their ids in the staging folder, with the events
+fn staging_params(staging: &Path) -> Result<String, serde_json::Error> {
+    serde_json::to_string(&BehaviorParams {
+        behavior: "allowAndName",
+        download_path: staging,
+        events_enabled: true,
+    })
+}
+
+/// Downloads under their suggested names in the folder, without the events
+fn folder_params(folder: &Path) -> Result<String, serde_json::Error> {
+    serde_json::to_string(&BehaviorParams {
+        behavior: "allow",
+        download_path: folder,
+        events_enabled: false,
+    })
+}
+
+// The `Browser` domain may be refused, e.g. on a page target, and then the
+// browser would keep showing its own download UI
+fn set_behavior(
+    webview: &webview2::WebView,
+    params: &str,
+    policy: strict::Policy,
+) -> Result<(), Error> {
+    webview.call_dev_tools_protocol_method("Browser.setDownloadBehavior", params, move |json| {
+        behavior_set(&json, policy)
+    })?;
+    Ok(())
+}
+
+/// Result of `Browser.setDownloadBehavior`, the refusal goes to the policy
+fn behavior_set(json: &str, policy: strict::Policy) -> Result<(), webview2::Error> {
+    policy.handler(
+        "Setting the download behavior failed",
+        protocol_result(json),
+    )
+}
+
+/// Error of the devtools method from its result, e.g. `{"code": -32601,
+/// "message": "'Browser.setDownloadBehavior' wasn't found"}`
+fn protocol_result(json: &str) -> Result<(), String> {
+    match serde_json::from_str::<ProtocolError>(json) {
+        Ok(err) => Err(format!("{} ({})", err.message, err.code)),
+        Err(_) => Ok(()),
+    }
+}
+
+/// Move the complete download from the staging folder, copied if the path is
+/// on another volume
+/// Move the completed download from the staging folder to its path, the
+/// failure goes to the policy and fails the download
+fn completed(staging: &Path, id: String, path: PathBuf, policy: strict::Policy) -> DownloadEvent {
+    match finish(&staging.join(&id), &path) {
+        Ok(()) => DownloadEvent::Completed { id, path },
+        Err(err) => {
+            policy.fail("Moving the download failed", &err);
+            DownloadEvent::Failed { id }
+        }
+    }
+}
+
+fn finish(staged: &Path, path: &Path) -> Result<(), Error> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    if std::fs::rename(staged, path).is_err() {
+        std::fs::copy(staged, path)?;
+        std::fs::remove_file(staged)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn staging_keeps_the_ids_and_sends_the_events() {
+        assert_eq!(
+            staging_params(Path::new("staging")).unwrap(),
+            r#"{"behavior":"allowAndName","downloadPath":"staging","eventsEnabled":true}"#
+        );
+    }
+
+    #[test]
+    fn folder_keeps_the_names_without_the_events() {
+        assert_eq!(
+            folder_params(Path::new("downloads")).unwrap(),
+            r#"{"behavior":"allow","downloadPath":"downloads","eventsEnabled":false}"#
+        );
+    }
+
+    #[test]
+    fn folder_path_is_escaped() {
+        assert_eq!(
+            folder_params(Path::new(r#"C:\Users\a "b"\Downloads"#)).unwrap(),
+            r#"{"behavior":"allow","downloadPath":"C:\\Users\\a \"b\"\\Downloads","eventsEnabled":false}"#
+        );
+    }
+
+    #[test]
+    fn protocol_errors_are_failures() {
+        assert_eq!(protocol_result("{}"), Ok(()));
+        assert_eq!(protocol_result(""), Ok(()));
+        assert_eq!(
+            protocol_result(
+                r#"{"code":-32601,"message":"'Browser.setDownloadBehavior' wasn't found"}"#
+            ),
+            Err("'Browser.setDownloadBehavior' wasn't found (-32601)".to_string())
+        );
+    }
+
+    // Own folder per test, the tests run in parallel
+    fn temp_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "webviewbuilder_win-download-{}-{}",
+            std::process::id(),
+            name
+        ));
+        let _ = std::fs::remove_dir_all(&path);
+        path
+    }
+
+    #[test]
+    fn completed_download_is_moved() {
+        let staging = temp_path("moved");
+        std::fs::create_dir_all(&staging).unwrap();
+        std::fs::write(staging.join("guid"), b"data").unwrap();
+        let path = staging.join("saved").join("file.txt");
+        assert_eq!(
+            completed(
+                &staging,
+                "guid".into(),
+                path.clone(),
+                strict::Policy::new(true)
+            ),
+            DownloadEvent::Completed {
+                id: "guid".into(),
+                path: path.clone()
+            }
+        );
+        assert_eq!(std::fs::read(&path).unwrap(), b"data");
+        let _ = std::fs::remove_dir_all(&staging);
+    }
+
+    #[test]
+    fn strict_download_move_panics() {
+        let staging = temp_path("strict-missing");
+        let message = strict::panic_message(|| {
+            completed(
+                &staging,
+                "guid".into(),
+                staging.join("file.txt"),
+                strict::Policy::new(true),
+            );
+        })
+        .unwrap();
+        assert!(message.starts_with("Moving the download failed"));
+    }
+
+    #[test]
+    fn lenient_download_move_fails_the_download() {
+        let staging = temp_path("lenient-missing");
+        assert_eq!(
+            completed(
+                &staging,
+                "guid".into(),
+                staging.join("file.txt"),
+                strict::Policy::new(false),
+            ),
+            DownloadEvent::Failed { id: "guid".into() }
+        );
+    }
+
+    #[test]
+    fn strict_refused_behavior_panics() {
+        let refused = r#"{"code":-32601,"message":"'Browser.setDownloadBehavior' wasn't found"}"#;
+        assert!(behavior_set("{}", strict::Policy::new(true)).is_ok());
+        let message = strict::panic_message(|| {
+            let _ = behavior_set(refused, strict::Policy::new(true));
+        })
+        .unwrap();
+        assert!(message.starts_with("Setting the download behavior failed"));
+    }
+
+    #[test]
+    fn lenient_refused_behavior_is_forgiven() {
+        let refused = r#"{"code":-32601,"message":"'Browser.setDownloadBehavior' wasn't found"}"#;
+        assert!(behavior_set(refused, strict::Policy::new(false)).is_ok());
+    }
+}
@@ -0,0 +1,95 @@
+//! Dragging the frameless windows by the page, see `WebViewBuilder::frameless`
+//!
+//! This version of WebView2 ignores `-webkit-app-region`, the shim looks it
+//! up instead. A left press on a `drag` region, not under a `no-drag` one,
+//! sends `{"__dragWindow": "drag"}`, and a double click sends
+//! `{"__dragWindow": "maximize"}`. The press is not passed on to the page, so
+//! the region doesn't select text or take the focus.
+
+use crate::middleware::{InboundCtx, MessageMiddleware, MiddlewareAction};
+use serde::Deserialize;
+use std::rc::Weak;
+use winapi::shared::windef::HWND;
+use winapi::um::winuser::{
+    GetAsyncKeyState, GetSystemMetrics, IsZoomed, ReleaseCapture, SM_SWAPBUTTON, VK_LBUTTON,
+    VK_RBUTTON,
+};
+use winit::platform::windows::WindowExtWindows;
+use winit::window::Window;
+
+#[derive(Deserialize)]
+struct DragEnvelope {
+    #[serde(rename = "__dragWindow")]
+    action: DragAction,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum DragAction {
+    Drag,
+    Maximize,
+}
+
+pub(crate) const SCRIPT: &str = r#"(() => {
+    const region = el => {
+        for (; el && el.nodeType === 1; el = el.parentElement) {
+            const r = getComputedStyle(el).getPropertyValue("-webkit-app-region").trim();
+            if (r === "drag" || r === "no-drag") return r;
+        }
+        return "none";
+    };
+    document.addEventListener("mousedown", e => {
+        if (e.button !== 0 || region(e.target) !== "drag") return;
+        e.preventDefault();
+        window.chrome.webview.postMessage(
+            JSON.stringify({ __dragWindow: e.detail === 2 ? "maximize" : "drag" })
+        );
+    }, true);
+})();"#;
+
+/// Built-in middleware moving the window for the drag regions of the shim
+pub(crate) struct DragWindow {
+    pub window: Weak<Window>,
+}
+
+impl MessageMiddleware for DragWindow {
+    fn inbound(&self, msg: InboundCtx) -> MiddlewareAction<InboundCtx> {
+        let action = match serde_json::from_str::<DragEnvelope>(&msg.message) {
+            Ok(envelope) => envelope.action,
+            Err(_) => return MiddlewareAction::Continue(msg),
+        };
+        let window = match self.window.upgrade() {
+            Some(window) => window,
+            None => return MiddlewareAction::Consume,
+        };
+        match action {
+            DragAction::Drag => {
+                // Released before the message came, the move would follow
+                // the mouse until the next click
+                if !primary_button_down() {
+                    return MiddlewareAction::Consume;
+                }
+
+                // The webview has captured the mouse for the press, the move
+                // of the window needs it
+                unsafe { ReleaseCapture() };
+                let _ = window.drag_window();
+            }
+            DragAction::Maximize => {
+                let maximized = unsafe { IsZoomed(window.hwnd() as HWND) } != 0;
+                window.set_maximized(!maximized);
+            }
+        }
+        MiddlewareAction::Consume
+    }
+}
+
+pub(crate) fn primary_button_down() -> bool {
+    // The physical button of the swapped buttons
+    let button = if unsafe { GetSystemMetrics(SM_SWAPBUTTON) } != 0 {
+        VK_RBUTTON
+    } else {
+        VK_LBUTTON
+    };
+    unsafe { GetAsyncKeyState(button) as u16 & 0x8000 != 0 }
+}
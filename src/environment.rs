@@ -0,0 +1,161 @@
+//! WebView2 environment shared by several webviews
+
+use crate::Error;
+use std::cell::RefCell;
+use std::mem;
+use std::rc::Rc;
+
+/// HRESULT_FROM_WIN32(ERROR_FILE_NOT_FOUND), the runtime is not installed
+const RUNTIME_NOT_FOUND: i32 = 0x8007_0002_u32 as i32;
+
+/// Version of the installed WebView2 Runtime, e.g. `"89.0.774.54"`
+pub fn available_runtime_version() -> Option<String> {
+    webview2::EnvironmentBuilder::new()
+        .get_available_browser_version_string()
+        .ok()
+}
+
+pub fn is_runtime_installed() -> bool {
+    available_runtime_version().is_some()
+}
+
+/// Error of building the environment, the missing runtime gets its own
+/// variant
+pub(crate) fn build_error(err: webview2::Error) -> Error {
+    if err.hresult() == RUNTIME_NOT_FOUND {
+        Error::RuntimeNotInstalled
+    } else {
+        Error::WebView2Error(err)
+    }
+}
+
+/// Webview waiting for the environment, it handles the failure itself
+type Waiter = Box<dyn FnOnce(Result<&webview2::Environment, &Error>)>;
+
+enum State {
+    Pending(Vec<Waiter>),
+    Ready(webview2::Environment),
+    Failed,
+}
+
+/// One browser process group for several webviews
+///
+/// Creating the environment is the slow part of the cold start, webviews
+/// built with the same environment skip it. The environment is created
+/// asynchronously, webviews built before it's ready create their controllers
+/// once it is. If it fails, the waiting webviews get the error, and the
+/// webviews built after that fail with `Error::EnvironmentFailed`.
+#[derive(Clone)]
+pub struct WebViewEnvironment {
+    state: Rc<RefCell<State>>,
+}
+
+impl WebViewEnvironment {
+    /// Start creating the environment with the default options
+    pub fn new() -> Result<Self, Error> {
+        Self::with_builder(webview2::EnvironmentBuilder::new())
+    }
+
+    /// Start creating the environment with the options of the builder
+    pub fn with_builder(builder: webview2::EnvironmentBuilder) -> Result<Self, Error> {
+        let state = Rc::new(RefCell::new(State::Pending(Vec::new())));
+        let state_ = state.clone();
+        builder
+            .build(move |env| {
+                settle(&state_, env.map_err(build_error));
+                Ok(())
+            })
+            .map_err(build_error)?;
+        Ok(WebViewEnvironment { state })
+    }
+
+    /// Is the environment created?
+    pub fn is_ready(&self) -> bool {
+        matches!(*self.state.borrow(), State::Ready(_))
+    }
+
+    /// Run `f` with the environment, now or once it's ready
+    ///
+    /// If the environment fails while `f` waits, `f` gets the error.
+    pub(crate) fn with(
+        &self,
+        f: impl FnOnce(Result<&webview2::Environment, &Error>) + 'static,
+    ) -> Result<(), Error> {
+        let env = match &mut *self.state.borrow_mut() {
+            State::Pending(waiters) => {
+                waiters.push(Box::new(f));
+                return Ok(());
+            }
+            State::Ready(env) => env.clone(),
+            State::Failed => return Err(Error::EnvironmentFailed),
+        };
+        f(Ok(&env));
+        Ok(())
+    }
+}
+
+/// Store the created environment, and hand it or its error to the waiters
+fn settle(state: &RefCell<State>, env: Result<webview2::Environment, Error>) {
+    let next = match &env {
+        Ok(env) => State::Ready(env.clone()),
+        Err(_) => State::Failed,
+    };
+    let previous = mem::replace(&mut *state.borrow_mut(), next);
+    if let State::Pending(waiters) = previous {
+        for waiter in waiters {
+            waiter(env.as_ref());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    fn pending() -> WebViewEnvironment {
+        WebViewEnvironment {
+            state: Rc::new(RefCell::new(State::Pending(Vec::new()))),
+        }
+    }
+
+    #[test]
+    fn waiting_webviews_get_the_error() {
+        let environment = pending();
+        let failures = Rc::new(Cell::new(0));
+        for _ in 0..2 {
+            let failures = failures.clone();
+            environment
+                .with(move |env| {
+                    assert!(matches!(env, Err(Error::RuntimeNotInstalled)));
+                    failures.set(failures.get() + 1);
+                })
+                .unwrap();
+        }
+        assert_eq!(failures.get(), 0);
+        settle(&environment.state, Err(Error::RuntimeNotInstalled));
+        assert_eq!(failures.get(), 2);
+        assert!(!environment.is_ready());
+    }
+
+    #[test]
+    fn webviews_built_after_the_failure_fail() {
+        let environment = pending();
+        settle(&environment.state, Err(Error::RuntimeNotInstalled));
+        let result = environment.with(|_| panic!("not called"));
+        assert!(matches!(result, Err(Error::EnvironmentFailed)));
+    }
+
+    #[test]
+    fn waiter_may_build_another_webview() {
+        let environment = pending();
+        let environment_ = environment.clone();
+        environment
+            .with(move |_| {
+                let result = environment_.with(|_| ());
+                assert!(matches!(result, Err(Error::EnvironmentFailed)));
+            })
+            .unwrap();
+        settle(&environment.state, Err(Error::EnvironmentFailed));
+    }
+}
@@ -0,0 +1,193 @@
+//! Errors, with the context of the webview and the operation
+
+use std::fmt;
+use winit::error::OsError;
+use winit::window::WindowId;
+
+#[derive(Debug)]
+pub enum Error {
+    CallCancelled,
+    ControllerNotCreated,
+    EnvironmentFailed,
+    EventLoopClosed,
+    InvalidBrowserArguments,
+    OutboxCorrupted,
+    OutboxFull,
+    PrintFailed,
+    RuntimeNotInstalled,
+    TraceBusy,
+    UnknownWebview,
+    UnsupportedBackgroundColor,
+    WebviewNotShown,
+    WebviewClosed,
+    IoError(std::io::Error),
+    SerializationError(serde_json::Error),
+    WebView2Error(webview2::Error),
+    WindowBuildError(OsError),
+
+    /// Error of an operation on a webview, see `kind` for the error itself
+    WithContext(Box<Error>, ErrorContext),
+}
+
+/// Which webview and which operation failed
+#[derive(Clone, Debug)]
+pub struct ErrorContext {
+    /// Tag given with `WebViewBuilder::tag`
+    pub tag: Option<String>,
+    pub window_id: WindowId,
+
+    /// E.g. `send_msg` or `put_bounds on Resized`
+    pub operation: &'static str,
+}
+
+impl Error {
+    /// Error without the context, for matching
+    pub fn kind(&self) -> &Error {
+        match self {
+            Error::WithContext(err, _) => err.kind(),
+            err => err,
+        }
+    }
+
+    /// Context of the innermost operation, the one that failed, the outer
+    /// operations don't replace it
+    pub fn context(&self) -> Option<&ErrorContext> {
+        match self {
+            Error::WithContext(_, context) => Some(context),
+            _ => None,
+        }
+    }
+
+    /// Attach the context, errors that have it already are kept as is
+    pub(crate) fn with_context(self, context: ErrorContext) -> Error {
+        match self {
+            err @ Error::WithContext(..) => err,
+            err => Error::WithContext(Box::new(err), context),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::CallCancelled => write!(f, "call was cancelled before the page replied"),
+            Error::ControllerNotCreated => write!(f, "controller is not created yet"),
+            Error::EnvironmentFailed => write!(f, "WebView2 environment failed"),
+            Error::EventLoopClosed => write!(f, "event loop has exited"),
+            Error::InvalidBrowserArguments => {
+                write!(f, "browser arguments have quotes or shell characters")
+            }
+            Error::OutboxCorrupted => write!(f, "outbox file is corrupted"),
+            Error::OutboxFull => write!(f, "outbox file is full"),
+            Error::PrintFailed => write!(f, "browser gave an invalid file"),
+            Error::RuntimeNotInstalled => write!(f, "WebView2 Runtime is not installed"),
+            Error::TraceBusy => write!(f, "trace of the webview is in progress"),
+            Error::UnknownWebview => write!(f, "no webview of the name"),
+            Error::UnsupportedBackgroundColor => {
+                write!(f, "background color alpha must be 0 or 255")
+            }
+            Error::WebviewNotShown => write!(f, "webview is not shown"),
+            Error::WebviewClosed => write!(f, "webview is closed"),
+            Error::IoError(err) => write!(f, "I/O failed: {}", err),
+            Error::SerializationError(err) => write!(f, "serialization failed: {}", err),
+            Error::WebView2Error(err) => write!(f, "WebView2 failed: {:?}", err),
+            Error::WindowBuildError(err) => write!(f, "window build failed: {}", err),
+            Error::WithContext(err, context) => write!(f, "{}: {}", context, err),
+        }
+    }
+}
+
+impl fmt::Display for ErrorContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.tag {
+            Some(tag) => write!(f, "{} on {} ({:?})", self.operation, tag, self.window_id),
+            None => write!(f, "{} on {:?}", self.operation, self.window_id),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<webview2::Error> for Error {
+    fn from(er: webview2::Error) -> Self {
+        Error::WebView2Error(er)
+    }
+}
+
+impl From<OsError> for Error {
+    fn from(er: OsError) -> Self {
+        Error::WindowBuildError(er)
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(er: std::io::Error) -> Self {
+        Error::IoError(er)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(er: serde_json::Error) -> Self {
+        Error::SerializationError(er)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context(tag: Option<&str>, operation: &'static str) -> ErrorContext {
+        ErrorContext {
+            tag: tag.map(String::from),
+            window_id: unsafe { WindowId::dummy() },
+            operation,
+        }
+    }
+
+    #[test]
+    fn display_has_the_tag_and_the_operation() {
+        let err = Error::WebviewClosed.with_context(context(Some("settings"), "send_msg"));
+        let display = err.to_string();
+        assert!(display.starts_with("send_msg on settings ("), "{}", display);
+        assert!(display.ends_with("): webview is closed"), "{}", display);
+    }
+
+    #[test]
+    fn debug_has_the_tag_and_the_operation() {
+        let err = Error::OutboxFull.with_context(context(Some("settings"), "send_msg"));
+        let debug = format!("{:?}", err);
+        assert!(debug.contains("OutboxFull"), "{}", debug);
+        assert!(debug.contains(r#"tag: Some("settings")"#), "{}", debug);
+        assert!(debug.contains(r#"operation: "send_msg""#), "{}", debug);
+    }
+
+    #[test]
+    fn display_without_the_tag_has_the_window() {
+        let err = Error::WebviewClosed.with_context(context(None, "put_bounds on Resized"));
+        let display = err.to_string();
+        assert!(
+            display.starts_with("put_bounds on Resized on WindowId("),
+            "{}",
+            display
+        );
+        assert!(display.ends_with(": webview is closed"), "{}", display);
+    }
+
+    #[test]
+    fn innermost_context_is_kept() {
+        let err = Error::ControllerNotCreated
+            .with_context(context(Some("main"), "apply_settings"))
+            .with_context(context(Some("other"), "send_msg"));
+        assert_eq!(err.context().unwrap().operation, "apply_settings");
+        assert_eq!(err.context().unwrap().tag.as_deref(), Some("main"));
+        assert!(matches!(err.kind(), Error::ControllerNotCreated));
+    }
+
+    #[test]
+    fn errors_without_context() {
+        let err = Error::WebviewClosed;
+        assert!(err.context().is_none());
+        assert!(matches!(err.kind(), Error::WebviewClosed));
+        assert_eq!(err.to_string(), "webview is closed");
+    }
+}
@@ -0,0 +1,124 @@
+//! Links opened in the system browser instead of the webview
+
+use crate::settings::url_origin;
+use std::iter::once;
+use std::ptr::{null, null_mut};
+use winapi::um::shellapi::ShellExecuteW;
+use winapi::um::winuser::SW_SHOWNORMAL;
+
+#[derive(Clone, Debug, Default)]
+pub(crate) struct ExternalLinks {
+    // Lowercase schemes without the colon, e.g. "mailto"
+    schemes: Vec<String>,
+
+    // Allowlist of the http(s) origins, others are external
+    internal_origins: Vec<String>,
+}
+
+impl ExternalLinks {
+    pub fn set_schemes(&mut self, schemes: Vec<String>) {
+        self.schemes = schemes
+            .into_iter()
+            .map(|s| s.trim_end_matches(':').to_ascii_lowercase())
+            .collect();
+    }
+
+    pub fn set_internal_origins(&mut self, origins: Vec<String>) {
+        self.internal_origins = origins
+            .iter()
+            .map(|o| url_origin(o).to_ascii_lowercase())
+            .collect();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.schemes.is_empty() && self.internal_origins.is_empty()
+    }
+
+    pub fn is_external(&self, uri: &str) -> bool {
+        let scheme = match scheme(uri) {
+            Some(scheme) => scheme,
+            None => return false,
+        };
+        if self.schemes.contains(&scheme) {
+            return true;
+        }
+        let is_web = scheme == "http" || scheme == "https";
+        is_web
+            && !self.internal_origins.is_empty()
+            && !self
+                .internal_origins
+                .contains(&url_origin(uri).to_ascii_lowercase())
+    }
+
+    /// Can the page open the uri in the system, only the http(s) urls and
+    /// the allowed schemes, e.g. not `file:` or `ms-msdt:` protocol handlers
+    pub fn may_open(&self, uri: &str) -> bool {
+        match scheme(uri) {
+            Some(scheme) => scheme == "http" || scheme == "https" || self.schemes.contains(&scheme),
+            None => false,
+        }
+    }
+}
+
+fn scheme(uri: &str) -> Option<String> {
+    uri.find(':').map(|i| uri[..i].to_ascii_lowercase())
+}
+
+/// Open the uri with the default handler of the system, e.g. the browser or
+/// the mail client
+pub(crate) fn open(uri: &str) {
+    let operation: Vec<u16> = "open".encode_utf16().chain(once(0)).collect();
+    let uri: Vec<u16> = uri.encode_utf16().chain(once(0)).collect();
+    unsafe {
+        ShellExecuteW(
+            null_mut(),
+            operation.as_ptr(),
+            uri.as_ptr(),
+            null(),
+            null(),
+            SW_SHOWNORMAL,
+        )
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn links(schemes: &[&str], origins: &[&str]) -> ExternalLinks {
+        let mut links = ExternalLinks::default();
+        links.set_schemes(schemes.iter().map(|s| s.to_string()).collect());
+        links.set_internal_origins(origins.iter().map(|s| s.to_string()).collect());
+        links
+    }
+
+    #[test]
+    fn system_opens_only_web_urls_by_default() {
+        let links = links(&[], &[]);
+        assert!(links.may_open("https://example.com/"));
+        assert!(links.may_open("HTTP://example.com/"));
+        assert!(!links.may_open("file:///C:/Windows/System32/calc.exe"));
+        assert!(!links.may_open("ms-msdt:/id PCWDiagnostic"));
+        assert!(!links.may_open("mailto:someone@example.com"));
+        assert!(!links.may_open("javascript:alert(1)"));
+        assert!(!links.may_open("no scheme"));
+    }
+
+    #[test]
+    fn system_opens_the_allowed_schemes() {
+        let links = links(&["mailto:", "TEL"], &[]);
+        assert!(links.may_open("mailto:someone@example.com"));
+        assert!(links.may_open("tel:+358401234567"));
+        assert!(!links.may_open("file:///C:/"));
+    }
+
+    #[test]
+    fn external_by_scheme_or_origin() {
+        let links = links(&["mailto"], &["https://app.example.com/index.html"]);
+        assert!(links.is_external("mailto:someone@example.com"));
+        assert!(links.is_external("https://other.example.com/"));
+        assert!(!links.is_external("https://APP.example.com/page"));
+        assert!(!links.is_external("file:///C:/"));
+        assert!(!links.is_external("about:blank"));
+    }
+}
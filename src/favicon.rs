@@ -0,0 +1,134 @@
+//! Favicon of the page for the host's own UI, e.g. the tabs
+//!
+//! This version of WebView2 has no favicon events, the shim watches the
+//! `<link rel="icon">` of the document instead, `/favicon.ico` of the origin
+//! without one. The icon is drawn to a 32x32 canvas and sent as
+//! `{"__favicon": {"url": "...", "rgba": "<base64>", "width": 32, "height":
+//! 32}}`. The pixels of the icons from other origins can't be read, they are
+//! sent with the url only.
+
+use crate::middleware::{InboundCtx, MessageMiddleware, MiddlewareAction};
+use crate::pdf::decode_base64;
+use serde::Deserialize;
+use std::rc::Weak;
+use winit::event_loop::EventLoopProxy;
+use winit::platform::windows::WindowExtWindows;
+use winit::window::{Icon, Window};
+
+/// Favicon of the page, see `WebViewBuilder::on_favicon_changed`
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Favicon {
+    pub url: String,
+
+    /// Pixels row by row, empty if the image couldn't be read
+    pub rgba: Vec<u8>,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Favicon {
+    /// Icon for the window, `None` without the pixels
+    pub fn to_icon(&self) -> Option<Icon> {
+        Icon::from_rgba(self.rgba.clone(), self.width, self.height).ok()
+    }
+}
+
+#[derive(Deserialize)]
+struct FaviconEnvelope {
+    #[serde(rename = "__favicon")]
+    favicon: FaviconMessage,
+}
+
+#[derive(Deserialize)]
+struct FaviconMessage {
+    url: String,
+    rgba: String,
+    width: u32,
+    height: u32,
+}
+
+pub(crate) const SCRIPT: &str = r#"(() => {
+    const SIZE = 32;
+    let current = null;
+    const post = favicon =>
+        window.chrome.webview.postMessage(JSON.stringify({ __favicon: favicon }));
+    const report = () => {
+        const link = document.querySelector('link[rel~="icon"]');
+        let url = link && link.href;
+        if (!url && /^https?:$/.test(location.protocol)) {
+            url = new URL("/favicon.ico", location.href).href;
+        }
+        if (!url || url === current) return;
+        current = url;
+        const img = new Image();
+        img.onload = () => {
+            if (url !== current) return;
+            const canvas = document.createElement("canvas");
+            canvas.width = canvas.height = SIZE;
+            const ctx = canvas.getContext("2d");
+            ctx.drawImage(img, 0, 0, SIZE, SIZE);
+            let rgba = "";
+            try {
+                const data = ctx.getImageData(0, 0, SIZE, SIZE).data;
+                let bytes = "";
+                for (let i = 0; i < data.length; i++) bytes += String.fromCharCode(data[i]);
+                rgba = btoa(bytes);
+            } catch (e) {
+                // Other origin, the canvas is tainted
+            }
+            post({ url, rgba, width: rgba ? SIZE : 0, height: rgba ? SIZE : 0 });
+        };
+        img.onerror = () => {
+            if (url === current) post({ url, rgba: "", width: 0, height: 0 });
+        };
+        img.src = url;
+    };
+    document.addEventListener("DOMContentLoaded", () => {
+        report();
+        new MutationObserver(report).observe(document.documentElement, {
+            childList: true,
+            subtree: true,
+            attributes: true,
+            attributeFilter: ["href", "rel"],
+        });
+    });
+})();"#;
+
+/// Built-in middleware for the favicons of the shim
+pub(crate) struct FaviconChanged<E: 'static> {
+    /// Window to set the icon of, and the icon of the builder for the pages
+    /// without a favicon
+    pub window: Option<(Weak<Window>, Option<Icon>)>,
+    pub event: Option<(fn(Favicon) -> E, EventLoopProxy<E>)>,
+}
+
+impl<E: 'static> MessageMiddleware for FaviconChanged<E> {
+    fn inbound(&self, msg: InboundCtx) -> MiddlewareAction<InboundCtx> {
+        let favicon = match serde_json::from_str::<FaviconEnvelope>(&msg.message) {
+            Ok(envelope) => envelope.favicon,
+            Err(_) => return MiddlewareAction::Continue(msg),
+        };
+        let size = favicon.width as usize * favicon.height as usize * 4;
+        let rgba = decode_base64(&favicon.rgba)
+            .filter(|rgba| rgba.len() == size)
+            .unwrap_or_default();
+        let favicon = Favicon {
+            url: favicon.url,
+            width: if rgba.is_empty() { 0 } else { favicon.width },
+            height: if rgba.is_empty() { 0 } else { favicon.height },
+            rgba,
+        };
+
+        if let Some((window, fallback)) = &self.window {
+            if let Some(window) = window.upgrade() {
+                let icon = favicon.to_icon().or_else(|| fallback.clone());
+                window.set_window_icon(icon.clone());
+                window.set_taskbar_icon(icon);
+            }
+        }
+        if let Some((event, proxy)) = &self.event {
+            let _ = proxy.send_event(event(favicon));
+        }
+        MiddlewareAction::Consume
+    }
+}
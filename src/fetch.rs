@@ -0,0 +1,192 @@
+//! Serve the `fetch()` calls of the page from Rust
+//!
+//! The shim replaces `window.fetch` in every document. Each call is posted to
+//! the host as a JSON string `{"__fetch": {"id": 1, "url": "...", "method":
+//! "GET", "headers": [["name", "value"]], "body": null}}`, and the host
+//! answers with a JSON message `{"__fetchReply": {"id": 1, "response": ...}}`
+//! where the response is `{"status": 200, "headers": [...], "body": "..."}`,
+//! or `null` to let the shim fall through to the real `fetch`. The bodies are
+//! text, and ignored for the statuses without a body such as 204 and 304.
+//! The page's own message listeners see the replies too, they can be told
+//! apart by the `__fetchReply` key.
+//!
+//! The replies pass through the outbound middlewares, but stay JSON like the
+//! other messages of the shims, whatever the codec.
+
+use crate::codec::JsonCodec;
+use crate::middleware::{self, InboundCtx, MessageMiddleware, MiddlewareAction};
+use crate::Error;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::rc::{Rc, Weak};
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct FetchRequest {
+    pub url: String,
+    pub method: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct FetchResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+pub(crate) type FetchHandler = Rc<dyn Fn(FetchRequest) -> Option<FetchResponse>>;
+
+#[derive(Deserialize)]
+struct FetchEnvelope {
+    #[serde(rename = "__fetch")]
+    fetch: FetchCall,
+}
+
+#[derive(Deserialize)]
+struct FetchCall {
+    id: u64,
+    #[serde(flatten)]
+    request: FetchRequest,
+}
+
+#[derive(Serialize)]
+struct ReplyEnvelope {
+    #[serde(rename = "__fetchReply")]
+    reply: FetchReply,
+}
+
+#[derive(Serialize)]
+struct FetchReply {
+    id: u64,
+    response: Option<FetchResponse>,
+}
+
+pub(crate) const SCRIPT: &str = r#"(() => {
+    const realFetch = window.fetch.bind(window);
+    const pending = new Map();
+    let nextId = 0;
+    const nullBody = [101, 103, 204, 205, 304];
+    window.chrome.webview.addEventListener("message", e => {
+        const reply = e.data && e.data.__fetchReply;
+        if (!reply || !pending.has(reply.id)) return;
+        const { resolve, reject, fallback } = pending.get(reply.id);
+        pending.delete(reply.id);
+        try {
+            if (reply.response) {
+                const r = reply.response;
+                const body = nullBody.includes(r.status) ? null : r.body;
+                resolve(new Response(body, { status: r.status, headers: r.headers }));
+            } else {
+                resolve(realFetch(fallback));
+            }
+        } catch (err) {
+            reject(err);
+        }
+    });
+    window.fetch = (input, init) => {
+        const request = new Request(input, init);
+        const fallback = request.clone();
+        return request.text().then(body => new Promise((resolve, reject) => {
+            const id = nextId++;
+            pending.set(id, { resolve, reject, fallback });
+            window.chrome.webview.postMessage(JSON.stringify({ __fetch: {
+                id,
+                url: request.url,
+                method: request.method,
+                headers: [...request.headers],
+                body: body || null,
+            }}));
+        }));
+    };
+})();"#;
+
+/// Built-in middleware answering the fetch calls of the shim
+pub(crate) struct FetchInterceptor {
+    pub handler: FetchHandler,
+    pub controller: Weak<RefCell<Option<webview2::Controller>>>,
+    pub middlewares: Rc<middleware::Chain>,
+}
+
+impl FetchInterceptor {
+    fn reply(&self, reply: &ReplyEnvelope) -> Result<(), Error> {
+        let controller_rc = self
+            .controller
+            .upgrade()
+            .ok_or(Error::ControllerNotCreated)?;
+        let c = controller_rc.borrow();
+        let controller = c.as_ref().ok_or(Error::ControllerNotCreated)?;
+        crate::post_value(
+            &controller.get_webview()?,
+            &self.middlewares,
+            &JsonCodec,
+            serde_json::to_value(reply)?,
+        )
+    }
+}
+
+impl MessageMiddleware for FetchInterceptor {
+    fn inbound(&self, msg: InboundCtx) -> MiddlewareAction<InboundCtx> {
+        let call = match serde_json::from_str::<FetchEnvelope>(&msg.message) {
+            Ok(envelope) => envelope.fetch,
+            Err(_) => return MiddlewareAction::Continue(msg),
+        };
+        let reply = ReplyEnvelope {
+            reply: FetchReply {
+                id: call.id,
+                response: (self.handler)(call.request),
+            },
+        };
+        match self.reply(&reply) {
+            Ok(()) => MiddlewareAction::Consume,
+            Err(err) => MiddlewareAction::Fail(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fetch_call_parses_with_the_request() {
+        let envelope: FetchEnvelope = serde_json::from_str(
+            r#"{"__fetch": {"id": 3, "url": "https://api/x", "method": "POST",
+                "headers": [["content-type", "text/plain"]], "body": "hi"}}"#,
+        )
+        .unwrap();
+        assert_eq!(envelope.fetch.id, 3);
+        assert_eq!(envelope.fetch.request.method, "POST");
+        assert_eq!(
+            envelope.fetch.request.headers,
+            vec![("content-type".to_string(), "text/plain".to_string())]
+        );
+        assert_eq!(envelope.fetch.request.body.as_deref(), Some("hi"));
+    }
+
+    #[test]
+    fn no_content_reply_keeps_the_empty_body() {
+        let reply = ReplyEnvelope {
+            reply: FetchReply {
+                id: 1,
+                response: Some(FetchResponse {
+                    status: 204,
+                    headers: vec![],
+                    body: String::new(),
+                }),
+            },
+        };
+        assert_eq!(
+            serde_json::to_string(&reply).unwrap(),
+            r#"{"__fetchReply":{"id":1,"response":{"status":204,"headers":[],"body":""}}}"#
+        );
+    }
+
+    #[test]
+    fn shim_gives_no_body_for_the_null_body_statuses() {
+        for status in &["101", "103", "204", "205", "304"] {
+            assert!(SCRIPT.contains(status), "{}", status);
+        }
+        assert!(SCRIPT.contains("nullBody.includes(r.status) ? null : r.body"));
+    }
+}
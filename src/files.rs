@@ -0,0 +1,437 @@
+//! Host files served to the page under unguessable urls
+//!
+//! `file://` urls can't be loaded from an https page, instead the granted
+//! files are served from `https://host.local/__host_file/<token>` by the
+//! WebResourceRequested handler. The page sees only the tokens, never the
+//! paths. Range requests are supported so that the `<video>` can seek.
+//!
+//! At most `MAX_RANGE` bytes are read per request. A larger file asked
+//! without a range gets its first bytes as a partial response, the media
+//! elements then ask for the rest with the ranges.
+
+use std::cell::{Cell, RefCell};
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::fs::File;
+use std::hash::{BuildHasher, Hasher};
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::rc::{Rc, Weak};
+use std::time::SystemTime;
+
+pub(crate) const URL_PREFIX: &str = "https://host.local/__host_file/";
+
+// Open ended ranges and the whole file requests are cut to this, the
+// browser asks for the rest
+const MAX_RANGE: u64 = 4 * 1024 * 1024;
+
+struct Grant {
+    path: PathBuf,
+    persistent: bool,
+}
+
+/// Granted files of a webview
+#[derive(Default)]
+pub(crate) struct Grants {
+    grants: RefCell<HashMap<String, Grant>>,
+    counter: Cell<u64>,
+}
+
+impl Grants {
+    pub fn grant(self: &Rc<Self>, path: &Path) -> GrantedUrl {
+        let token = self.token(path);
+        self.grants.borrow_mut().insert(
+            token.clone(),
+            Grant {
+                path: path.to_owned(),
+                persistent: false,
+            },
+        );
+        GrantedUrl {
+            url: format!("{}{}", URL_PREFIX, token),
+            token,
+            grants: Rc::downgrade(self),
+        }
+    }
+
+    /// Drop the grants that don't survive the navigation
+    pub fn navigated(&self) {
+        self.grants.borrow_mut().retain(|_, grant| grant.persistent);
+    }
+
+    // 128 bits from the randomly keyed SipHash of the process
+    fn token(&self, path: &Path) -> String {
+        let n = self.counter.get() + 1;
+        self.counter.set(n);
+        let time = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |d| d.as_nanos());
+        let half = |state: RandomState| {
+            let mut hasher = state.build_hasher();
+            hasher.write_u64(n);
+            hasher.write_u128(time);
+            hasher.write(path.to_string_lossy().as_bytes());
+            hasher.finish()
+        };
+        format!(
+            "{:016x}{:016x}",
+            half(RandomState::new()),
+            half(RandomState::new())
+        )
+    }
+
+    /// Response to the request of the url
+    pub fn serve(&self, uri: &str, range: Option<&str>) -> Response {
+        let token = uri
+            .strip_prefix(URL_PREFIX)
+            .map(|t| t.split(|c| c == '?' || c == '#').next().unwrap_or(""));
+        let path = match token.and_then(|t| self.grants.borrow().get(t).map(|g| g.path.clone())) {
+            Some(path) => path,
+            None => return Response::status(404, "Not Found"),
+        };
+        match read(&path, range) {
+            Ok(response) => response,
+            Err(_) => Response::status(404, "Not Found"),
+        }
+    }
+}
+
+/// Url of a granted file, the grant is revoked when this is dropped
+pub struct GrantedUrl {
+    url: String,
+    token: String,
+    grants: Weak<Grants>,
+}
+
+impl GrantedUrl {
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Keep the grant over the navigations, until it's revoked
+    pub fn persistent(self) -> Self {
+        if let Some(grants) = self.grants.upgrade() {
+            if let Some(grant) = grants.grants.borrow_mut().get_mut(&self.token) {
+                grant.persistent = true;
+            }
+        }
+        self
+    }
+
+    /// Same as dropping
+    pub fn revoke(self) {}
+}
+
+impl Drop for GrantedUrl {
+    fn drop(&mut self) {
+        if let Some(grants) = self.grants.upgrade() {
+            grants.grants.borrow_mut().remove(&self.token);
+        }
+    }
+}
+
+pub(crate) struct Response {
+    pub status: i32,
+    pub reason: &'static str,
+    pub headers: Vec<(&'static str, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    fn status(status: i32, reason: &'static str) -> Self {
+        Response {
+            status,
+            reason,
+            headers: Vec::new(),
+            body: Vec::new(),
+        }
+    }
+
+    /// Headers as the CRLF separated lines
+    pub fn header_lines(&self) -> String {
+        self.headers
+            .iter()
+            .map(|(name, value)| format!("{}: {}", name, value))
+            .collect::<Vec<_>>()
+            .join("\r\n")
+    }
+}
+
+fn read(path: &Path, range: Option<&str>) -> std::io::Result<Response> {
+    let mut file = File::open(path)?;
+    let len = file.metadata()?.len();
+    let mut headers = vec![
+        ("Content-Type", mime_type(path).to_owned()),
+        ("Accept-Ranges", "bytes".to_owned()),
+        ("Access-Control-Allow-Origin", "*".to_owned()),
+    ];
+    let (start, end) = match range.map(|r| parse_range(r, len)) {
+        None if len <= MAX_RANGE => {
+            let mut body = Vec::with_capacity(len as usize);
+            file.read_to_end(&mut body)?;
+            headers.push(("Content-Length", body.len().to_string()));
+            return Ok(Response {
+                status: 200,
+                reason: "OK",
+                headers,
+                body,
+            });
+        }
+        None => (0, MAX_RANGE - 1),
+        Some(None) => {
+            headers.push(("Content-Range", format!("bytes */{}", len)));
+            return Ok(Response {
+                status: 416,
+                reason: "Range Not Satisfiable",
+                headers,
+                body: Vec::new(),
+            });
+        }
+        Some(Some(range)) => range,
+    };
+    let mut body = vec![0; (end - start + 1) as usize];
+    file.seek(SeekFrom::Start(start))?;
+    file.read_exact(&mut body)?;
+    headers.push(("Content-Range", format!("bytes {}-{}/{}", start, end, len)));
+    headers.push(("Content-Length", body.len().to_string()));
+    Ok(Response {
+        status: 206,
+        reason: "Partial Content",
+        headers,
+        body,
+    })
+}
+
+/// Inclusive byte range of a single range `Range` header, `None` if it
+/// can't be satisfied
+fn parse_range(header: &str, len: u64) -> Option<(u64, u64)> {
+    let spec = header.trim().strip_prefix("bytes=")?;
+
+    // Only the first of the multiple ranges is served
+    let spec = spec.split(',').next()?.trim();
+    let (start, end) = spec.split_at(spec.find('-')?);
+    let end = &end[1..];
+    let (start, end) = match (start.parse::<u64>().ok(), end.parse::<u64>().ok()) {
+        (Some(start), Some(end)) => (start, end.min(len.checked_sub(1)?)),
+        (Some(start), None) if end.is_empty() => (
+            start,
+            start.saturating_add(MAX_RANGE - 1).min(len.checked_sub(1)?),
+        ),
+        // Suffix range, the last bytes
+        (None, Some(suffix)) if start.is_empty() && suffix > 0 => {
+            (len.saturating_sub(suffix), len.checked_sub(1)?)
+        }
+        _ => return None,
+    };
+    if start > end {
+        return None;
+    }
+    Some((start, end))
+}
+
+fn mime_type(path: &Path) -> &'static str {
+    let ext = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_ascii_lowercase())
+        .unwrap_or_default();
+    match ext.as_str() {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "svg" => "image/svg+xml",
+        "bmp" => "image/bmp",
+        "ico" => "image/x-icon",
+        "mp4" | "m4v" => "video/mp4",
+        "webm" => "video/webm",
+        "ogv" => "video/ogg",
+        "mp3" => "audio/mpeg",
+        "m4a" => "audio/mp4",
+        "ogg" | "oga" => "audio/ogg",
+        "wav" => "audio/wav",
+        "flac" => "audio/flac",
+        "pdf" => "application/pdf",
+        "txt" => "text/plain",
+        "json" => "application/json",
+        _ => "application/octet-stream",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header<'a>(response: &'a Response, name: &str) -> Option<&'a str> {
+        response
+            .headers
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    // Own file per test, the tests run in parallel
+    fn temp_file(name: &str, len: usize) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "webviewbuilder_win-files-{}-{}",
+            std::process::id(),
+            name
+        ));
+        let bytes: Vec<u8> = (0..len).map(|i| (i % 251) as u8).collect();
+        std::fs::write(&path, bytes).unwrap();
+        path
+    }
+
+    #[test]
+    fn parse_range_forms() {
+        assert_eq!(parse_range("bytes=0-99", 1000), Some((0, 99)));
+        assert_eq!(parse_range("bytes=100-", 1000), Some((100, 999)));
+        assert_eq!(parse_range("bytes=-100", 1000), Some((900, 999)));
+        assert_eq!(parse_range(" bytes=0-0, 5-9", 1000), Some((0, 0)));
+    }
+
+    #[test]
+    fn parse_range_clamps_to_the_file() {
+        assert_eq!(parse_range("bytes=990-2000", 1000), Some((990, 999)));
+        assert_eq!(parse_range("bytes=-5000", 1000), Some((0, 999)));
+    }
+
+    #[test]
+    fn parse_range_cuts_open_ended_ranges() {
+        let len = 3 * MAX_RANGE;
+        assert_eq!(parse_range("bytes=0-", len), Some((0, MAX_RANGE - 1)));
+        assert_eq!(
+            parse_range(&format!("bytes={}-", 2 * MAX_RANGE + 1), len),
+            Some((2 * MAX_RANGE + 1, len - 1))
+        );
+    }
+
+    #[test]
+    fn parse_range_unsatisfiable() {
+        assert_eq!(parse_range("bytes=1000-", 1000), None);
+        assert_eq!(parse_range("bytes=10-5", 1000), None);
+        assert_eq!(parse_range("bytes=-0", 1000), None);
+        assert_eq!(parse_range("bytes=0-10", 0), None);
+        assert_eq!(parse_range("items=0-10", 1000), None);
+        assert_eq!(parse_range("bytes=a-b", 1000), None);
+    }
+
+    #[test]
+    fn serves_small_files_whole() {
+        let path = temp_file("small.mp4", 1000);
+        let grants = Rc::new(Grants::default());
+        let url = grants.grant(&path);
+        let response = grants.serve(url.url(), None);
+        assert_eq!(response.status, 200);
+        assert_eq!(response.body.len(), 1000);
+        assert_eq!(header(&response, "Content-Type"), Some("video/mp4"));
+        assert_eq!(header(&response, "Accept-Ranges"), Some("bytes"));
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn serves_large_files_without_range_in_part() {
+        let len = MAX_RANGE as usize + 10;
+        let path = temp_file("large.webm", len);
+        let grants = Rc::new(Grants::default());
+        let url = grants.grant(&path);
+        let response = grants.serve(url.url(), None);
+        assert_eq!(response.status, 206);
+        assert_eq!(response.body.len(), MAX_RANGE as usize);
+        assert_eq!(
+            header(&response, "Content-Range"),
+            Some(format!("bytes 0-{}/{}", MAX_RANGE - 1, len).as_str())
+        );
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn seeks_serve_the_asked_bytes() {
+        let path = temp_file("seek.mp4", 1000);
+        let grants = Rc::new(Grants::default());
+        let url = grants.grant(&path);
+        for &(range, start, end) in &[
+            ("bytes=0-9", 0usize, 9usize),
+            ("bytes=500-", 500, 999),
+            ("bytes=-10", 990, 999),
+        ] {
+            let response = grants.serve(url.url(), Some(range));
+            assert_eq!(response.status, 206);
+            let expected: Vec<u8> = (start..=end).map(|i| (i % 251) as u8).collect();
+            assert_eq!(response.body, expected);
+            assert_eq!(
+                header(&response, "Content-Range"),
+                Some(format!("bytes {}-{}/1000", start, end).as_str())
+            );
+        }
+        let response = grants.serve(url.url(), Some("bytes=1000-"));
+        assert_eq!(response.status, 416);
+        assert_eq!(header(&response, "Content-Range"), Some("bytes */1000"));
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn query_and_fragment_are_ignored() {
+        let path = temp_file("query.png", 10);
+        let grants = Rc::new(Grants::default());
+        let url = grants.grant(&path);
+        let response = grants.serve(&format!("{}?v=1#x", url.url()), None);
+        assert_eq!(response.status, 200);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn unknown_tokens_are_not_found() {
+        let grants = Rc::new(Grants::default());
+        assert_eq!(
+            grants.serve(&format!("{}nope", URL_PREFIX), None).status,
+            404
+        );
+        assert_eq!(grants.serve("https://example.com/", None).status, 404);
+    }
+
+    #[test]
+    fn dropping_the_url_revokes_the_grant() {
+        let path = temp_file("revoke.txt", 10);
+        let grants = Rc::new(Grants::default());
+        let url = grants.grant(&path);
+        let uri = url.url().to_owned();
+        assert_eq!(grants.serve(&uri, None).status, 200);
+        url.revoke();
+        assert_eq!(grants.serve(&uri, None).status, 404);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn navigation_revokes_all_but_persistent_grants() {
+        let path = temp_file("navigate.txt", 10);
+        let grants = Rc::new(Grants::default());
+        let temporary = grants.grant(&path);
+        let persistent = grants.grant(&path).persistent();
+        grants.navigated();
+        assert_eq!(grants.serve(temporary.url(), None).status, 404);
+        assert_eq!(grants.serve(persistent.url(), None).status, 200);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn concurrent_grants_of_the_same_file_are_independent() {
+        let path = temp_file("concurrent.txt", 10);
+        let grants = Rc::new(Grants::default());
+        let first = grants.grant(&path);
+        let second = grants.grant(&path);
+        assert_ne!(first.url(), second.url());
+        drop(first);
+        assert_eq!(grants.serve(second.url(), None).status, 200);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn urls_outlive_the_grants() {
+        let path = temp_file("outlive.txt", 10);
+        let grants = Rc::new(Grants::default());
+        let url = grants.grant(&path);
+        drop(grants);
+        drop(url);
+        let _ = std::fs::remove_file(&path);
+    }
+}
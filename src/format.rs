@@ -0,0 +1,461 @@
+//! Locale formatting by the host, for the pages without the Intl data
+//!
+//! The shim exposes `window.host.format.number(value, options)` and
+//! `window.host.format.date(value, options)`, both return promises of
+//! strings. The requests made during the same task are batched into one JSON
+//! string `{"__format": [{"id": 1, "kind": "number", "value": 1.5, "options":
+//! {}}]}`, and the host answers with one JSON message `{"__formatReply":
+//! [{"id": 1, "result": "1.50"}]}`. The result is `null` if the value can't be
+//! formatted, and the promise rejects.
+//!
+//! Number options are `{"decimals": 2}`, without it the locale's default
+//! number of decimals is used. Date values are milliseconds since the epoch
+//! like `Date.getTime()`, or `Date` objects, and the options are `{"style":
+//! "short"}` (the default) or `{"style": "long"}`. Dates are in the local
+//! time zone.
+
+use crate::middleware::{InboundCtx, MessageMiddleware, MiddlewareAction};
+use crate::Error;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::iter::once;
+use std::ptr::{null, null_mut};
+use std::rc::Weak;
+use winapi::ctypes::c_int;
+use winapi::shared::minwindef::FILETIME;
+use winapi::um::datetimeapi::GetDateFormatEx;
+use winapi::um::minwinbase::SYSTEMTIME;
+use winapi::um::timezoneapi::{FileTimeToSystemTime, SystemTimeToTzSpecificLocalTime};
+use winapi::um::winnls::{
+    GetLocaleInfoEx, GetNumberFormatEx, DATE_LONGDATE, DATE_SHORTDATE, LCTYPE, LOCALE_ILZERO,
+    LOCALE_INEGNUMBER, LOCALE_RETURN_NUMBER, LOCALE_SDECIMAL, LOCALE_SGROUPING, LOCALE_STHOUSAND,
+    NUMBERFMTW,
+};
+
+/// Locale of the host formatting service
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FormatLocale {
+    /// Regional settings of the user
+    User,
+
+    /// Locale name forced by the app, e.g. `fi-FI`
+    Name(String),
+}
+
+#[derive(Deserialize)]
+struct FormatEnvelope {
+    #[serde(rename = "__format")]
+    requests: Vec<FormatRequest>,
+}
+
+#[derive(Deserialize)]
+struct FormatRequest {
+    id: u64,
+    #[serde(flatten)]
+    kind: FormatKind,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+enum FormatKind {
+    Number {
+        value: f64,
+        #[serde(default)]
+        options: NumberOptions,
+    },
+    Date {
+        value: f64,
+        #[serde(default)]
+        options: DateOptions,
+    },
+}
+
+#[derive(Default, Deserialize)]
+struct NumberOptions {
+    decimals: Option<u32>,
+}
+
+#[derive(Default, Deserialize)]
+struct DateOptions {
+    style: Option<DateStyle>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum DateStyle {
+    Short,
+    Long,
+}
+
+#[derive(Serialize)]
+struct ReplyEnvelope {
+    #[serde(rename = "__formatReply")]
+    replies: Vec<FormatReply>,
+}
+
+#[derive(Serialize)]
+struct FormatReply {
+    id: u64,
+    result: Option<String>,
+}
+
+pub(crate) const SCRIPT: &str = r#"(() => {
+    const pending = new Map();
+    let nextId = 0;
+    let batch = null;
+    window.chrome.webview.addEventListener("message", e => {
+        const replies = e.data && e.data.__formatReply;
+        if (!Array.isArray(replies)) return;
+        for (const { id, result } of replies) {
+            const p = pending.get(id);
+            if (!p) continue;
+            pending.delete(id);
+            if (result === null) {
+                p.reject(new Error("The host could not format the value"));
+            } else {
+                p.resolve(result);
+            }
+        }
+    });
+    const request = (kind, value, options) => new Promise((resolve, reject) => {
+        const id = nextId++;
+        pending.set(id, { resolve, reject });
+        if (!batch) {
+            batch = [];
+            Promise.resolve().then(() => {
+                window.chrome.webview.postMessage(JSON.stringify({ __format: batch }));
+                batch = null;
+            });
+        }
+        batch.push({ id, kind, value: +value, options: options || {} });
+    });
+    window.host = window.host || {};
+    window.host.format = {
+        number: (value, options) => request("number", value, options),
+        date: (value, options) => request("date", value, options),
+    };
+})();"#;
+
+/// Built-in middleware answering the format requests of the shim
+pub(crate) struct Formatter {
+    pub locale: FormatLocale,
+    pub controller: Weak<RefCell<Option<webview2::Controller>>>,
+}
+
+impl Formatter {
+    fn reply(&self, reply: &ReplyEnvelope) -> Result<(), Error> {
+        let controller_rc = self
+            .controller
+            .upgrade()
+            .ok_or(Error::ControllerNotCreated)?;
+        let c = controller_rc.borrow();
+        let controller = c.as_ref().ok_or(Error::ControllerNotCreated)?;
+        let msgstr = &serde_json::to_string(reply)?;
+        controller.get_webview()?.post_web_message_as_json(msgstr)?;
+        Ok(())
+    }
+}
+
+impl MessageMiddleware for Formatter {
+    fn inbound(&self, msg: InboundCtx) -> MiddlewareAction<InboundCtx> {
+        let requests = match serde_json::from_str::<FormatEnvelope>(&msg.message) {
+            Ok(envelope) => envelope.requests,
+            Err(_) => return MiddlewareAction::Continue(msg),
+        };
+
+        let name = locale_name(&self.locale);
+        let locale = name.as_ref().map_or(null(), |n| n.as_ptr());
+        let replies = requests
+            .into_iter()
+            .map(|request| FormatReply {
+                id: request.id,
+                result: match request.kind {
+                    FormatKind::Number { value, options } => format_number(locale, value, &options),
+                    FormatKind::Date { value, options } => format_date(locale, value, &options),
+                },
+            })
+            .collect();
+        match self.reply(&ReplyEnvelope { replies }) {
+            Ok(()) => MiddlewareAction::Consume,
+            Err(err) => MiddlewareAction::Fail(err),
+        }
+    }
+}
+
+/// Name for the NLS functions, LOCALE_NAME_USER_DEFAULT is the null pointer
+fn locale_name(locale: &FormatLocale) -> Option<Vec<u16>> {
+    match locale {
+        FormatLocale::User => None,
+        FormatLocale::Name(name) => Some(wide(name)),
+    }
+}
+
+fn wide(s: &str) -> Vec<u16> {
+    s.encode_utf16().chain(once(0)).collect()
+}
+
+/// Output of a NLS function, asking first for the length
+fn nls_string(f: impl Fn(*mut u16, c_int) -> c_int) -> Option<String> {
+    let len = f(null_mut(), 0);
+    if len <= 0 {
+        return None;
+    }
+    let mut buf = vec![0u16; len as usize];
+    let len = f(buf.as_mut_ptr(), len);
+    if len <= 0 {
+        return None;
+    }
+    Some(String::from_utf16_lossy(&buf[..len as usize - 1]))
+}
+
+fn locale_number(locale: *const u16, lctype: LCTYPE) -> u32 {
+    let mut value: u32 = 0;
+    unsafe {
+        GetLocaleInfoEx(
+            locale,
+            lctype | LOCALE_RETURN_NUMBER,
+            &mut value as *mut u32 as *mut u16,
+            2,
+        )
+    };
+    value
+}
+
+fn locale_string(locale: *const u16, lctype: LCTYPE) -> Vec<u16> {
+    let s = nls_string(|buf, len| unsafe { GetLocaleInfoEx(locale, lctype, buf, len) });
+    wide(&s.unwrap_or_default())
+}
+
+/// Grouping of the NUMBERFMTW from the `LOCALE_SGROUPING`, e.g. "3;0" is 3,
+/// "3;2;0" is 32 and "3" is 30
+fn grouping(sgrouping: &str) -> u32 {
+    let digits: String = sgrouping.chars().filter(|c| c.is_ascii_digit()).collect();
+    let value = digits.parse::<u32>().unwrap_or(0);
+    if sgrouping.ends_with(";0") {
+        value / 10
+    } else {
+        value * 10
+    }
+}
+
+fn format_number(locale: *const u16, value: f64, options: &NumberOptions) -> Option<String> {
+    if !value.is_finite() {
+        return None;
+    }
+    let value_str = match options.decimals {
+        Some(decimals) => format!("{:.*}", decimals as usize, value),
+        None => format!("{}", value),
+    };
+    let value_str = wide(&value_str);
+    match options.decimals {
+        None => nls_string(|buf, len| unsafe {
+            GetNumberFormatEx(locale, 0, value_str.as_ptr(), null(), buf, len)
+        }),
+        Some(decimals) => {
+            // Custom format must be filled completely, rest comes from the
+            // locale
+            let mut decimal_sep = locale_string(locale, LOCALE_SDECIMAL);
+            let mut thousand_sep = locale_string(locale, LOCALE_STHOUSAND);
+            let sgrouping = locale_string(locale, LOCALE_SGROUPING);
+            let sgrouping = String::from_utf16_lossy(&sgrouping[..sgrouping.len() - 1]);
+            let format = NUMBERFMTW {
+                NumDigits: decimals,
+                LeadingZero: locale_number(locale, LOCALE_ILZERO),
+                Grouping: grouping(&sgrouping),
+                lpDecimalSep: decimal_sep.as_mut_ptr(),
+                lpThousandSep: thousand_sep.as_mut_ptr(),
+                NegativeOrder: locale_number(locale, LOCALE_INEGNUMBER),
+            };
+            nls_string(|buf, len| unsafe {
+                GetNumberFormatEx(locale, 0, value_str.as_ptr(), &format, buf, len)
+            })
+        }
+    }
+}
+
+fn format_date(locale: *const u16, value: f64, options: &DateOptions) -> Option<String> {
+    if !value.is_finite() {
+        return None;
+    }
+
+    // FILETIME counts 100ns intervals since 1601-01-01
+    let ticks = (value as i64 + 11_644_473_600_000).checked_mul(10_000)?;
+    if ticks < 0 {
+        return None;
+    }
+    let filetime = FILETIME {
+        dwLowDateTime: ticks as u32,
+        dwHighDateTime: (ticks >> 32) as u32,
+    };
+    let mut utc: SYSTEMTIME = unsafe { std::mem::zeroed() };
+    let mut local: SYSTEMTIME = unsafe { std::mem::zeroed() };
+    unsafe {
+        if FileTimeToSystemTime(&filetime, &mut utc) == 0
+            || SystemTimeToTzSpecificLocalTime(null(), &utc, &mut local) == 0
+        {
+            return None;
+        }
+    }
+    let flags = match options.style {
+        Some(DateStyle::Long) => DATE_LONGDATE,
+        Some(DateStyle::Short) | None => DATE_SHORTDATE,
+    };
+    nls_string(|buf, len| unsafe {
+        GetDateFormatEx(locale, flags, &local, null(), buf, len, null())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Noon UTC is the same date in the time zones of the test machines
+    const JUNE_15_2020: f64 = 1_592_222_400_000.0;
+
+    fn number(locale: &str, value: f64, decimals: Option<u32>) -> Option<String> {
+        let name = wide(locale);
+        format_number(name.as_ptr(), value, &NumberOptions { decimals })
+    }
+
+    fn date(locale: &str, value: f64, style: DateStyle) -> Option<String> {
+        let name = wide(locale);
+        let options = DateOptions { style: Some(style) };
+        format_date(name.as_ptr(), value, &options)
+    }
+
+    #[test]
+    fn grouping_from_sgrouping() {
+        assert_eq!(grouping("3;0"), 3);
+        assert_eq!(grouping("3;2;0"), 32);
+        assert_eq!(grouping("3"), 30);
+        assert_eq!(grouping(""), 0);
+    }
+
+    #[test]
+    fn numbers_in_known_locales() {
+        assert_eq!(
+            number("en-US", 1234.5, Some(2)).as_deref(),
+            Some("1,234.50")
+        );
+        assert_eq!(
+            number("de-DE", 1234.5, Some(2)).as_deref(),
+            Some("1.234,50")
+        );
+        assert_eq!(
+            number("fi-FI", 1234.5, Some(2)).as_deref(),
+            Some("1\u{a0}234,50")
+        );
+        assert_eq!(number("en-US", -1.5, Some(1)).as_deref(), Some("-1.5"));
+        assert_eq!(number("en-US", 1234.6, Some(0)).as_deref(), Some("1,235"));
+    }
+
+    #[test]
+    fn numbers_use_the_default_decimals_of_the_locale() {
+        assert_eq!(number("en-US", 1234.5, None).as_deref(), Some("1,234.50"));
+        assert_eq!(number("de-DE", 1234.5, None).as_deref(), Some("1.234,50"));
+    }
+
+    #[test]
+    fn not_finite_numbers_are_not_formatted() {
+        assert_eq!(number("en-US", f64::NAN, Some(2)), None);
+        assert_eq!(number("en-US", f64::INFINITY, None), None);
+    }
+
+    #[test]
+    fn dates_in_known_locales() {
+        assert_eq!(
+            date("en-US", JUNE_15_2020, DateStyle::Short).as_deref(),
+            Some("6/15/2020")
+        );
+        assert_eq!(
+            date("de-DE", JUNE_15_2020, DateStyle::Short).as_deref(),
+            Some("15.06.2020")
+        );
+        assert_eq!(
+            date("fi-FI", JUNE_15_2020, DateStyle::Short).as_deref(),
+            Some("15.6.2020")
+        );
+        assert_eq!(
+            date("en-US", JUNE_15_2020, DateStyle::Long).as_deref(),
+            Some("Monday, June 15, 2020")
+        );
+    }
+
+    #[test]
+    fn dates_out_of_range_are_not_formatted() {
+        assert_eq!(date("en-US", f64::NAN, DateStyle::Short), None);
+        assert_eq!(date("en-US", -1e20, DateStyle::Short), None);
+    }
+
+    #[test]
+    fn forced_locale_overrides_the_user_locale() {
+        assert_eq!(locale_name(&FormatLocale::User), None);
+        let name = locale_name(&FormatLocale::Name("fi-FI".into())).unwrap();
+        let options = NumberOptions { decimals: Some(2) };
+        assert_eq!(
+            format_number(name.as_ptr(), 1234.5, &options).as_deref(),
+            Some("1\u{a0}234,50")
+        );
+        assert!(format_number(null(), 1234.5, &options).is_some());
+    }
+
+    #[test]
+    fn batched_requests_parse_and_replies_serialize() {
+        let envelope: FormatEnvelope = serde_json::from_str(
+            r#"{"__format": [
+                {"id": 1, "kind": "number", "value": 1.5, "options": {"decimals": 2}},
+                {"id": 2, "kind": "date", "value": 0, "options": {"style": "long"}},
+                {"id": 3, "kind": "number", "value": 2}
+            ]}"#,
+        )
+        .unwrap();
+        let ids: Vec<u64> = envelope.requests.iter().map(|r| r.id).collect();
+        assert_eq!(ids, vec![1, 2, 3]);
+        assert!(matches!(
+            envelope.requests[0].kind,
+            FormatKind::Number {
+                options: NumberOptions { decimals: Some(2) },
+                ..
+            }
+        ));
+        assert!(matches!(
+            envelope.requests[1].kind,
+            FormatKind::Date {
+                options: DateOptions {
+                    style: Some(DateStyle::Long)
+                },
+                ..
+            }
+        ));
+        assert!(matches!(
+            envelope.requests[2].kind,
+            FormatKind::Number {
+                options: NumberOptions { decimals: None },
+                ..
+            }
+        ));
+
+        let reply = ReplyEnvelope {
+            replies: vec![
+                FormatReply {
+                    id: 1,
+                    result: Some("1.50".into()),
+                },
+                FormatReply {
+                    id: 2,
+                    result: None,
+                },
+            ],
+        };
+        assert_eq!(
+            serde_json::to_string(&reply).unwrap(),
+            r#"{"__formatReply":[{"id":1,"result":"1.50"},{"id":2,"result":null}]}"#
+        );
+    }
+
+    #[test]
+    fn other_messages_are_not_format_requests() {
+        assert!(serde_json::from_str::<FormatEnvelope>(r#"{"id": 1}"#).is_err());
+        assert!(serde_json::from_str::<FormatEnvelope>("not json").is_err());
+    }
+}
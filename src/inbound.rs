@@ -0,0 +1,218 @@
+//! Pausing the delivery of the page messages, e.g. around a modal native
+//! dialog
+
+use crate::strict;
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+use std::rc::Rc;
+
+/// What happens to a message when the buffer of a paused webview is full
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum InboundOverflow {
+    /// Drop the oldest buffered message to make room for the new one
+    DropOldest,
+
+    /// Drop the new message
+    DropNewest,
+}
+
+/// Messages buffered while the delivery is paused, in the arrival order
+pub(crate) struct Inbound {
+    // Nested pauses, delivered when it drops back to zero
+    pauses: Cell<usize>,
+    capacity: usize,
+    overflow: InboundOverflow,
+    policy: strict::Policy,
+    queue: RefCell<VecDeque<Box<dyn FnOnce()>>>,
+}
+
+impl Inbound {
+    pub fn new(capacity: usize, overflow: InboundOverflow, policy: strict::Policy) -> Self {
+        Inbound {
+            pauses: Cell::new(0),
+            capacity,
+            overflow,
+            policy,
+            queue: RefCell::new(VecDeque::new()),
+        }
+    }
+
+    /// Deliver now, or buffer if paused
+    pub fn deliver(&self, f: impl FnOnce() + 'static) {
+        if self.pauses.get() == 0 {
+            f();
+            return;
+        }
+        let mut queue = self.queue.borrow_mut();
+        if queue.len() >= self.capacity {
+            self.policy
+                .fail("Paused webview dropped a message", &self.overflow);
+            match self.overflow {
+                InboundOverflow::DropOldest => {
+                    queue.pop_front();
+                }
+                InboundOverflow::DropNewest => return,
+            }
+        }
+        if self.capacity > 0 {
+            queue.push_back(Box::new(f));
+        }
+    }
+
+    pub fn pause(&self) {
+        self.pauses.set(self.pauses.get() + 1);
+    }
+
+    /// Undo one pause, the last one delivers the buffered messages
+    pub fn resume(&self) {
+        let pauses = self.pauses.get().saturating_sub(1);
+        self.pauses.set(pauses);
+        if pauses > 0 {
+            return;
+        }
+
+        // One at a time, a delivery may pause again
+        while self.pauses.get() == 0 {
+            let next = self.queue.borrow_mut().pop_front();
+            match next {
+                Some(f) => f(),
+                None => break,
+            }
+        }
+    }
+}
+
+/// Resumes the delivery when dropped, see `WebViewWrapper::pause_inbound_guard`
+pub struct InboundPauseGuard {
+    inbound: Rc<Inbound>,
+}
+
+impl InboundPauseGuard {
+    pub(crate) fn new(inbound: Rc<Inbound>) -> Self {
+        inbound.pause();
+        InboundPauseGuard { inbound }
+    }
+}
+
+impl Drop for InboundPauseGuard {
+    fn drop(&mut self) {
+        self.inbound.resume();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn recorder() -> (Rc<RefCell<Vec<u32>>>, impl Fn(u32) -> Box<dyn FnOnce()>) {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let log_ = log.clone();
+        let message = move |n: u32| -> Box<dyn FnOnce()> {
+            let log = log_.clone();
+            Box::new(move || log.borrow_mut().push(n))
+        };
+        (log, message)
+    }
+
+    #[test]
+    fn delivers_immediately_when_not_paused() {
+        let (log, message) = recorder();
+        let inbound = Inbound::new(4, InboundOverflow::DropOldest, strict::Policy::new(false));
+        inbound.deliver(message(1));
+        assert_eq!(*log.borrow(), vec![1]);
+    }
+
+    #[test]
+    fn resume_delivers_in_arrival_order() {
+        let (log, message) = recorder();
+        let inbound = Inbound::new(4, InboundOverflow::DropOldest, strict::Policy::new(false));
+        inbound.pause();
+        for n in 1..=3 {
+            inbound.deliver(message(n));
+        }
+        assert!(log.borrow().is_empty());
+        inbound.resume();
+        assert_eq!(*log.borrow(), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn nested_pauses_deliver_on_the_last_resume() {
+        let (log, message) = recorder();
+        let inbound = Inbound::new(4, InboundOverflow::DropOldest, strict::Policy::new(false));
+        inbound.pause();
+        inbound.pause();
+        inbound.deliver(message(1));
+        inbound.resume();
+        assert!(log.borrow().is_empty());
+        inbound.resume();
+        assert_eq!(*log.borrow(), vec![1]);
+    }
+
+    #[test]
+    fn delivery_that_pauses_again_keeps_the_rest_buffered() {
+        let (log, message) = recorder();
+        let inbound = Rc::new(Inbound::new(
+            4,
+            InboundOverflow::DropOldest,
+            strict::Policy::new(false),
+        ));
+        inbound.pause();
+        let inbound_ = inbound.clone();
+        let first = message(1);
+        inbound.deliver(move || {
+            first();
+            inbound_.pause();
+        });
+        inbound.deliver(message(2));
+        inbound.resume();
+        assert_eq!(*log.borrow(), vec![1]);
+        inbound.resume();
+        assert_eq!(*log.borrow(), vec![1, 2]);
+    }
+
+    #[test]
+    fn drop_oldest_keeps_the_newest_messages() {
+        let (log, message) = recorder();
+        let inbound = Inbound::new(2, InboundOverflow::DropOldest, strict::Policy::new(false));
+        inbound.pause();
+        for n in 1..=4 {
+            inbound.deliver(message(n));
+        }
+        inbound.resume();
+        assert_eq!(*log.borrow(), vec![3, 4]);
+    }
+
+    #[test]
+    fn drop_newest_keeps_the_oldest_messages() {
+        let (log, message) = recorder();
+        let inbound = Inbound::new(2, InboundOverflow::DropNewest, strict::Policy::new(false));
+        inbound.pause();
+        for n in 1..=4 {
+            inbound.deliver(message(n));
+        }
+        inbound.resume();
+        assert_eq!(*log.borrow(), vec![1, 2]);
+    }
+
+    #[test]
+    fn zero_capacity_drops_everything_while_paused() {
+        for &overflow in &[InboundOverflow::DropOldest, InboundOverflow::DropNewest] {
+            let (log, message) = recorder();
+            let inbound = Inbound::new(0, overflow, strict::Policy::new(false));
+            inbound.pause();
+            inbound.deliver(message(1));
+            inbound.resume();
+            assert!(log.borrow().is_empty());
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "Paused webview dropped a message")]
+    fn strict_mode_panics_on_overflow() {
+        let (_log, message) = recorder();
+        let inbound = Inbound::new(1, InboundOverflow::DropOldest, strict::Policy::new(true));
+        inbound.pause();
+        inbound.deliver(message(1));
+        inbound.deliver(message(2));
+    }
+}
@@ -0,0 +1,280 @@
+//! Hints to the page while the window is being resized or moved
+//!
+//! Winit doesn't tell when the interactive resize or move starts and ends,
+//! instead the first `Resized` or `Moved` after a quiet period starts the
+//! gesture, and `WebViewWrapper::poll` ends it once the events have been
+//! quiet for the period. The page gets `{"__interaction": {"resizing": true,
+//! "moving": false}}` on every change, e.g. to render a cheap preview while
+//! resizing. The page's own message listeners see them too, they can be told
+//! apart by the `__interaction` key.
+
+use serde::Serialize;
+use std::cell::Cell;
+use std::time::{Duration, Instant};
+
+/// Resize or move gesture in progress
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default, Serialize)]
+pub struct Interaction {
+    pub resizing: bool,
+    pub moving: bool,
+}
+
+#[derive(Serialize)]
+pub(crate) struct Envelope {
+    #[serde(rename = "__interaction")]
+    pub interaction: Interaction,
+}
+
+pub(crate) struct Detector {
+    quiet: Duration,
+    state: Cell<Interaction>,
+    last_resize: Cell<Option<Instant>>,
+    last_move: Cell<Option<Instant>>,
+
+    // Events of the changes made by the app, not by the user
+    programmatic_resizes: Cell<u32>,
+    programmatic_moves: Cell<u32>,
+}
+
+impl Detector {
+    pub fn new(quiet: Duration) -> Self {
+        Detector {
+            quiet,
+            state: Cell::new(Interaction::default()),
+            last_resize: Cell::new(None),
+            last_move: Cell::new(None),
+            programmatic_resizes: Cell::new(0),
+            programmatic_moves: Cell::new(0),
+        }
+    }
+
+    /// Skip the next `Resized`, the app is resizing the window
+    pub fn expect_resize(&self) {
+        self.programmatic_resizes
+            .set(self.programmatic_resizes.get() + 1);
+    }
+
+    /// Skip the next `Moved`, the app is moving the window
+    pub fn expect_move(&self) {
+        self.programmatic_moves
+            .set(self.programmatic_moves.get() + 1);
+    }
+
+    /// The new state, if the event changed it
+    pub fn resized(&self, now: Instant) -> Option<Interaction> {
+        if take(&self.programmatic_resizes) {
+            return None;
+        }
+        self.last_resize.set(Some(now));
+        self.update(now)
+    }
+
+    pub fn moved(&self, now: Instant) -> Option<Interaction> {
+        if take(&self.programmatic_moves) {
+            return None;
+        }
+        self.last_move.set(Some(now));
+        self.update(now)
+    }
+
+    /// End the quiet gestures, the new state if it changed
+    pub fn poll(&self, now: Instant) -> Option<Interaction> {
+        self.update(now)
+    }
+
+    /// When the gesture in progress ends if no more events come
+    pub fn deadline(&self) -> Option<Instant> {
+        let state = self.state.get();
+        let resize = self.last_resize.get().filter(|_| state.resizing);
+        let moving = self.last_move.get().filter(|_| state.moving);
+        resize
+            .into_iter()
+            .chain(moving)
+            .map(|t| t + self.quiet)
+            .min()
+    }
+
+    fn update(&self, now: Instant) -> Option<Interaction> {
+        let active = |last: Option<Instant>| {
+            last.map_or(false, |t| now.saturating_duration_since(t) < self.quiet)
+        };
+        let new = Interaction {
+            resizing: active(self.last_resize.get()),
+            moving: active(self.last_move.get()),
+        };
+        if new == self.state.replace(new) {
+            None
+        } else {
+            Some(new)
+        }
+    }
+}
+
+fn take(count: &Cell<u32>) -> bool {
+    match count.get() {
+        0 => false,
+        n => {
+            count.set(n - 1);
+            true
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const QUIET: Duration = Duration::from_millis(200);
+
+    #[derive(Copy, Clone)]
+    enum Event {
+        Resized(u64),
+        Moved(u64),
+        Poll(u64),
+    }
+
+    const fn state(resizing: bool, moving: bool) -> Interaction {
+        Interaction { resizing, moving }
+    }
+
+    // Changes of the state with their times in milliseconds
+    fn run(detector: &Detector, trace: &[Event]) -> Vec<(u64, Interaction)> {
+        let start = Instant::now();
+        let at = |ms: u64| start + Duration::from_millis(ms);
+        trace
+            .iter()
+            .filter_map(|&event| {
+                let (ms, change) = match event {
+                    Event::Resized(ms) => (ms, detector.resized(at(ms))),
+                    Event::Moved(ms) => (ms, detector.moved(at(ms))),
+                    Event::Poll(ms) => (ms, detector.poll(at(ms))),
+                };
+                change.map(|c| (ms, c))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn resize_drag_starts_and_ends() {
+        let detector = Detector::new(QUIET);
+        let trace = [
+            Event::Resized(0),
+            Event::Resized(16),
+            Event::Poll(100),
+            Event::Resized(150),
+            Event::Poll(300),
+            Event::Poll(349),
+            Event::Poll(350),
+            Event::Poll(400),
+        ];
+        assert_eq!(
+            run(&detector, &trace),
+            vec![(0, state(true, false)), (350, state(false, false))]
+        );
+    }
+
+    #[test]
+    fn quiet_period_boundary() {
+        let detector = Detector::new(QUIET);
+        let trace = [Event::Moved(0), Event::Poll(199), Event::Poll(200)];
+        assert_eq!(
+            run(&detector, &trace),
+            vec![(0, state(false, true)), (200, state(false, false))]
+        );
+    }
+
+    #[test]
+    fn event_after_the_quiet_period_starts_a_new_gesture() {
+        let detector = Detector::new(QUIET);
+        let trace = [
+            Event::Resized(0),
+            Event::Poll(200),
+            Event::Resized(500),
+            Event::Poll(700),
+        ];
+        assert_eq!(
+            run(&detector, &trace),
+            vec![
+                (0, state(true, false)),
+                (200, state(false, false)),
+                (500, state(true, false)),
+                (700, state(false, false)),
+            ]
+        );
+    }
+
+    #[test]
+    fn late_event_without_poll_ends_and_restarts_the_gesture() {
+        let detector = Detector::new(QUIET);
+        let trace = [Event::Resized(0), Event::Moved(300)];
+        assert_eq!(
+            run(&detector, &trace),
+            vec![(0, state(true, false)), (300, state(false, true))]
+        );
+    }
+
+    #[test]
+    fn resize_and_move_overlap() {
+        // Dragging the top left corner moves and resizes
+        let detector = Detector::new(QUIET);
+        let trace = [
+            Event::Resized(0),
+            Event::Moved(1),
+            Event::Resized(50),
+            Event::Poll(210),
+            Event::Moved(220),
+            Event::Poll(300),
+            Event::Poll(420),
+        ];
+        assert_eq!(
+            run(&detector, &trace),
+            vec![
+                (0, state(true, false)),
+                (1, state(true, true)),
+                (210, state(true, false)),
+                (220, state(true, true)),
+                (300, state(false, true)),
+                (420, state(false, false)),
+            ]
+        );
+    }
+
+    #[test]
+    fn deadline_follows_the_last_event() {
+        let detector = Detector::new(QUIET);
+        let start = Instant::now();
+        assert_eq!(detector.deadline(), None);
+        detector.resized(start);
+        assert_eq!(detector.deadline(), Some(start + QUIET));
+        let later = start + Duration::from_millis(50);
+        detector.resized(later);
+        assert_eq!(detector.deadline(), Some(later + QUIET));
+        detector.poll(later + QUIET);
+        assert_eq!(detector.deadline(), None);
+    }
+
+    #[test]
+    fn programmatic_changes_are_skipped() {
+        let detector = Detector::new(QUIET);
+        detector.expect_resize();
+        detector.expect_move();
+        let trace = [Event::Resized(0), Event::Moved(10), Event::Poll(300)];
+        assert_eq!(run(&detector, &trace), vec![]);
+        assert_eq!(detector.deadline(), None);
+
+        // Only the expected events, the next ones are the user's
+        let trace = [Event::Resized(400)];
+        assert_eq!(run(&detector, &trace), vec![(400, state(true, false))]);
+    }
+
+    #[test]
+    fn programmatic_change_during_a_gesture_doesnt_extend_it() {
+        let detector = Detector::new(QUIET);
+        let start = Instant::now();
+        detector.resized(start);
+        detector.expect_resize();
+        assert_eq!(detector.resized(start + Duration::from_millis(150)), None);
+        assert_eq!(detector.deadline(), Some(start + QUIET));
+        assert_eq!(detector.poll(start + QUIET), Some(state(false, false)));
+    }
+}
@@ -0,0 +1,539 @@
+//! Bounds and visibility of the controller
+
+use crate::strict;
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+use std::rc::Rc;
+use std::time::Instant;
+use winapi::shared::windef::RECT;
+use winit::dpi::PhysicalSize;
+
+/// Rectangle in physical pixels, relative to the client area
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub struct Rect {
+    pub left: i32,
+    pub top: i32,
+    pub right: i32,
+    pub bottom: i32,
+}
+
+impl Rect {
+    /// Rectangle covering the whole client area of the size
+    pub fn from_size(size: PhysicalSize<u32>) -> Self {
+        Rect {
+            left: 0,
+            top: 0,
+            right: size.width as i32,
+            bottom: size.height as i32,
+        }
+    }
+
+    pub fn width(&self) -> i32 {
+        self.right - self.left
+    }
+
+    pub fn height(&self) -> i32 {
+        self.bottom - self.top
+    }
+}
+
+/// Placement of the controller in the client area
+#[derive(Copy, Clone, Debug)]
+pub enum BoundsMode {
+    /// Whole client area, the default
+    FillClient,
+
+    /// Fixed rectangle, not changed when the window is resized
+    Fixed(Rect),
+
+    /// Client area minus the insets in physical pixels
+    Relative {
+        top: i32,
+        left: i32,
+        right: i32,
+        bottom: i32,
+    },
+
+    /// Computed from the size of the client area, e.g. for the panes
+    /// sharing a window
+    Custom(fn(PhysicalSize<u32>) -> Rect),
+}
+
+impl Default for BoundsMode {
+    fn default() -> Self {
+        BoundsMode::FillClient
+    }
+}
+
+impl BoundsMode {
+    /// Bounds for the client area of the size
+    pub(crate) fn rect(&self, size: PhysicalSize<u32>) -> Rect {
+        match *self {
+            BoundsMode::FillClient => Rect::from_size(size),
+            BoundsMode::Fixed(rect) => rect,
+            BoundsMode::Custom(f) => f(size),
+            BoundsMode::Relative {
+                top,
+                left,
+                right,
+                bottom,
+            } => {
+                // Insets larger than the client area collapse the rect to
+                // zero size instead of inverting it
+                let (width, height) = (size.width as i32, size.height as i32);
+                let left = left.max(0).min(width);
+                let top = top.max(0).min(height);
+                Rect {
+                    left,
+                    top,
+                    right: (width - right.max(0)).max(left),
+                    bottom: (height - bottom.max(0)).max(top),
+                }
+            }
+        }
+    }
+}
+
+/// Rectangle in logical pixels, relative to the client area
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub struct LogicalRect {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+impl From<RECT> for Rect {
+    fn from(r: RECT) -> Self {
+        Rect {
+            left: r.left,
+            top: r.top,
+            right: r.right,
+            bottom: r.bottom,
+        }
+    }
+}
+
+impl From<Rect> for RECT {
+    fn from(r: Rect) -> Self {
+        RECT {
+            left: r.left,
+            top: r.top,
+            right: r.right,
+            bottom: r.bottom,
+        }
+    }
+}
+
+/// What caused the layout transition
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum LayoutTrigger {
+    Created,
+    Resized,
+    ScaleFactorChanged,
+    SetBounds,
+    SetVisible,
+    Shown,
+    Closed,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum LayoutChange {
+    Bounds(Rect),
+    Visible(bool),
+}
+
+/// Recurring sign of a layout bug in consecutive bounds
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum LayoutArtifact {
+    /// Size differs by a pixel, e.g. a logical size rounded differently
+    DpiRounding,
+
+    /// Origin moved by exactly the top or left inset of
+    /// `BoundsMode::Relative`, e.g. the inset applied twice or not at all
+    Padding,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct LayoutTransition {
+    pub at: Instant,
+    pub trigger: LayoutTrigger,
+    pub change: LayoutChange,
+
+    /// Compared to the previous bounds
+    pub artifact: Option<LayoutArtifact>,
+}
+
+/// Ring buffer of the latest transitions, records only if enabled
+pub(crate) struct LayoutHistory {
+    enabled: bool,
+    policy: strict::Policy,
+    bounds: Rc<Cell<BoundsMode>>,
+    transitions: RefCell<VecDeque<LayoutTransition>>,
+}
+
+impl LayoutHistory {
+    const CAPACITY: usize = 64;
+
+    /// The artifacts are checked against the insets of `bounds`
+    pub fn new(enabled: bool, policy: strict::Policy, bounds: Rc<Cell<BoundsMode>>) -> Self {
+        LayoutHistory {
+            enabled,
+            policy,
+            bounds,
+            transitions: RefCell::new(VecDeque::new()),
+        }
+    }
+
+    pub fn record(&self, trigger: LayoutTrigger, change: LayoutChange) {
+        if !self.enabled {
+            return;
+        }
+        let mut transitions = self.transitions.borrow_mut();
+        let mut artifact = None;
+        if let LayoutChange::Bounds(rect) = change {
+            let previous = transitions.iter().rev().find_map(|t| match t.change {
+                LayoutChange::Bounds(rect) => Some(rect),
+                _ => None,
+            });
+            if let Some(previous) = previous {
+                artifact = find_artifact(&previous, &rect, &self.bounds.get());
+            }
+            if let Some(artifact) = artifact {
+                self.policy.warn(
+                    &format!(
+                        "Layout bounds on {:?} have {:?} artifact",
+                        trigger, artifact
+                    ),
+                    &(previous, rect),
+                );
+            }
+        }
+        if transitions.len() == Self::CAPACITY {
+            transitions.pop_front();
+        }
+        transitions.push_back(LayoutTransition {
+            at: Instant::now(),
+            trigger,
+            change,
+            artifact,
+        });
+    }
+
+    pub fn transitions(&self) -> Vec<LayoutTransition> {
+        self.transitions.borrow().iter().copied().collect()
+    }
+}
+
+/// Artifact of the bounds following the previous ones
+fn find_artifact(previous: &Rect, rect: &Rect, mode: &BoundsMode) -> Option<LayoutArtifact> {
+    if let BoundsMode::Relative { top, left, .. } = *mode {
+        let dx = (rect.left - previous.left).abs();
+        let dy = (rect.top - previous.top).abs();
+        if (left > 0 && dx == left) || (top > 0 && dy == top) {
+            return Some(LayoutArtifact::Padding);
+        }
+    }
+    let dw = (rect.width() - previous.width()).abs();
+    let dh = (rect.height() - previous.height()).abs();
+    if (dw == 1 && dh <= 1) || (dh == 1 && dw <= 1) {
+        return Some(LayoutArtifact::DpiRounding);
+    }
+    None
+}
+
+/// Page of the transitions, oldest first, e.g. for `navigate_to_string` in
+/// a diagnostics window
+pub(crate) fn diagnostics_page(transitions: &[LayoutTransition]) -> String {
+    let first = transitions.first().map(|t| t.at);
+    let rows: String = transitions
+        .iter()
+        .map(|t| {
+            let ms = first.map_or(0, |first| t.at.duration_since(first).as_millis());
+            let change = match t.change {
+                LayoutChange::Bounds(r) => {
+                    format!("bounds {},{} {}x{}", r.left, r.top, r.width(), r.height())
+                }
+                LayoutChange::Visible(visible) => format!("visible {}", visible),
+            };
+            let artifact = t.artifact.map_or(String::new(), |a| format!("{:?}", a));
+            format!(
+                "<tr{}><td>+{} ms</td><td>{:?}</td><td>{}</td><td>{}</td></tr>",
+                if t.artifact.is_some() {
+                    " class=\"artifact\""
+                } else {
+                    ""
+                },
+                ms,
+                t.trigger,
+                change,
+                artifact
+            )
+        })
+        .collect();
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<title>Layout history</title>
+<style>
+body {{ font: 13px monospace; }}
+td {{ padding: 2px 12px 2px 0; }}
+.artifact {{ background: #fdd; }}
+</style>
+</head>
+<body>
+<table>
+<tr><th>At</th><th>Trigger</th><th>Change</th><th>Artifact</th></tr>
+{}
+</table>
+</body>
+</html>
+"#,
+        rows
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn size(width: u32, height: u32) -> PhysicalSize<u32> {
+        PhysicalSize::new(width, height)
+    }
+
+    fn rect(left: i32, top: i32, right: i32, bottom: i32) -> Rect {
+        Rect {
+            left,
+            top,
+            right,
+            bottom,
+        }
+    }
+
+    fn toolbar() -> BoundsMode {
+        BoundsMode::Relative {
+            top: 40,
+            left: 0,
+            right: 0,
+            bottom: 0,
+        }
+    }
+
+    fn history(mode: BoundsMode) -> LayoutHistory {
+        LayoutHistory::new(true, strict::Policy::new(true), Rc::new(Cell::new(mode)))
+    }
+
+    // Bounds of the mode for the sizes, as `WebViewWrapper::resize` records
+    // them
+    fn resize(history: &LayoutHistory, mode: &BoundsMode, sizes: &[PhysicalSize<u32>]) {
+        for &size in sizes {
+            history.record(
+                LayoutTrigger::Resized,
+                LayoutChange::Bounds(mode.rect(size)),
+            );
+        }
+    }
+
+    fn artifacts(history: &LayoutHistory) -> Vec<Option<LayoutArtifact>> {
+        history.transitions().iter().map(|t| t.artifact).collect()
+    }
+
+    #[test]
+    fn fill_client_follows_the_size() {
+        assert_eq!(
+            BoundsMode::FillClient.rect(size(800, 600)),
+            rect(0, 0, 800, 600)
+        );
+        assert_eq!(BoundsMode::FillClient.rect(size(0, 0)), rect(0, 0, 0, 0));
+    }
+
+    #[test]
+    fn fixed_ignores_the_size() {
+        let fixed = BoundsMode::Fixed(rect(10, 10, 110, 60));
+        assert_eq!(fixed.rect(size(800, 600)), rect(10, 10, 110, 60));
+        assert_eq!(fixed.rect(size(50, 50)), rect(10, 10, 110, 60));
+    }
+
+    #[test]
+    fn relative_subtracts_the_insets() {
+        let mode = BoundsMode::Relative {
+            top: 40,
+            left: 10,
+            right: 20,
+            bottom: 30,
+        };
+        assert_eq!(mode.rect(size(800, 600)), rect(10, 40, 780, 570));
+    }
+
+    #[test]
+    fn relative_collapses_instead_of_inverting() {
+        let mode = BoundsMode::Relative {
+            top: 40,
+            left: 10,
+            right: 20,
+            bottom: 30,
+        };
+        let r = mode.rect(size(25, 50));
+        assert_eq!(r, rect(10, 40, 10, 40));
+        assert_eq!((r.width(), r.height()), (0, 0));
+        assert_eq!(mode.rect(size(5, 5)), rect(5, 5, 5, 5));
+    }
+
+    #[test]
+    fn relative_ignores_negative_insets() {
+        let mode = BoundsMode::Relative {
+            top: -10,
+            left: -10,
+            right: -10,
+            bottom: -10,
+        };
+        assert_eq!(mode.rect(size(100, 100)), rect(0, 0, 100, 100));
+    }
+
+    #[test]
+    fn custom_is_computed_from_the_size() {
+        fn left_half(size: PhysicalSize<u32>) -> Rect {
+            rect(0, 0, size.width as i32 / 2, size.height as i32)
+        }
+        let mode = BoundsMode::Custom(left_half);
+        assert_eq!(mode.rect(size(801, 600)), rect(0, 0, 400, 600));
+    }
+
+    #[test]
+    fn disabled_history_records_nothing() {
+        let history = LayoutHistory::new(
+            false,
+            strict::Policy::new(true),
+            Rc::new(Cell::new(BoundsMode::FillClient)),
+        );
+        resize(&history, &BoundsMode::FillClient, &[size(800, 600)]);
+        assert!(history.transitions().is_empty());
+    }
+
+    #[test]
+    fn resize_drag_is_recorded_in_order() {
+        let mode = toolbar();
+        let history = history(mode);
+        history.record(
+            LayoutTrigger::Created,
+            LayoutChange::Bounds(mode.rect(size(800, 600))),
+        );
+        resize(&history, &mode, &[size(820, 610), size(900, 700)]);
+        history.record(LayoutTrigger::SetVisible, LayoutChange::Visible(false));
+
+        let transitions = history.transitions();
+        let triggers: Vec<_> = transitions.iter().map(|t| t.trigger).collect();
+        assert_eq!(
+            triggers,
+            vec![
+                LayoutTrigger::Created,
+                LayoutTrigger::Resized,
+                LayoutTrigger::Resized,
+                LayoutTrigger::SetVisible,
+            ]
+        );
+        assert_eq!(
+            transitions[2].change,
+            LayoutChange::Bounds(rect(0, 40, 900, 700))
+        );
+        assert_eq!(transitions[3].change, LayoutChange::Visible(false));
+        assert!(transitions.windows(2).all(|w| w[0].at <= w[1].at));
+        assert_eq!(artifacts(&history), vec![None; 4]);
+    }
+
+    #[test]
+    fn keeps_the_latest_transitions() {
+        let mode = BoundsMode::FillClient;
+        let history = history(mode);
+        let sizes: Vec<_> = (0..100).map(|i| size(1000 + 10 * i, 600)).collect();
+        resize(&history, &mode, &sizes);
+        let transitions = history.transitions();
+        assert_eq!(transitions.len(), LayoutHistory::CAPACITY);
+        assert_eq!(
+            transitions.last().unwrap().change,
+            LayoutChange::Bounds(rect(0, 0, 1990, 600))
+        );
+    }
+
+    #[test]
+    fn dpi_rounding_artifact() {
+        // 150% scale, the same logical size rounded both ways
+        let mode = BoundsMode::FillClient;
+        let history = history(mode);
+        resize(&history, &mode, &[size(1200, 900), size(1201, 900)]);
+        history.record(
+            LayoutTrigger::ScaleFactorChanged,
+            LayoutChange::Bounds(rect(0, 0, 1200, 899)),
+        );
+        assert_eq!(
+            artifacts(&history),
+            vec![
+                None,
+                Some(LayoutArtifact::DpiRounding),
+                Some(LayoutArtifact::DpiRounding),
+            ]
+        );
+    }
+
+    #[test]
+    fn visibility_between_bounds_is_skipped() {
+        let mode = BoundsMode::FillClient;
+        let history = history(mode);
+        resize(&history, &mode, &[size(1200, 900)]);
+        history.record(LayoutTrigger::SetVisible, LayoutChange::Visible(true));
+        resize(&history, &mode, &[size(1201, 901)]);
+        assert_eq!(
+            artifacts(&history),
+            vec![None, None, Some(LayoutArtifact::DpiRounding)]
+        );
+    }
+
+    #[test]
+    fn padding_artifact() {
+        // Un-maximizing gave the whole client area for a moment
+        let mode = toolbar();
+        let history = history(mode);
+        resize(&history, &mode, &[size(1920, 1080)]);
+        history.record(
+            LayoutTrigger::Resized,
+            LayoutChange::Bounds(BoundsMode::FillClient.rect(size(800, 600))),
+        );
+        resize(&history, &mode, &[size(800, 600)]);
+        assert_eq!(
+            artifacts(&history),
+            vec![
+                None,
+                Some(LayoutArtifact::Padding),
+                Some(LayoutArtifact::Padding),
+            ]
+        );
+    }
+
+    #[test]
+    fn padding_is_not_checked_without_insets() {
+        let mode = BoundsMode::FillClient;
+        let history = history(mode);
+        history.record(
+            LayoutTrigger::SetBounds,
+            LayoutChange::Bounds(rect(0, 40, 800, 600)),
+        );
+        history.record(
+            LayoutTrigger::SetBounds,
+            LayoutChange::Bounds(rect(0, 0, 800, 600)),
+        );
+        assert_eq!(artifacts(&history), vec![None, None]);
+    }
+
+    #[test]
+    fn diagnostics_page_lists_the_transitions() {
+        let mode = BoundsMode::FillClient;
+        let history = history(mode);
+        resize(&history, &mode, &[size(1200, 900), size(1201, 900)]);
+        let page = diagnostics_page(&history.transitions());
+        assert_eq!(page.matches("<td>Resized</td>").count(), 2);
+        assert!(page.contains("bounds 0,0 1201x900"));
+        assert_eq!(page.matches("class=\"artifact\"").count(), 1);
+        assert!(page.contains("<td>DpiRounding</td>"));
+    }
+}
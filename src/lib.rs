@@ -3,21 +3,100 @@
 //! Intention here is to provide simple builder that creates the winit window
 //! and inits the msedge webview2.
 
+mod accelerator;
+mod background;
+mod batch;
+mod capture;
+mod codec;
+#[cfg(feature = "dialogs")]
+pub mod dialogs;
+mod dnd;
+mod download;
+mod drag;
+mod environment;
+mod error;
+mod external;
+mod favicon;
+mod fetch;
+mod files;
+mod format;
+mod inbound;
+mod interaction;
+mod layout;
+mod loading;
+mod manager;
+mod menu;
+mod middleware;
+mod outbox;
+mod pdf;
+mod permission;
+mod process;
+mod route;
+mod rpc;
+mod scheduler;
+mod sender;
+mod settings;
+mod show;
+mod strict;
+mod throttle;
+mod timers;
+#[cfg(feature = "cdp")]
+mod trace;
+mod watchdog;
+mod window_state;
+
+pub use accelerator::{Accelerator, AcceleratorAction, AcceleratorPolicy};
+pub use capture::ImageFormat;
+pub use codec::{JsonCodec, MessageCodec};
+pub use dnd::CrossWindowDrop;
+pub use download::{DownloadDecision, DownloadEvent, DownloadRequest};
+pub use environment::{available_runtime_version, is_runtime_installed, WebViewEnvironment};
+pub use error::{Error, ErrorContext};
+pub use favicon::Favicon;
+pub use fetch::{FetchRequest, FetchResponse};
+pub use files::GrantedUrl;
+pub use format::FormatLocale;
+pub use inbound::{InboundOverflow, InboundPauseGuard};
+pub use interaction::Interaction;
+pub use layout::{
+    BoundsMode, LayoutArtifact, LayoutChange, LayoutTransition, LayoutTrigger, LogicalRect, Rect,
+};
+pub use loading::{LoadingEvent, NavigationFailed};
+pub use manager::WebViewManager;
+pub use middleware::{InboundCtx, MessageMiddleware, MiddlewareAction, OutboundCtx};
+pub use pdf::PdfSettings;
+pub use permission::PermissionRequest;
+pub use process::ProcessFailure;
+pub use scheduler::{Scheduler, TaskToken};
+pub use sender::WebViewSender;
+pub use settings::WebViewSettings;
+#[cfg(feature = "cdp")]
+pub use trace::TraceStats;
+pub use watchdog::WatchdogEvent;
+pub use window_state::{JsonFileStore, WindowState, WindowStatePersistence, WindowStateStore};
+
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
-use std::cell::RefCell;
-use std::mem;
-use std::{fmt::Debug, marker::PhantomData, rc::Rc};
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use std::{
+    fmt::Debug,
+    marker::PhantomData,
+    rc::{Rc, Weak},
+};
 use webview2::Settings;
 use winapi::{
-    shared::windef::{HWND, RECT},
-    um::winuser::{GetClientRect, SetForegroundWindow},
+    shared::windef::HWND,
+    um::winuser::{IsIconic, IsWindowVisible, IsZoomed, SetForegroundWindow},
 };
+use winit::dpi::{PhysicalSize, Position, Size};
 use winit::event::WindowEvent;
-use winit::platform::windows::WindowExtWindows;
+use winit::platform::windows::{WindowBuilderExtWindows, WindowExtWindows};
 use winit::{
-    error::OsError,
     event_loop::{EventLoop, EventLoopProxy, EventLoopWindowTarget},
-    window::{Window, WindowBuilder, WindowId},
+    window::{Icon, Theme, Window, WindowBuilder, WindowId},
 };
 
 #[derive(Copy, Clone, PartialEq, Serialize, Deserialize, Debug)]
@@ -28,37 +107,85 @@ pub enum ShowWebview {
     Immediately,
     OnNavigationCompleted,
     OnContentLoading,
+
+    /// When the DOM is parsed, before the images and the other resources
+    /// are loaded
+    OnDomContentLoaded,
+
+    /// Only when `WebViewWrapper::show` is called
+    Manual,
 }
 
-impl<T: 'static> ReceiveWebviewMessage<T> for NoMsg {
-    fn pass_to_event_loop_proxy(self: Self, _: &EventLoopProxy<T>) {}
+/// Characters of the shell and the quoting, not in the Chromium flags
+const BROWSER_ARGUMENTS_REJECTED: &[char] = &[
+    '"', '\'', '`', '&', '|', ';', '<', '>', '^', '%', '$', '\n', '\r', '\0',
+];
+
+/// What happens to a navigation, see `WebViewBuilder::navigation_filter`
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum NavigationDecision {
+    Allow,
+
+    /// The webview keeps showing the current page
+    Cancel,
+
+    /// Cancel, and open the url with the default handler of the system
+    OpenExternal,
 }
 
-#[derive(Debug)]
-pub enum Error {
-    ControllerNotCreated,
-    WebviewNotShown,
-    SerializationError(serde_json::Error),
-    WebView2Error(webview2::Error),
-    WindowBuildError(OsError),
+/// What happens to `window.open` and the `target="_blank"` links, see
+/// `WebViewBuilder::new_window`
+#[derive(Clone)]
+pub enum NewWindowPolicy<EventLoopType> {
+    /// Navigate this webview to the url instead
+    SuppressAndNavigateSelf,
+
+    /// Open the http(s) urls and the `external_schemes` in the system, the
+    /// other schemes such as `file:` are blocked
+    OpenInSystemBrowser,
+
+    /// No window, `window.open` returns `null`
+    Block,
+
+    /// Send the event from the url to the event loop, e.g. to build another
+    /// webview for it
+    OpenWebview(fn(&str) -> EventLoopType),
 }
 
-impl From<webview2::Error> for Error {
-    fn from(er: webview2::Error) -> Self {
-        Error::WebView2Error(er)
-    }
+/// What happens when the page calls `window.close()`
+#[derive(Clone)]
+enum OnCloseRequested<EventLoopType> {
+    Call(fn()),
+    SendEvent(EventLoopType),
 }
 
-impl From<OsError> for Error {
-    fn from(er: OsError) -> Self {
-        Error::WindowBuildError(er)
-    }
+/// Page navigated to when the controller is created
+#[derive(Clone)]
+enum InitialContent {
+    Url(String),
+    Html(String),
 }
 
-impl From<serde_json::Error> for Error {
-    fn from(er: serde_json::Error) -> Self {
-        Error::SerializationError(er)
-    }
+type PermissionHandler =
+    Rc<dyn Fn(&str, webview2::PermissionKind) -> Option<webview2::PermissionState>>;
+
+type NavigationStartingHandler = Rc<
+    dyn Fn(
+        &webview2::WebView,
+        &webview2::NavigationStartingEventArgs,
+    ) -> Result<(), webview2::Error>,
+>;
+
+/// Outcome of `handle_window_events`
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub struct EventBatchOutcome {
+    /// Number of the events for this window
+    pub events: usize,
+    pub close_requested: bool,
+}
+
+impl<T: 'static> ReceiveWebviewMessage<T> for NoMsg {
+    fn pass_to_event_loop_proxy(self: Self, _: &EventLoopProxy<T>) {}
 }
 
 pub trait ReceiveWebviewMessage<T: 'static> {
@@ -76,33 +203,178 @@ where
     msg_to_webview: PhantomData<MsgToWebView>,
     msg_from_webview: PhantomData<MsgFromWebView>,
     window_builder: Option<WindowBuilder>,
+    icon: Option<Icon>,
+    favicon_fn: Option<fn(Favicon) -> EventLoopType>,
+    favicon_window_icon: bool,
+    frameless: bool,
+    scheduler: Option<Scheduler>,
+    optional_keep_alive: bool,
+    ready_event: Option<EventLoopType>,
     show_on: ShowWebview,
+    focus_webview: bool,
     #[allow(clippy::type_complexity)]
     // settings_fn: Option<Box<dyn Fn(&Settings) -> Result<(), webview2::Error>>>,
     settings_fn: Option<fn(&Settings) -> Result<(), webview2::Error>>,
+    debug_mode: bool,
     #[allow(clippy::type_complexity)]
     // webview_fn: Option<Box<dyn Fn(&webview2::WebView) -> Result<(), webview2::Error>>>,
     webview_fn: Option<fn(&webview2::WebView) -> Result<(), webview2::Error>>,
+    middlewares: middleware::Chain,
+    raw_message_fn: Option<fn(String)>,
+    message_error_fn: Option<fn(String, serde_json::Error)>,
+    move_focus_fn: Option<fn(webview2::MoveFocusReason) -> bool>,
+    routes: route::Routes<EventLoopType>,
+    accelerators: Option<AcceleratorPolicy<EventLoopType>>,
+    accessible_name: Option<String>,
+    max_fps: Option<u32>,
+    debug_layout: bool,
+    fetch_handler: Option<fetch::FetchHandler>,
+    bounds: BoundsMode,
+    format_locale: Option<FormatLocale>,
+    native_menus: Option<menu::Limits>,
+    native_menu_fn: Option<fn(Option<String>) -> EventLoopType>,
+    environment: Option<WebViewEnvironment>,
+    browser_executable_path: Option<PathBuf>,
+    browser_arguments: Option<String>,
+    durable_outbox: Option<(PathBuf, u64)>,
+    auto_grant_clipboard: bool,
+    strict: bool,
+    external_links: external::ExternalLinks,
+    navigation_filter: Option<(fn(&str) -> NavigationDecision, bool)>,
+    new_window: Option<NewWindowPolicy<EventLoopType>>,
+    background_color: Option<(u8, u8, u8, u8)>,
+    tag: Option<String>,
+    close_requested: Option<OnCloseRequested<EventLoopType>>,
+    navigation_started_fn: Option<NavigationStartingHandler>,
+    content: Option<InitialContent>,
+    inbound_capacity: usize,
+    inbound_overflow: InboundOverflow,
+    pause_lifecycle_events: bool,
+    #[allow(clippy::type_complexity)]
+    navigation_completed_fn: Option<
+        fn(
+            &webview2::WebView,
+            &webview2::NavigationCompletedEventArgs,
+        ) -> Result<(), webview2::Error>,
+    >,
+    #[allow(clippy::type_complexity)]
+    process_failed_fn: Option<
+        fn(&webview2::WebView, &webview2::ProcessFailedEventArgs) -> Result<(), webview2::Error>,
+    >,
+    window_state_key: Option<String>,
+    window_state_store: Rc<dyn WindowStateStore>,
+    window_state_persistence: Option<WindowStatePersistence>,
+    interaction_quiet: Option<Duration>,
+    interaction_fn: Option<fn(Interaction)>,
+    history_fn: Option<fn(bool, bool)>,
+    history_event_fn: Option<fn(bool, bool) -> EventLoopType>,
+    codec: Rc<dyn MessageCodec>,
+    source_event_fn: Option<fn(&str, bool) -> EventLoopType>,
+    permission_fn: Option<PermissionHandler>,
+    permission_event_fn: Option<fn(PermissionRequest) -> EventLoopType>,
+    loading_event_fn: Option<fn(LoadingEvent) -> EventLoopType>,
+    navigation_failed_fn: Option<fn(NavigationFailed) -> EventLoopType>,
+    downloads: Option<(
+        PathBuf,
+        fn(&DownloadRequest) -> DownloadDecision,
+        fn(DownloadEvent) -> EventLoopType,
+    )>,
+    download_folder: Option<PathBuf>,
+    show_on_failure: bool,
+    process_failure_fn: Option<fn(ProcessFailure) -> EventLoopType>,
+    auto_recover: bool,
+    watchdog: Option<(Duration, Option<u32>, fn(WatchdogEvent) -> EventLoopType)>,
+    cross_window_drag: Option<usize>,
 }
 
 impl<EventLoopType> WebViewBuilder<EventLoopType, NoMsg, NoMsg>
 where
     EventLoopType: 'static + Clone,
 {
-    #[allow(clippy::new_without_default)]
     pub fn new() -> WebViewBuilder<EventLoopType, NoMsg, NoMsg> {
         WebViewBuilder {
             event_loop_type: PhantomData,
             msg_to_webview: PhantomData,
             msg_from_webview: PhantomData,
             window_builder: None,
+            icon: None,
+            favicon_fn: None,
+            favicon_window_icon: false,
+            frameless: false,
+            scheduler: None,
+            optional_keep_alive: false,
+            ready_event: None,
             show_on: ShowWebview::OnNavigationCompleted,
+            focus_webview: true,
             webview_fn: None,
             settings_fn: None,
+            debug_mode: false,
+            middlewares: middleware::Chain::default(),
+            raw_message_fn: None,
+            message_error_fn: None,
+            move_focus_fn: None,
+            routes: route::Routes::new(),
+            accelerators: None,
+            accessible_name: None,
+            max_fps: None,
+            debug_layout: false,
+            fetch_handler: None,
+            bounds: BoundsMode::FillClient,
+            format_locale: None,
+            native_menus: None,
+            native_menu_fn: None,
+            environment: None,
+            browser_executable_path: None,
+            browser_arguments: None,
+            durable_outbox: None,
+            auto_grant_clipboard: false,
+            strict: false,
+            external_links: external::ExternalLinks::default(),
+            navigation_filter: None,
+            new_window: None,
+            background_color: None,
+            tag: None,
+            close_requested: None,
+            navigation_started_fn: None,
+            content: None,
+            inbound_capacity: 1024,
+            inbound_overflow: InboundOverflow::DropOldest,
+            pause_lifecycle_events: false,
+            window_state_key: None,
+            window_state_store: Rc::new(JsonFileStore::default()),
+            window_state_persistence: None,
+            interaction_quiet: None,
+            interaction_fn: None,
+            history_fn: None,
+            history_event_fn: None,
+            codec: Rc::new(JsonCodec),
+            source_event_fn: None,
+            permission_fn: None,
+            permission_event_fn: None,
+            loading_event_fn: None,
+            navigation_failed_fn: None,
+            downloads: None,
+            download_folder: None,
+            show_on_failure: true,
+            navigation_completed_fn: None,
+            process_failed_fn: None,
+            process_failure_fn: None,
+            auto_recover: false,
+            watchdog: None,
+            cross_window_drag: None,
         }
     }
 }
 
+impl<EventLoopType> Default for WebViewBuilder<EventLoopType, NoMsg, NoMsg>
+where
+    EventLoopType: 'static + Clone,
+{
+    fn default() -> Self {
+        WebViewBuilder::new()
+    }
+}
+
 impl<EventLoopType, MsgToWebView, MsgFromWebView>
     WebViewBuilder<EventLoopType, MsgToWebView, MsgFromWebView>
 where
@@ -120,9 +392,71 @@ where
             msg_to_webview: PhantomData,
             msg_from_webview: PhantomData,
             window_builder: self.window_builder,
+            icon: self.icon,
+            favicon_fn: self.favicon_fn,
+            favicon_window_icon: self.favicon_window_icon,
+            frameless: self.frameless,
+            scheduler: self.scheduler,
+            optional_keep_alive: self.optional_keep_alive,
+            ready_event: self.ready_event,
             show_on: self.show_on,
+            focus_webview: self.focus_webview,
             webview_fn: self.webview_fn,
             settings_fn: self.settings_fn,
+            debug_mode: self.debug_mode,
+            middlewares: self.middlewares,
+            raw_message_fn: self.raw_message_fn,
+            message_error_fn: self.message_error_fn,
+            move_focus_fn: self.move_focus_fn,
+            routes: self.routes,
+            accelerators: self.accelerators,
+            accessible_name: self.accessible_name,
+            max_fps: self.max_fps,
+            debug_layout: self.debug_layout,
+            fetch_handler: self.fetch_handler,
+            bounds: self.bounds,
+            format_locale: self.format_locale,
+            native_menus: self.native_menus,
+            native_menu_fn: self.native_menu_fn,
+            environment: self.environment,
+            browser_executable_path: self.browser_executable_path,
+            browser_arguments: self.browser_arguments,
+            durable_outbox: self.durable_outbox,
+            auto_grant_clipboard: self.auto_grant_clipboard,
+            strict: self.strict,
+            external_links: self.external_links,
+            navigation_filter: self.navigation_filter,
+            new_window: self.new_window,
+            background_color: self.background_color,
+            tag: self.tag,
+            close_requested: self.close_requested,
+            navigation_started_fn: self.navigation_started_fn,
+            content: self.content,
+            inbound_capacity: self.inbound_capacity,
+            inbound_overflow: self.inbound_overflow,
+            pause_lifecycle_events: self.pause_lifecycle_events,
+            window_state_key: self.window_state_key,
+            window_state_store: self.window_state_store,
+            window_state_persistence: self.window_state_persistence,
+            interaction_quiet: self.interaction_quiet,
+            interaction_fn: self.interaction_fn,
+            history_fn: self.history_fn,
+            history_event_fn: self.history_event_fn,
+            codec: self.codec,
+            source_event_fn: self.source_event_fn,
+            permission_fn: self.permission_fn,
+            permission_event_fn: self.permission_event_fn,
+            loading_event_fn: self.loading_event_fn,
+            navigation_failed_fn: self.navigation_failed_fn,
+            downloads: self.downloads,
+            download_folder: self.download_folder,
+            show_on_failure: self.show_on_failure,
+            navigation_completed_fn: self.navigation_completed_fn,
+            process_failed_fn: self.process_failed_fn,
+            process_failure_fn: self.process_failure_fn,
+            auto_recover: self.auto_recover,
+            watchdog: self.watchdog,
+            cross_window_drag: self.cross_window_drag,
         }
     }
     pub fn msg_to_webview<T: Debug + Serialize + 'static + Clone>(
@@ -133,17 +467,137 @@ where
             msg_to_webview: PhantomData,
             msg_from_webview: PhantomData,
             window_builder: self.window_builder,
+            icon: self.icon,
+            favicon_fn: self.favicon_fn,
+            favicon_window_icon: self.favicon_window_icon,
+            frameless: self.frameless,
+            scheduler: self.scheduler,
+            optional_keep_alive: self.optional_keep_alive,
+            ready_event: self.ready_event,
             show_on: self.show_on,
+            focus_webview: self.focus_webview,
             webview_fn: self.webview_fn,
             settings_fn: self.settings_fn,
+            debug_mode: self.debug_mode,
+            middlewares: self.middlewares,
+            raw_message_fn: self.raw_message_fn,
+            message_error_fn: self.message_error_fn,
+            move_focus_fn: self.move_focus_fn,
+            routes: self.routes,
+            accelerators: self.accelerators,
+            accessible_name: self.accessible_name,
+            max_fps: self.max_fps,
+            debug_layout: self.debug_layout,
+            fetch_handler: self.fetch_handler,
+            bounds: self.bounds,
+            format_locale: self.format_locale,
+            native_menus: self.native_menus,
+            native_menu_fn: self.native_menu_fn,
+            environment: self.environment,
+            browser_executable_path: self.browser_executable_path,
+            browser_arguments: self.browser_arguments,
+            durable_outbox: self.durable_outbox,
+            auto_grant_clipboard: self.auto_grant_clipboard,
+            strict: self.strict,
+            external_links: self.external_links,
+            navigation_filter: self.navigation_filter,
+            new_window: self.new_window,
+            background_color: self.background_color,
+            tag: self.tag,
+            close_requested: self.close_requested,
+            navigation_started_fn: self.navigation_started_fn,
+            content: self.content,
+            inbound_capacity: self.inbound_capacity,
+            inbound_overflow: self.inbound_overflow,
+            pause_lifecycle_events: self.pause_lifecycle_events,
+            window_state_key: self.window_state_key,
+            window_state_store: self.window_state_store,
+            window_state_persistence: self.window_state_persistence,
+            interaction_quiet: self.interaction_quiet,
+            interaction_fn: self.interaction_fn,
+            history_fn: self.history_fn,
+            history_event_fn: self.history_event_fn,
+            codec: self.codec,
+            source_event_fn: self.source_event_fn,
+            permission_fn: self.permission_fn,
+            permission_event_fn: self.permission_event_fn,
+            loading_event_fn: self.loading_event_fn,
+            navigation_failed_fn: self.navigation_failed_fn,
+            downloads: self.downloads,
+            download_folder: self.download_folder,
+            show_on_failure: self.show_on_failure,
+            navigation_completed_fn: self.navigation_completed_fn,
+            process_failed_fn: self.process_failed_fn,
+            process_failure_fn: self.process_failure_fn,
+            auto_recover: self.auto_recover,
+            watchdog: self.watchdog,
+            cross_window_drag: self.cross_window_drag,
         }
     }
 
+    /// Name of the webview in the errors, e.g. `"settings"`
+    pub fn tag(mut self, tag: &str) -> Self {
+        self.tag = Some(tag.to_owned());
+        self
+    }
+
     pub fn window_builder(mut self, window_builder: WindowBuilder) -> Self {
         self.window_builder = Some(window_builder);
         self
     }
 
+    /// Icon of the window's title bar, Alt+Tab and the taskbar, e.g. from
+    /// `Icon::from_rgba`
+    ///
+    /// Overrides the icons of the `window_builder`, and is not used with
+    /// `build_in_window`. With `favicon_window_icon` this is the icon of the
+    /// pages without a favicon.
+    pub fn icon(mut self, icon: Icon) -> Self {
+        self.icon = Some(icon);
+        self
+    }
+
+    /// Window without the title bar and the borders, the page draws its own
+    /// and is dragged by its `-webkit-app-region: drag` elements
+    ///
+    /// Double click on a drag region maximizes or restores the window. See
+    /// the `drag` module for the regions. With `build_in_window` the window
+    /// keeps its decorations, only the regions are used.
+    pub fn frameless(mut self) -> Self {
+        self.frameless = true;
+        self
+    }
+
+    /// Let the page drag its items to the other webviews of a
+    /// `WebViewManager` with `window.host.dnd.startDrag(payload)`, the page
+    /// of the target gets them in `window.host.dnd.drop(payload)`
+    ///
+    /// The drags are followed by `WebViewManager::poll_drag`, see the `dnd`
+    /// module. Starts with a message over `max_payload_bytes` are dropped.
+    pub fn cross_window_drag(mut self, max_payload_bytes: usize) -> Self {
+        self.cross_window_drag = Some(max_payload_bytes);
+        self
+    }
+
+    /// Send the favicon of the page to the event loop as it changes, e.g.
+    /// for the host's own tabs
+    ///
+    /// See the `favicon` module for how the favicon is found, the ones that
+    /// can't be loaded or read are sent without the pixels.
+    pub fn on_favicon_changed(mut self, event: fn(Favicon) -> EventLoopType) -> Self {
+        self.favicon_fn = Some(event);
+        self
+    }
+
+    /// Show the favicon of the page as the icon of the window, by default
+    /// false
+    ///
+    /// Not used with `build_in_window`, the window is shared.
+    pub fn favicon_window_icon(mut self, enabled: bool) -> Self {
+        self.favicon_window_icon = enabled;
+        self
+    }
+
     /// Settings init closure
     pub fn settings(
         mut self,
@@ -153,239 +607,3073 @@ where
         self
     }
 
+    /// Enable the devtools, the default context menus, the status bar and the
+    /// browser logging, in the debug builds only
+    ///
+    /// The settings are applied after the `settings` closure, and override
+    /// it. In the release builds this does nothing.
+    pub fn debug_mode(mut self) -> Self {
+        if cfg!(debug_assertions) {
+            self.debug_mode = true;
+            self.browser_arguments = Some(match self.browser_arguments {
+                Some(args) => format!("{} --enable-logging", args),
+                None => "--enable-logging".to_owned(),
+            });
+        }
+        self
+    }
+
     /// Delay the showing until the webview controller responds
     pub fn show_on(mut self, show_on: ShowWebview) -> Self {
         self.show_on = show_on;
         self
     }
 
-    /// Webview init closure
-    pub fn webview_init(
-        mut self,
-        webview_closure: fn(&webview2::WebView) -> Result<(), webview2::Error>,
-    ) -> Self {
-        self.webview_fn = Some(webview_closure);
+    /// Move the keyboard focus into the webview when the window is shown or
+    /// gains focus, enabled by default
+    ///
+    /// Disable this if the app keeps the focus on native widgets.
+    pub fn focus_webview(mut self, focus_webview: bool) -> Self {
+        self.focus_webview = focus_webview;
         self
     }
 
-    /// Tries to build the webview
-    pub fn build(
-        &self,
-        event_loop: &EventLoop<EventLoopType>,
-    ) -> Result<WebViewWrapper<MsgToWebView>, Error> {
-        let proxy = event_loop.create_proxy();
-        self.build_with_proxy(event_loop, &proxy)
+    /// Turn the conditions that are forgiven by default into panics, for
+    /// catching the bugs during development
+    ///
+    /// Differences to the default:
+    ///
+    /// - Message from the page that can't be deserialized, without the
+    ///   `on_message_error` handler, panics with the raw message instead of
+    ///   printing it in the debug builds
+    /// - Message failed by a middleware panics instead of being dropped
+    /// - Queued message that fails to deliver when the controller is ready
+    ///   panics instead of being dropped, as does the replayed message of
+    ///   the `durable_outbox`
+    /// - Corrupted file of the `durable_outbox` panics instead of being
+    ///   emptied
+    /// - Errors while showing and focusing the webview after the navigation
+    ///   panic instead of being ignored, also `WebViewOptional::show`, and
+    ///   hiding the kept alive window of `WebViewOptional::hide`
+    /// - Page message dropped by a full `inbound_buffer` while the delivery
+    ///   is paused panics instead of being dropped
+    /// - Drag of `cross_window_drag` with a payload over the limit panics
+    ///   instead of being dropped
+    /// - Environment or controller that fails to be created panics instead
+    ///   of closing the webview, `Error::WebviewClosed` from then on
+    /// - Download behavior refused by the browser, see `downloads` and
+    ///   `download_folder`, panics instead of leaving the browser's download
+    ///   UI in place
+    /// - Download that fails to start, to report its progress or to be moved
+    ///   from the staging folder panics instead of being ignored, the move
+    ///   otherwise gives `DownloadEvent::Failed`
+    ///
+    /// The suspicious conditions are printed in the debug builds in both
+    /// modes: the layout artifacts of `debug_layout`, the flags of
+    /// `with_additional_browser_arguments`, and the events of the other
+    /// windows with the ids of both windows. The events of the other windows
+    /// are skipped, passing every event to every webview is the intended
+    /// usage, so each other window is warned about once.
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
     }
 
-    /// Tries to build the webview
-    pub fn build_with_proxy(
-        &self,
-        event_loop: &EventLoopWindowTarget<EventLoopType>,
-        event_loop_proxy: &EventLoopProxy<EventLoopType>,
-    ) -> Result<WebViewWrapper<MsgToWebView>, Error> {
-        let window = self
-            .window_builder
-            .clone()
-            .unwrap_or_else(|| WindowBuilder::new().with_title(""))
-            .with_visible(self.show_on == ShowWebview::Immediately)
-            .build(&event_loop)?;
-        let parent_hwnd = window.hwnd() as u32;
-        let window_ref = Rc::new(window);
-        let webview = WebViewWrapper {
-            msg_to_webview_type: PhantomData::<MsgToWebView>,
-            controller: Rc::new(RefCell::new(None)),
-            window: window_ref.clone(),
-        };
-        let settings = self.settings_fn;
-        let webview_with = self.webview_fn;
-        let controller_weak = Rc::downgrade(&webview.controller);
-        let window_weak = Rc::downgrade(&window_ref);
-        let event_loop_proxy = event_loop_proxy.clone();
-        let show_on = self.show_on;
-
-        webview2::EnvironmentBuilder::new().build(move |env| {
-            // Following is ran asynchronously somewhere after the
-            // WebViewBuilder::build() finishes, for this reason the moved
-            // variables must be passed as a weak.
-            env?.create_controller(parent_hwnd as HWND, move |host| {
-                let controller = host?;
-                let webview = controller.get_webview()?;
+    /// Called when a message from the webview can't be deserialized to the
+    /// `MsgFromWebView`, in both debug and release builds
+    ///
+    /// Without the handler the error is only printed in debug builds.
+    pub fn on_message_error(mut self, handler: fn(String, serde_json::Error)) -> Self {
+        self.message_error_fn = Some(handler);
+        self
+    }
 
-                if let Some(settings_fn) = settings {
-                    webview.get_settings().map(|o| settings_fn(&o))??;
-                }
+    /// Called when the page calls `window.close()`
+    ///
+    /// The window is not closed by the builder, without the handler the
+    /// request is ignored.
+    pub fn on_close_requested(mut self, handler: fn()) -> Self {
+        self.close_requested = Some(OnCloseRequested::Call(handler));
+        self
+    }
 
-                unsafe {
-                    let mut rect = mem::zeroed();
-                    GetClientRect(parent_hwnd as HWND, &mut rect);
-                    controller.put_bounds(rect)?;
-                }
+    /// Send the event to the event loop when the page calls `window.close()`
+    pub fn on_close_send_event(mut self, event: EventLoopType) -> Self {
+        self.close_requested = Some(OnCloseRequested::SendEvent(event));
+        self
+    }
 
-                let window_weak_ = window_weak.clone();
-                webview.add_document_title_changed(move |args| {
-                    if let Some(window_rc) = window_weak_.upgrade() {
-                        let title = args.get_document_title()?;
-                        window_rc.set_title(&title);
-                        window_rc.request_redraw();
-                    }
-                    Ok(())
-                })?;
+    /// Called when a navigation starts, e.g. for an address bar or a
+    /// loading spinner
+    ///
+    /// The navigation can be cancelled with `put_cancel(true)`, the webview
+    /// keeps showing the current page. The args tell also whether the user
+    /// started it, `get_is_user_initiated`, and whether it's a redirect,
+    /// `get_is_redirected`, e.g. to handle the clicked links in the host:
+    ///
+    /// ```ignore
+    /// let allowed = vec!["https://app.example.com/".to_owned()];
+    /// builder.on_navigation_started(move |_webview, args| {
+    ///     let uri = args.get_uri()?;
+    ///     if !allowed.iter().any(|a| uri.starts_with(a)) {
+    ///         if args.get_is_user_initiated()? && !args.get_is_redirected()? {
+    ///             println!("Clicked a link to {}", uri);
+    ///         }
+    ///         args.put_cancel(true)?;
+    ///     }
+    ///     Ok(())
+    /// })
+    /// ```
+    pub fn on_navigation_started(
+        mut self,
+        handler: impl Fn(
+                &webview2::WebView,
+                &webview2::NavigationStartingEventArgs,
+            ) -> Result<(), webview2::Error>
+            + 'static,
+    ) -> Self {
+        self.navigation_started_fn = Some(Rc::new(handler));
+        self
+    }
 
-                // Show the window after event trigger
-                let window_weak_ = window_weak.clone();
-                let controller_weak_ = controller_weak.clone();
-                let do_it = move || {
-                    if let Some(controller_rc) = controller_weak_.upgrade() {
-                        if let Some(controller) = controller_rc.borrow().as_ref() {
-                            controller.put_is_visible(true)?;
-                        }
-                    }
-                    if let Some(_window_rc) = window_weak_.upgrade() {
-                        _window_rc.set_visible(true);
-                    }
-                    Ok(())
-                };
-                match show_on {
-                    ShowWebview::Immediately => {}
-                    ShowWebview::OnNavigationCompleted => {
-                        webview.add_navigation_completed(move |_, _args| do_it())?;
-                    }
-                    ShowWebview::OnContentLoading => {
-                        webview.add_content_loading(move |_, _args| do_it())?;
-                    }
-                }
+    /// Called when a navigation completes, also when it fails
+    ///
+    /// Independent of `ShowWebview::OnNavigationCompleted`, both are called.
+    pub fn on_navigation_completed(
+        mut self,
+        handler: fn(
+            &webview2::WebView,
+            &webview2::NavigationCompletedEventArgs,
+        ) -> Result<(), webview2::Error>,
+    ) -> Self {
+        self.navigation_completed_fn = Some(handler);
+        self
+    }
 
-                // Webview requested a close?
-                let window_weak_ = window_weak.clone();
-                webview.add_window_close_requested(move |_webview| {
-                    if let Some(_window_rc) = window_weak_.upgrade() {
-                        // TODO: Send message to eventloop?
-                    }
-                    Ok(())
-                })?;
+    /// Send the event to the event loop when a navigation fails, e.g. the
+    /// host can't be reached or the connection is refused
+    ///
+    /// The cancelled navigations are sent too, with the status
+    /// `OperationCanceled`.
+    pub fn on_navigation_failed(mut self, event: fn(NavigationFailed) -> EventLoopType) -> Self {
+        self.navigation_failed_fn = Some(event);
+        self
+    }
 
-                // Message passing
-                webview.add_web_message_received(move |_webview, args| {
-                    let message = args.try_get_web_message_as_string()?;
+    /// Handle the downloads of the page instead of the browser's download UI
+    ///
+    /// `decide` is called as a download starts, see `DownloadDecision`. The
+    /// files are saved to the `staging` folder under their ids while in
+    /// progress, and moved to the decided path once complete. The progress
+    /// is sent to the event loop.
+    pub fn downloads(
+        mut self,
+        staging: impl Into<PathBuf>,
+        decide: fn(&DownloadRequest) -> DownloadDecision,
+        event: fn(DownloadEvent) -> EventLoopType,
+    ) -> Self {
+        self.downloads = Some((staging.into(), decide, event));
+        self
+    }
 
-                    match serde_json::from_str::<MsgFromWebView>(&message) {
-                        Ok(msg) => msg.pass_to_event_loop_proxy(&event_loop_proxy),
-                        Err(_err) => {
-                            // TODO: Should we send parsing error message to event_loop_proxy?
-                            #[cfg(debug_assertions)]
-                            println!(
-                                "Webview gave unparseable result: {:?}, error: {:?}",
-                                message, _err
-                            );
-                        }
-                    }
+    /// Save the downloads of the page to the folder without the browser's
+    /// download UI, under their suggested names
+    ///
+    /// Not used with `downloads`, which decides the path of each download.
+    pub fn download_folder(mut self, folder: impl Into<PathBuf>) -> Self {
+        self.download_folder = Some(folder.into());
+        self
+    }
 
-                    Ok(())
-                })?;
+    /// Show the window also when the navigation fails, with the browser's
+    /// error page, by default true
+    ///
+    /// Otherwise the window of the failed page stays hidden until it's shown
+    /// with `WebViewWrapper::show`. Not used with `ShowWebview::Immediately`
+    /// and `ShowWebview::Manual`.
+    pub fn show_on_failure(mut self, show: bool) -> Self {
+        self.show_on_failure = show;
+        self
+    }
 
-                if let Some(webview_with_fn) = webview_with {
-                    webview_with_fn(&webview)?;
-                }
+    /// Called when a browser or a renderer process of the webview exits or
+    /// hangs, e.g. to show a reload prompt
+    pub fn on_process_failed(
+        mut self,
+        handler: fn(
+            &webview2::WebView,
+            &webview2::ProcessFailedEventArgs,
+        ) -> Result<(), webview2::Error>,
+    ) -> Self {
+        self.process_failed_fn = Some(handler);
+        self
+    }
 
-                if let Some(controller_rc) = controller_weak.upgrade() {
-                    let mut controller_cell = controller_rc.borrow_mut();
-                    *controller_cell = Some(controller);
-                }
+    /// Send the event to the event loop when a process of the webview exits
+    /// or hangs, see `ProcessFailure`
+    ///
+    /// When the browser process exits the webview is closed, `send_msg`
+    /// fails with `Error::WebviewClosed` instead of losing the messages, and
+    /// the webview must be built again, e.g. `WebViewOptional::hide` and
+    /// `show`, unless `auto_recover` builds it again.
+    pub fn on_process_failure(mut self, event: fn(ProcessFailure) -> EventLoopType) -> Self {
+        self.process_failure_fn = Some(event);
+        self
+    }
 
-                Ok(())
-            })
-        })?;
-        Ok(webview)
+    /// Reload the page when its renderer process exits, and build the
+    /// environment and the controller again when the browser process exits,
+    /// by default the webview stays blank or closed
+    ///
+    /// The rebuilt webview runs the settings and the scripts of the builder
+    /// again and navigates back to the url of the page, `on_ready` is sent
+    /// again. The messages sent meanwhile wait for the new controller, the
+    /// queued ones and the waiting calls of the exited browser are lost, see
+    /// `durable_outbox`. The webviews of a shared `WebViewEnvironment` are
+    /// closed as without this, the environment can't be created again from
+    /// here.
+    pub fn auto_recover(mut self, auto_recover: bool) -> Self {
+        self.auto_recover = auto_recover;
+        self
     }
 
-    /// Build optional window
+    /// Ping the page each period and send the event to the event loop when
+    /// it doesn't answer in time, e.g. it's stuck in an endless loop, and
+    /// again when it answers, see `WatchdogEvent`
     ///
-    /// This window does not exist until it's shown, after closing it, it needs
-    /// to be shown again.
-    pub fn build_optional(
-        &self,
-        _event_loop: &EventLoop<EventLoopType>,
-    ) -> WebViewOptional<EventLoopType, MsgToWebView, MsgFromWebView> {
-        WebViewOptional::new(self.clone())
+    /// The page is reloaded after each `reload_after` misses, `None` leaves
+    /// it to the app, e.g. a "Wait or reload" prompt. The pings are sent by
+    /// `WebViewWrapper::poll` or the `scheduler`, and pause while the webview
+    /// is hidden.
+    pub fn watchdog(
+        mut self,
+        period: Duration,
+        reload_after: Option<u32>,
+        event: fn(WatchdogEvent) -> EventLoopType,
+    ) -> Self {
+        self.watchdog = Some((period, reload_after, event));
+        self
     }
-}
 
-pub struct WebViewWrapper<MsgToWebView>
-where
-    MsgToWebView: Serialize + 'static,
-{
-    msg_to_webview_type: PhantomData<MsgToWebView>,
+    /// Tab moved the focus out of the webview
+    ///
+    /// Return true if the host moved the focus to its own controls, false to
+    /// wrap the focus around in the webview, which is also the default.
+    pub fn on_move_focus(mut self, move_focus: fn(webview2::MoveFocusReason) -> bool) -> Self {
+        self.move_focus_fn = Some(move_focus);
+        self
+    }
 
-    // Controller persists the webview, while it's alive, the webview is shown
-    controller: Rc<RefCell<Option<webview2::Controller>>>,
-    window: Rc<Window>,
-}
+    /// Send an event to the event loop when navigating to a matching url
+    ///
+    /// Pattern is a glob where `*` matches any run of characters and `?` a
+    /// single character, e.g. `https://app.local/orders/*`. When several
+    /// routes match, the first registered one wins. The navigation itself
+    /// is not cancelled.
+    pub fn route(mut self, pattern: impl Into<String>, handler: fn(&str) -> EventLoopType) -> Self {
+        self.routes.push(pattern.into(), handler);
+        self
+    }
 
-impl<MsgToWebView> WebViewWrapper<MsgToWebView>
-where
-    MsgToWebView: Debug + Serialize + 'static + Clone,
-{
-    /// Pass message to the WebView
-    pub fn send_msg(&self, m: MsgToWebView) -> Result<(), Error> {
-        let c = self.controller.borrow_mut();
-        if let Some(controller) = c.as_ref() {
-            let webview = controller.get_webview()?;
-            let msgstr = &serde_json::to_string(&m)?;
-            webview.post_web_message_as_json(msgstr)?;
-        }
-        Ok(())
+    /// Open the links with the schemes in the system, e.g. `mailto` and `tel`
+    ///
+    /// The navigation and the new window are cancelled, and the link is
+    /// opened with `ShellExecuteW`. By default every link navigates the
+    /// webview.
+    pub fn external_schemes(mut self, schemes: Vec<String>) -> Self {
+        self.external_links.set_schemes(schemes);
+        self
     }
 
-    /// Is matching window?
-    pub fn is_window(&self, window_id: &WindowId) -> bool {
-        window_id == &self.window.id()
+    /// Open the http(s) links to the other origins in the system browser,
+    /// e.g. `https://app.example.com`
+    ///
+    /// With the allowlist only the listed origins navigate the webview, by
+    /// default every origin does.
+    pub fn internal_origins(mut self, origins: Vec<String>) -> Self {
+        self.external_links.set_internal_origins(origins);
+        self
     }
 
-    /// Call the webview instance
-    pub fn webview_with(&self, mut cb: impl FnMut(&webview2::WebView)) -> Result<(), Error> {
-        let c = self.controller.borrow_mut();
-        if let Some(controller) = c.as_ref() {
-            let mut webview = controller.get_webview()?;
-            cb(&mut webview);
-            Ok(())
-        } else {
-            Err(Error::ControllerNotCreated)
-        }
+    /// Decide by the url whether a navigation happens, e.g. to keep the
+    /// webview in the app's own origin
+    ///
+    /// Runs for every navigation of the top level page, also the redirects
+    /// and the navigations of the scripts. The first navigation, e.g. of
+    /// `url` or `webview_init`, is filtered only with `include_initial`.
+    pub fn navigation_filter(
+        mut self,
+        filter: fn(&str) -> NavigationDecision,
+        include_initial: bool,
+    ) -> Self {
+        self.navigation_filter = Some((filter, include_initial));
+        self
     }
 
-    /// Handle Window Event
+    /// Handle the new windows of the page, by default the browser opens them
+    /// in its own popup windows
     ///
-    /// Runs the side effects to keep the webview2 control happy, you must plug
-    /// this in to the main event loop.
-    pub fn handle_window_event(&self, t: &WindowEvent, window_id: &WindowId) -> Result<(), Error> {
+    /// Applies to both the links and `window.open` of the scripts. The
+    /// windows of `external_schemes` and `internal_origins` are opened in
+    /// the system before this.
+    pub fn new_window(mut self, policy: NewWindowPolicy<EventLoopType>) -> Self {
+        self.new_window = Some(policy);
+        self
+    }
+
+    /// Intercept the browser shortcuts, or forward them to the event loop
+    pub fn accelerators(mut self, policy: AcceleratorPolicy<EventLoopType>) -> Self {
+        self.accelerators = Some(policy);
+        self
+    }
+
+    /// Name for the window when neither the window builder nor the page
+    /// gives a title
+    ///
+    /// Screen readers announce the window text, without this the optional
+    /// tool windows with empty titles are announced only as "window".
+    pub fn accessible_name(mut self, name: &str) -> Self {
+        self.accessible_name = Some(name.to_owned());
+        self
+    }
+
+    /// Limit the frame rate of the page to save battery, e.g. for background
+    /// windows
+    ///
+    /// Throttles the `requestAnimationFrame` of the page, which drives most of
+    /// the rendering work, CSS animations and videos are not affected. Use
+    /// `WebViewWrapper::set_max_fps` to change the limit, e.g. to restore the
+    /// full rate when the window comes to the foreground.
+    pub fn max_fps(mut self, max_fps: u32) -> Self {
+        self.max_fps = Some(max_fps);
+        self
+    }
+
+    /// Record the latest bounds and visibility transitions for debugging the
+    /// layout bugs, see `WebViewWrapper::layout_history`
+    ///
+    /// Also warns in the debug builds when consecutive bounds differ by a DPI
+    /// rounding artifact or by the `bounds_inset` padding, see
+    /// `LayoutTransition::artifact`.
+    pub fn debug_layout(mut self, debug_layout: bool) -> Self {
+        self.debug_layout = debug_layout;
+        self
+    }
+
+    /// Serve the `fetch()` calls of the page from Rust
+    ///
+    /// A shim replacing the `window.fetch` is injected to every document, it
+    /// posts the requests to the handler. When the handler returns `None`
+    /// the shim falls through to the real `fetch`. See the `fetch` module for
+    /// the message contract of the shim.
+    pub fn on_fetch(
+        mut self,
+        handler: impl Fn(FetchRequest) -> Option<FetchResponse> + 'static,
+    ) -> Self {
+        self.fetch_handler = Some(Rc::new(handler));
+        self
+    }
+
+    /// Decide the permission requests of the pages, e.g. by the origin of
+    /// the uri
+    ///
+    /// The handler gets the uri of the page and the kind of the permission.
+    /// `None` leaves the request to the default prompt of WebView2, which is
+    /// also what happens without the handler.
+    ///
+    /// ```ignore
+    /// builder.on_permission_requested(|uri, kind| match kind {
+    ///     PermissionKind::Camera if uri.starts_with("https://app.example.com/") => {
+    ///         Some(PermissionState::Allow)
+    ///     }
+    ///     _ => Some(PermissionState::Deny),
+    /// })
+    /// ```
+    pub fn on_permission_requested(
+        mut self,
+        handler: impl Fn(&str, webview2::PermissionKind) -> Option<webview2::PermissionState> + 'static,
+    ) -> Self {
+        self.permission_fn = Some(Rc::new(handler));
+        self
+    }
+
+    /// Send the permission requests to the event loop, and answer them later
+    /// with `PermissionRequest::respond`, e.g. from a native dialog
+    ///
+    /// Only the requests that `on_permission_requested` and
+    /// `auto_grant_clipboard` leave undecided are sent, the page waits for
+    /// the answer.
+    ///
+    /// ```ignore
+    /// Event::UserEvent(AppEvent::Permission(request)) => {
+    ///     let allowed = ask_the_user(request.uri(), request.kind());
+    ///     let _ = request.respond(Some(if allowed {
+    ///         PermissionState::Allow
+    ///     } else {
+    ///         PermissionState::Deny
+    ///     }));
+    /// }
+    /// ```
+    pub fn defer_permission_requests(
+        mut self,
+        event: fn(PermissionRequest) -> EventLoopType,
+    ) -> Self {
+        self.permission_event_fn = Some(event);
+        self
+    }
+
+    /// Allow the page to read the clipboard without the permission prompt
+    ///
+    /// Only for the trusted content: every page the webview navigates to,
+    /// including the third-party iframes, can then read whatever the user
+    /// has copied, e.g. passwords. Writing to the clipboard needs no
+    /// permission.
+    pub fn auto_grant_clipboard(mut self) -> Self {
+        self.auto_grant_clipboard = true;
+        self
+    }
+
+    /// Format the numbers and dates for the page with the Windows regional
+    /// settings, e.g. `FormatLocale::User`
+    ///
+    /// Exposes `window.host.format.number(value, options)` and
+    /// `window.host.format.date(value, options)` to the page, for the
+    /// bundles without the Intl data or to match the native UI. See the
+    /// `format` module for the options and the message contract.
+    pub fn format_locale(mut self, locale: FormatLocale) -> Self {
+        self.format_locale = Some(locale);
+        self
+    }
+
+    /// Show the native Win32 context menus the page asks for
+    ///
+    /// Exposes `window.host.menu.show(items, x, y)` to the page, e.g. from
+    /// its `contextmenu` event. Menus nested deeper than `max_depth` or with
+    /// more than `max_items` items in total are refused. See the `menu`
+    /// module for the items and the message contract.
+    pub fn native_menus(mut self, max_depth: usize, max_items: usize) -> Self {
+        self.native_menus = Some(menu::Limits {
+            max_depth,
+            max_items,
+        });
+        self
+    }
+
+    /// Send the event from the chosen id of a native menu to the event loop,
+    /// `None` if the menu was dismissed
+    pub fn on_native_menu(mut self, event: fn(Option<String>) -> EventLoopType) -> Self {
+        self.native_menu_fn = Some(event);
+        self
+    }
+
+    /// Color shown before the page paints and behind the pages without a
+    /// background, white by default
+    ///
+    /// Alpha must be 255, or 0 for a transparent webview in a window built
+    /// `with_transparent(true)`, other values fail the build with
+    /// `Error::UnsupportedBackgroundColor`. The older runtimes ignore the
+    /// color.
+    ///
+    /// The color is given to the runtime in a process-wide environment
+    /// variable, the other webviews wait until this one's controller is
+    /// created, and the previous value is restored then. Don't read or write
+    /// the environment variables from other threads while a webview is
+    /// being built.
+    pub fn background_color(mut self, r: u8, g: u8, b: u8, a: u8) -> Self {
+        self.background_color = Some((r, g, b, a));
+        self
+    }
+
+    /// Place the webview in a part of the client area, e.g. to leave room
+    /// for the native controls, by default it fills the client area
+    pub fn bounds(mut self, bounds: BoundsMode) -> Self {
+        self.bounds = bounds;
+        self
+    }
+
+    /// Leave the physical pixels of the client area to the host, e.g.
+    /// `bounds_inset(40, 0, 0, 0)` for a native toolbar at the top
+    pub fn bounds_inset(self, top: i32, right: i32, bottom: i32, left: i32) -> Self {
+        self.bounds(BoundsMode::Relative {
+            top,
+            left,
+            right,
+            bottom,
+        })
+    }
+
+    /// Compute the bounds from the physical size of the client area
+    pub fn bounds_fn(self, f: fn(PhysicalSize<u32>) -> Rect) -> Self {
+        self.bounds(BoundsMode::Custom(f))
+    }
+
+    /// Create the controller in the shared environment, instead of creating
+    /// a new environment for this webview
+    pub fn with_environment(mut self, environment: &WebViewEnvironment) -> Self {
+        self.environment = Some(environment.clone());
+        self
+    }
+
+    /// Create the environment of this webview with the runtime in the folder,
+    /// e.g. the Fixed Version runtime shipped with the app
+    ///
+    /// The folder has the `msedgewebview2.exe`. Not used with
+    /// `with_environment`, the shared environment is built with its own
+    /// `webview2::EnvironmentBuilder`.
+    pub fn with_browser_executable_path(mut self, path: impl AsRef<Path>) -> Self {
+        self.browser_executable_path = Some(path.as_ref().to_owned());
+        self
+    }
+
+    /// Chromium flags of the browser process of this webview, e.g.
+    /// `--remote-debugging-port=9222` for the tests driving it with CDP
+    ///
+    /// The flags are separated by the spaces. Quotes and the shell
+    /// characters fail the build with `Error::InvalidBrowserArguments`, and
+    /// the flags are warned about with the other suspicious conditions of
+    /// `strict`, many flags weaken the security of the pages. Not used with `with_environment`, same as
+    /// `with_browser_executable_path`.
+    pub fn with_additional_browser_arguments(mut self, args: &str) -> Self {
+        self.browser_arguments = Some(args.to_owned());
+        self
+    }
+
+    /// Keep the messages of `send_msg` in the file until the page
+    /// acknowledges them, for the at least once delivery over the crashes
+    ///
+    /// The page gets `{"__seq": 7, "msg": ...}` instead of the message, and
+    /// acknowledges with `{"__ack": 7}` the highest sequence it has got
+    /// without gaps. The unacknowledged messages are posted again whenever a
+    /// page has loaded, so the page should skip the sequences it has seen.
+    /// `send_msg` fails with `Error::OutboxFull` when the file would grow over
+    /// `max_bytes`. A corrupted file is emptied with a warning of the strict
+    /// mode policy.
+    pub fn durable_outbox(mut self, path: impl AsRef<Path>, max_bytes: u64) -> Self {
+        self.durable_outbox = Some((path.as_ref().to_owned(), max_bytes));
+        self
+    }
+
+    /// Navigate to the url when the webview is created
+    ///
+    /// The navigation starts after the `webview_init` closure has run, so the
+    /// closure can still add the scripts and the handlers for the page. A
+    /// navigation in the closure is replaced by this one.
+    pub fn url(mut self, url: impl Into<String>) -> Self {
+        self.content = Some(InitialContent::Url(url.into()));
+        self
+    }
+
+    /// Navigate to the HTML document when the webview is created, in the
+    /// same order as `url`
+    pub fn html(mut self, html: impl Into<String>) -> Self {
+        self.content = Some(InitialContent::Html(html.into()));
+        self
+    }
+
+    /// Size of the buffer for the page messages while the delivery is
+    /// paused, see `WebViewWrapper::pause_inbound`
+    ///
+    /// By default 1024 messages, and the oldest are dropped. The dropped
+    /// messages are reported like the other forgiven conditions, see `strict`.
+    pub fn inbound_buffer(mut self, capacity: usize, overflow: InboundOverflow) -> Self {
+        self.inbound_capacity = capacity;
+        self.inbound_overflow = overflow;
+        self
+    }
+
+    /// Pause also the events the builder sends to the event loop, the routes
+    /// and `on_close_send_event`, with the page messages
+    pub fn pause_lifecycle_events(mut self, pause: bool) -> Self {
+        self.pause_lifecycle_events = pause;
+        self
+    }
+
+    /// Open the window where it was when `WebViewWrapper::save_window_state`
+    /// was called with the key
+    ///
+    /// The size is restored always, the position only if it's still on one
+    /// of the monitors. Only for the windows created by the builder.
+    pub fn restore_window_state(mut self, key: &str) -> Self {
+        self.window_state_key = Some(key.to_owned());
+        self
+    }
+
+    /// Where the window states are saved, by default a `JsonFileStore`
+    pub fn window_state_store(mut self, store: impl WindowStateStore + 'static) -> Self {
+        self.window_state_store = Rc::new(store);
+        self
+    }
+
+    /// Save the placement of the window as it changes, and open the window
+    /// where it was, see `WindowStatePersistence`
+    ///
+    /// Same as `restore_window_state` with the persistence as the store, and
+    /// the state saved on the moves, the resizes and the close, the events
+    /// must be passed to `WebViewWrapper::handle_window_event`.
+    pub fn persist_window_state(mut self, persistence: &WindowStatePersistence, key: &str) -> Self {
+        self.window_state_key = Some(key.to_owned());
+        self.window_state_store = Rc::new(persistence.clone());
+        self.window_state_persistence = Some(persistence.clone());
+        self
+    }
+
+    /// Run the timed features with the scheduler of the app instead of
+    /// `WebViewWrapper::poll`, see `Scheduler`
+    pub fn scheduler(mut self, scheduler: &Scheduler) -> Self {
+        self.scheduler = Some(scheduler.clone());
+        self
+    }
+
+    /// Tell the page when the window is being resized or moved, see the
+    /// `Interaction`
+    ///
+    /// The gesture ends when there have been no events for the `quiet`
+    /// period, `WebViewWrapper::poll` must be called for it.
+    pub fn interaction_hints(mut self, quiet: Duration) -> Self {
+        self.interaction_quiet = Some(quiet);
+        self
+    }
+
+    /// Called when a resize or move gesture starts or ends, needs
+    /// `interaction_hints`
+    pub fn on_interaction(mut self, handler: fn(Interaction)) -> Self {
+        self.interaction_fn = Some(handler);
+        self
+    }
+
+    /// Send the events of the navigation stages to the event loop, e.g. for
+    /// a native spinner
+    ///
+    /// Registered next to the `show_on` handlers and the navigation hooks,
+    /// all of them are called.
+    pub fn loading_events(mut self, event: fn(LoadingEvent) -> EventLoopType) -> Self {
+        self.loading_event_fn = Some(event);
+        self
+    }
+
+    /// Send the event from the url to the event loop when it changes,
+    /// including `history.pushState` and the fragment changes
+    ///
+    /// The flag is true for the navigations to a new document, and false for
+    /// the changes within the page.
+    pub fn notify_source_changed(mut self, event: fn(&str, bool) -> EventLoopType) -> Self {
+        self.source_event_fn = Some(event);
+        self
+    }
+
+    /// Called with `(can_go_back, can_go_forward)` when they change, e.g.
+    /// to enable the native back and forward buttons
+    ///
+    /// The first call comes after the first navigation.
+    pub fn on_history_changed(mut self, handler: fn(bool, bool)) -> Self {
+        self.history_fn = Some(handler);
+        self
+    }
+
+    /// Send the event from `(can_go_back, can_go_forward)` to the event loop
+    /// when they change, like `on_history_changed`
+    pub fn history_event(mut self, event: fn(bool, bool) -> EventLoopType) -> Self {
+        self.history_event_fn = Some(event);
+        self
+    }
+
+    /// Format of the messages, by default JSON, see the `MessageCodec`
+    pub fn codec(mut self, codec: impl MessageCodec + 'static) -> Self {
+        self.codec = Rc::new(codec);
+        self
+    }
+
+    /// Webview init closure
+    pub fn webview_init(
+        mut self,
+        webview_closure: fn(&webview2::WebView) -> Result<(), webview2::Error>,
+    ) -> Self {
+        self.webview_fn = Some(webview_closure);
+        self
+    }
+
+    /// Add a middleware that sees every message, see the `MessageMiddleware`
+    pub fn message_middleware(mut self, mw: impl MessageMiddleware + 'static) -> Self {
+        self.middlewares.push(Rc::new(mw));
+        self
+    }
+
+    /// Handle the messages from the webview as raw strings
+    ///
+    /// The handler bypasses the serde, `MsgFromWebView` is not deserialized
+    /// when this is set. Useful for prototyping and for the messages that
+    /// can't be described as a Rust enum. Alternatively `serde_json::Value`
+    /// works as `MsgFromWebView`.
+    pub fn raw_message_handler(mut self, handler: fn(String)) -> Self {
+        self.raw_message_fn = Some(handler);
+        self
+    }
+
+    /// Tries to build the webview
+    pub fn build(
+        &self,
+        event_loop: &EventLoop<EventLoopType>,
+    ) -> Result<WebViewWrapper<MsgToWebView>, Error> {
+        let proxy = event_loop.create_proxy();
+        self.build_with_proxy(event_loop, &proxy)
+    }
+
+    /// Tries to build the webview
+    pub fn build_with_proxy(
+        &self,
+        event_loop: &EventLoopWindowTarget<EventLoopType>,
+        event_loop_proxy: &EventLoopProxy<EventLoopType>,
+    ) -> Result<WebViewWrapper<MsgToWebView>, Error> {
+        let mut window_builder = self
+            .window_builder
+            .clone()
+            .unwrap_or_else(|| WindowBuilder::new().with_title(""));
+        if let Some(name) = &self.accessible_name {
+            if window_builder.window.title.is_empty() {
+                window_builder = window_builder.with_title(name);
+            }
+        }
+        if let Some(icon) = &self.icon {
+            // Small icon of the title bar and the big one of Alt+Tab
+            window_builder = window_builder
+                .with_window_icon(Some(icon.clone()))
+                .with_taskbar_icon(Some(icon.clone()));
+        }
+        if self.frameless {
+            window_builder = window_builder.with_decorations(false);
+        }
+        if let Some(key) = &self.window_state_key {
+            if let Some(state) = self.window_state_store.load(key) {
+                window_builder = window_state::apply(window_builder, state, event_loop);
+            }
+        }
+        let window = window_builder
+            .with_visible(self.show_on == ShowWebview::Immediately)
+            .build(&event_loop)?;
+        self.attach(Rc::new(window), true, event_loop_proxy)
+    }
+
+    /// Build the webview in a window owned by the caller
+    ///
+    /// For windows the builder can't express, e.g. created with the custom
+    /// platform flags, or several webviews sharing the window, e.g. two panes
+    /// placed with `bounds`. The window title and visibility are left to the
+    /// caller, the `show_on` only controls when the webview itself is shown.
+    /// `is_window` compares against the id of the given window.
+    pub fn build_in_window(
+        &self,
+        window: Rc<Window>,
+        event_loop_proxy: &EventLoopProxy<EventLoopType>,
+    ) -> Result<WebViewWrapper<MsgToWebView>, Error> {
+        self.attach(window, false, event_loop_proxy)
+    }
+
+    /// Create the controller in the window
+    fn attach(
+        &self,
+        window_ref: Rc<Window>,
+        owns_window: bool,
+        event_loop_proxy: &EventLoopProxy<EventLoopType>,
+    ) -> Result<WebViewWrapper<MsgToWebView>, Error> {
+        if let Some((_, _, _, a)) = self.background_color {
+            if a != 0 && a != 255 {
+                return Err(Error::UnsupportedBackgroundColor);
+            }
+        }
+        if let Some(args) = &self.browser_arguments {
+            if args.contains(BROWSER_ARGUMENTS_REJECTED) {
+                return Err(Error::InvalidBrowserArguments);
+            }
+            strict::Policy::new(self.strict).warn("WebView2 browser started with the flags", args);
+        }
+        let outbox = match &self.durable_outbox {
+            Some((path, max_bytes)) => {
+                let policy = strict::Policy::new(self.strict);
+                Some(Rc::new(outbox::Outbox::open_forgiving(
+                    path, *max_bytes, policy,
+                )?))
+            }
+            None => None,
+        };
+        let parent_hwnd = window_ref.hwnd() as u32;
+
+        // Physical size of the client area, unlike GetClientRect in the
+        // callback this is right also for the logical sizes on high-DPI
+        let inner_size = window_ref.inner_size();
+        let bounds = Rc::new(Cell::new(self.bounds));
+        let mut webview = WebViewWrapper {
+            msg_to_webview_type: PhantomData::<MsgToWebView>,
+            controller: Rc::new(RefCell::new(None)),
+            pending: Rc::new(RefCell::new(VecDeque::new())),
+            calls: Rc::new(rpc::Calls::new(self.codec.clone())),
+            middlewares: Rc::new(self.middlewares.clone()),
+            codec: self.codec.clone(),
+            outbox,
+            focus_webview: self.focus_webview,
+            inbound: Rc::new(inbound::Inbound::new(
+                self.inbound_capacity,
+                self.inbound_overflow,
+                strict::Policy::new(self.strict),
+            )),
+            temp_settings: Rc::new(RefCell::new(None)),
+            closed: Rc::new(Cell::new(false)),
+            source: Rc::new(RefCell::new(None)),
+            document_title: Rc::new(RefCell::new(None)),
+            history: Rc::new(Cell::new(None)),
+            files: Rc::new(files::Grants::default()),
+            #[cfg(feature = "cdp")]
+            tracer: Rc::new(trace::Tracer::default()),
+            window_state_store: self.window_state_store.clone(),
+            window_state_persistence: match (&self.window_state_persistence, &self.window_state_key)
+            {
+                (Some(persistence), Some(key)) => {
+                    Some((persistence.clone(), Rc::from(key.as_str())))
+                }
+                _ => None,
+            },
+            scheduled: None,
+            recover: None,
+            interaction: self
+                .interaction_quiet
+                .map(|quiet| Rc::new(interaction::Detector::new(quiet))),
+            interaction_fn: self.interaction_fn,
+            watchdog: self.watchdog.map(|(period, reload_after, f)| {
+                let event_loop_proxy = event_loop_proxy.clone();
+                Rc::new(watchdog::Watchdog::new(
+                    period,
+                    reload_after,
+                    Instant::now(),
+                    move |event| {
+                        let _ = event_loop_proxy.send_event(f(event));
+                    },
+                ))
+            }),
+            drag_start: self.cross_window_drag.map(|_| Rc::new(RefCell::new(None))),
+            layout: Rc::new(layout::LayoutHistory::new(
+                self.debug_layout,
+                strict::Policy::new(self.strict),
+                bounds.clone(),
+            )),
+            bounds,
+            owns_window,
+            policy: strict::Policy::new(self.strict),
+            foreign_windows: Rc::new(strict::ForeignWindows::default()),
+            title: Rc::new(show::DeferredTitle::default()),
+            tag: self.tag.as_deref().map(Rc::from),
+            window: window_ref.clone(),
+        };
+        if let Some(scheduler) = &self.scheduler {
+            let timers = webview.timers();
+            let context = webview.error_context("scheduler");
+            let registration = scheduler::Registration::new(scheduler, move |now| {
+                timers
+                    .poll(now)
+                    .map_err(|err| err.with_context(context.clone()))
+            });
+            webview.scheduled = Some(Rc::new(registration));
+
+            // First ping of the watchdog
+            webview.schedule();
+        }
+        let settings = self.settings_fn;
+        let debug_mode = self.debug_mode;
+        let webview_with = self.webview_fn;
+        let content = self.content.clone();
+        let controller_weak = Rc::downgrade(&webview.controller);
+        let pending_weak = Rc::downgrade(&webview.pending);
+        let calls_weak = Rc::downgrade(&webview.calls);
+        let outbox = webview.outbox.clone();
+        let temp_settings_weak = Rc::downgrade(&webview.temp_settings);
+        let closed = webview.closed.clone();
+        let source_weak = Rc::downgrade(&webview.source);
+        let document_title_weak = Rc::downgrade(&webview.document_title);
+        let history_weak = Rc::downgrade(&webview.history);
+        let history_fn = self.history_fn;
+        let source_event_fn = self.source_event_fn;
+        let loading_event_fn = self.loading_event_fn;
+        let history_event_fn = self.history_event_fn;
+        let files_weak = Rc::downgrade(&webview.files);
+        let layout = webview.layout.clone();
+        let watchdog = webview.watchdog.clone();
+        let drag_start = webview.drag_start.clone();
+        let cross_window_drag = self.cross_window_drag;
+        let bounds = webview.bounds.clone();
+        let title = webview.title.clone();
+        let inbound = webview.inbound.clone();
+        let pause_lifecycle_events = self.pause_lifecycle_events;
+        let middlewares = webview.middlewares.clone();
+        let codec = webview.codec.clone();
+        let window_weak = Rc::downgrade(&window_ref);
+        let event_loop_proxy = event_loop_proxy.clone();
+        let show_on = self.show_on;
+        let focus_webview = self.focus_webview;
+        let raw_message_fn = self.raw_message_fn;
+        let message_error_fn = self.message_error_fn;
+        let move_focus_fn = self.move_focus_fn;
+        let routes = self.routes.clone();
+        let accelerators = self.accelerators.clone();
+        let accessible_name = self.accessible_name.clone();
+        let max_fps = self.max_fps;
+        let fetch_handler = self.fetch_handler.clone();
+        let format_locale = self.format_locale.clone();
+        let native_menus = self.native_menus;
+        let native_menu_fn = self.native_menu_fn;
+        let favicon_fn = self.favicon_fn;
+        let frameless = self.frameless;
+        let favicon_window_icon = self.favicon_window_icon && owns_window;
+        let icon = self.icon.clone();
+        let auto_grant_clipboard = self.auto_grant_clipboard;
+        let permission_fn = self.permission_fn.clone();
+        let permission_event_fn = self.permission_event_fn;
+        let policy = strict::Policy::new(self.strict);
+        let external_links = self.external_links.clone();
+        let navigation_filter = self.navigation_filter;
+        let new_window = self.new_window.clone();
+        let background_color = self.background_color;
+        let close_requested = self.close_requested.clone();
+        let navigation_started_fn = self.navigation_started_fn.clone();
+        let navigation_completed_fn = self.navigation_completed_fn;
+        let navigation_failed_fn = self.navigation_failed_fn;
+        let downloads = self.downloads.clone();
+        let download_folder = self.download_folder.clone();
+        let show_on_failure = self.show_on_failure;
+        let process_failed_fn = self.process_failed_fn;
+        let process_failure_fn = self.process_failure_fn;
+        let ready_event = self.ready_event.clone();
+        let auto_recover = self.auto_recover;
+
+        // Nothing would ever deliver the queued messages, the calls fail with
+        // `Error::WebviewClosed` instead
+        let closed_ = webview.closed.clone();
+        let pending_weak_ = pending_weak.clone();
+        let calls_weak_ = calls_weak.clone();
+        let failed = move |context: &str, err: &dyn Debug| {
+            fail_creation(policy, context, err, &closed_, &pending_weak_, &calls_weak_);
+        };
+        let failed_ = failed.clone();
+
+        // Url of the page when the browser process exited, the rebuilt
+        // controller navigates back to it, with `auto_recover`
+        let recovered_url = Rc::new(RefCell::new(None::<String>));
+        let recovery = Rc::new(RefCell::new(None::<Weak<dyn Fn()>>));
+        let recovery_ = recovery.clone();
+
+        let create = move |env: &webview2::Environment, color: Option<background::Override>| {
+            // Following is ran asynchronously somewhere after the
+            // WebViewBuilder::build() finishes, for this reason the moved
+            // variables must be passed as a weak.
+            let env_ = env.clone();
+
+            // Ran again for each rebuild of `auto_recover`
+            let outbox = outbox.clone();
+            let content = content.clone();
+            let controller_weak = controller_weak.clone();
+            let pending_weak = pending_weak.clone();
+            let calls_weak = calls_weak.clone();
+            let temp_settings_weak = temp_settings_weak.clone();
+            let closed = closed.clone();
+            let source_weak = source_weak.clone();
+            let document_title_weak = document_title_weak.clone();
+            let history_weak = history_weak.clone();
+            let files_weak = files_weak.clone();
+            let layout = layout.clone();
+            let watchdog = watchdog.clone();
+            let drag_start = drag_start.clone();
+            let bounds = bounds.clone();
+            let title = title.clone();
+            let inbound = inbound.clone();
+            let middlewares = middlewares.clone();
+            let codec = codec.clone();
+            let window_weak = window_weak.clone();
+            let event_loop_proxy = event_loop_proxy.clone();
+            let routes = routes.clone();
+            let accelerators = accelerators.clone();
+            let accessible_name = accessible_name.clone();
+            let fetch_handler = fetch_handler.clone();
+            let format_locale = format_locale.clone();
+            let icon = icon.clone();
+            let permission_fn = permission_fn.clone();
+            let external_links = external_links.clone();
+            let new_window = new_window.clone();
+            let close_requested = close_requested.clone();
+            let navigation_started_fn = navigation_started_fn.clone();
+            let downloads = downloads.clone();
+            let download_folder = download_folder.clone();
+            let ready_event = ready_event.clone();
+            let failed_ = failed_.clone();
+            let recovered_url = recovered_url.clone();
+            let recovery = recovery_.clone();
+
+            // Held until the controller is created or its creation fails
+            let color = Rc::new(RefCell::new(color));
+            let color_ = color.clone();
+            let result = env.create_controller(parent_hwnd as HWND, move |host| {
+                color_.borrow_mut().take();
+                let controller = match host {
+                    Ok(controller) => controller,
+                    Err(err) => {
+                        failed_("Controller was not created", &err);
+                        return Ok(());
+                    }
+                };
+                if closed.get() {
+                    // Closed before the controller was created
+                    return controller.close();
+                }
+                let webview = controller.get_webview()?;
+
+                if let Some(settings_fn) = settings {
+                    webview.get_settings().map(|o| settings_fn(&o))??;
+                }
+                if debug_mode {
+                    let settings = webview.get_settings()?;
+                    settings.put_are_dev_tools_enabled(true)?;
+                    settings.put_are_default_context_menus_enabled(true)?;
+                    settings.put_is_status_bar_enabled(true)?;
+                }
+
+                if let Some(max_fps) = max_fps {
+                    webview.add_script_to_execute_on_document_created(
+                        &throttle::script(max_fps),
+                        |_| Ok(()),
+                    )?;
+                }
+
+                if fetch_handler.is_some() {
+                    webview.add_script_to_execute_on_document_created(fetch::SCRIPT, |_| Ok(()))?;
+                }
+
+                if format_locale.is_some() {
+                    webview
+                        .add_script_to_execute_on_document_created(format::SCRIPT, |_| Ok(()))?;
+                }
+
+                if native_menus.is_some() {
+                    webview.add_script_to_execute_on_document_created(menu::SCRIPT, |_| Ok(()))?;
+                }
+
+                if favicon_fn.is_some() || favicon_window_icon {
+                    webview
+                        .add_script_to_execute_on_document_created(favicon::SCRIPT, |_| Ok(()))?;
+                }
+
+                if frameless {
+                    webview.add_script_to_execute_on_document_created(drag::SCRIPT, |_| Ok(()))?;
+                }
+
+                if cross_window_drag.is_some() {
+                    webview.add_script_to_execute_on_document_created(dnd::SCRIPT, |_| Ok(()))?;
+                }
+
+                if let Some(watchdog) = &watchdog {
+                    webview
+                        .add_script_to_execute_on_document_created(watchdog::SCRIPT, |_| Ok(()))?;
+
+                    // The next page answers once it's loaded
+                    let watchdog_ = watchdog.clone();
+                    webview.add_navigation_starting(move |_webview, _args| {
+                        watchdog_.restart(Instant::now());
+                        Ok(())
+                    })?;
+                }
+
+                let rect = bounds.get().rect(inner_size);
+                controller.put_bounds(rect.into())?;
+                layout.record(LayoutTrigger::Created, LayoutChange::Bounds(rect));
+
+                let window_weak_ = window_weak.clone();
+                let title_ = title.clone();
+                webview.add_document_title_changed(move |args| {
+                    let title = args.get_document_title()?;
+                    if let Some(document_title) = document_title_weak.upgrade() {
+                        *document_title.borrow_mut() = Some(title.clone());
+                    }
+                    if !owns_window {
+                        return Ok(());
+                    }
+                    if let Some(window_rc) = window_weak_.upgrade() {
+                        let title = match &accessible_name {
+                            Some(name) if title.is_empty() => name.clone(),
+                            _ => title,
+                        };
+                        title_.set(&window_rc, title);
+                    }
+                    Ok(())
+                })?;
+                if !owns_window && show_on != ShowWebview::Immediately {
+                    // The window may be visible already, hide the webview
+                    // instead
+                    controller.put_is_visible(false)?;
+                }
+
+                // Tab past the first or the last element, without handling
+                // the focus would die
+                controller.add_move_focus_requested(move |controller, args| {
+                    let reason = args.get_reason()?;
+                    let handled = move_focus_fn.map_or(false, |f| f(reason));
+                    if !handled {
+                        // Next focuses the first, Previous the last element
+                        controller.move_focus(reason)?;
+                    }
+                    args.put_handled(true)?;
+                    Ok(())
+                })?;
+
+                // Browser shortcuts
+                if let Some(accelerators) = accelerators {
+                    let event_loop_proxy = event_loop_proxy.clone();
+                    controller.add_accelerator_key_pressed(move |_controller, args| {
+                        let is_down = match args.get_key_event_kind()? {
+                            webview2::KeyEventKind::KeyDown
+                            | webview2::KeyEventKind::SystemKeyDown => true,
+                            _ => false,
+                        };
+                        let accelerator = Accelerator::pressed(args.get_virtual_key()?);
+                        match accelerators.action(&accelerator) {
+                            None | Some(AcceleratorAction::PassToBrowser) => {}
+                            Some(AcceleratorAction::Handled) => {
+                                args.put_handled(true)?;
+                            }
+                            Some(AcceleratorAction::ForwardToEventLoop(event)) => {
+                                if is_down {
+                                    let _ = event_loop_proxy.send_event(event.clone());
+                                }
+                                args.put_handled(true)?;
+                            }
+                        }
+                        Ok(())
+                    })?;
+                }
+
+                // Decision of the host, then the clipboard without the
+                // permission prompt, then the host's later answer
+                if permission_fn.is_some() || auto_grant_clipboard || permission_event_fn.is_some()
+                {
+                    let event_loop_proxy_ = event_loop_proxy.clone();
+                    webview.add_permission_requested(move |_webview, args| {
+                        let kind = args.get_permission_kind()?;
+                        let decision = match &permission_fn {
+                            Some(f) => f(&args.get_uri()?, kind),
+                            None => None,
+                        };
+                        let decision = decision.or_else(|| match kind {
+                            webview2::PermissionKind::ClipboardRead if auto_grant_clipboard => {
+                                Some(webview2::PermissionState::Allow)
+                            }
+                            _ => None,
+                        });
+                        match (decision, permission_event_fn) {
+                            (Some(state), _) => args.put_state(state)?,
+                            (None, Some(f)) => {
+                                let request = PermissionRequest::defer(args)?;
+                                let _ = event_loop_proxy_.send_event(f(request));
+                            }
+                            (None, None) => (),
+                        }
+                        Ok(())
+                    })?;
+                }
+
+                let event_loop_proxy_ = event_loop_proxy.clone();
+                webview.add_source_changed(move |webview, args| {
+                    let url = webview.get_source()?;
+                    if let Some(f) = source_event_fn {
+                        let event = f(&url, args.get_is_new_document()?);
+                        let _ = event_loop_proxy_.send_event(event);
+                    }
+                    if let Some(source) = source_weak.upgrade() {
+                        *source.borrow_mut() = Some(url);
+                    }
+                    Ok(())
+                })?;
+
+                // Back and forward, also after the navigations for the first
+                // state
+                let event_loop_proxy_ = event_loop_proxy.clone();
+                let history_changed = move |webview: &webview2::WebView| {
+                    let history_rc = match history_weak.upgrade() {
+                        Some(history_rc) => history_rc,
+                        None => return Ok(()),
+                    };
+                    let state = (webview.get_can_go_back()?, webview.get_can_go_forward()?);
+                    if history_rc.replace(Some(state)) == Some(state) {
+                        return Ok(());
+                    }
+                    if let Some(f) = history_fn {
+                        f(state.0, state.1);
+                    }
+                    if let Some(f) = history_event_fn {
+                        let _ = event_loop_proxy_.send_event(f(state.0, state.1));
+                    }
+                    Ok(())
+                };
+                let history_changed = Rc::new(history_changed);
+                let history_changed_ = history_changed.clone();
+                webview.add_history_changed(move |webview| history_changed_(&webview))?;
+                webview
+                    .add_navigation_completed(move |webview, _args| history_changed(&webview))?;
+
+                // The page navigated away from won't reply to its calls
+                let calls_weak_ = calls_weak.clone();
+                webview.add_navigation_starting(move |_webview, _args| {
+                    if let Some(calls) = calls_weak_.upgrade() {
+                        calls.cancel_all();
+                    }
+                    Ok(())
+                })?;
+
+                // Granted host files
+                let files_weak_ = files_weak.clone();
+                webview.add_navigation_starting(move |_webview, _args| {
+                    if let Some(files) = files_weak_.upgrade() {
+                        files.navigated();
+                    }
+                    Ok(())
+                })?;
+                webview.add_web_resource_requested_filter(
+                    &format!("{}*", files::URL_PREFIX),
+                    webview2::WebResourceContext::All,
+                )?;
+                webview.add_web_resource_requested(move |_webview, args| {
+                    let files = match files_weak.upgrade() {
+                        Some(files) => files,
+                        None => return Ok(()),
+                    };
+                    let request = args.get_request()?;
+                    let range = request.get_headers()?.get_header("Range").ok();
+                    let response = files.serve(&request.get_uri()?, range.as_deref());
+                    args.put_response(env_.create_web_resource_response(
+                        webview2::Stream::from_bytes(&response.body),
+                        response.status,
+                        response.reason,
+                        &response.header_lines(),
+                    )?)?;
+                    Ok(())
+                })?;
+
+                // Navigation hooks of the caller, separate from the show
+                // trigger and the routes
+                if let Some(f) = navigation_started_fn {
+                    webview.add_navigation_starting(move |webview, args| f(&webview, &args))?;
+                }
+                if let Some(f) = navigation_completed_fn {
+                    webview.add_navigation_completed(move |webview, args| f(&webview, &args))?;
+                }
+
+                // Loading indicators
+                if let Some(f) = loading_event_fn {
+                    let event_loop_proxy_ = event_loop_proxy.clone();
+                    webview.add_navigation_starting(move |_webview, args| {
+                        let navigation_id = args.get_navigation_id()?;
+                        let _ = event_loop_proxy_
+                            .send_event(f(LoadingEvent::Started { navigation_id }));
+                        Ok(())
+                    })?;
+                    let event_loop_proxy_ = event_loop_proxy.clone();
+                    webview.add_content_loading(move |_webview, args| {
+                        let navigation_id = args.get_navigation_id()?;
+                        let _ = event_loop_proxy_
+                            .send_event(f(LoadingEvent::ContentLoading { navigation_id }));
+                        Ok(())
+                    })?;
+                    let event_loop_proxy_ = event_loop_proxy.clone();
+                    webview.add_navigation_completed(move |_webview, args| {
+                        let event = LoadingEvent::Completed {
+                            navigation_id: args.get_navigation_id()?,
+                            is_success: args.get_is_success()?,
+                        };
+                        let _ = event_loop_proxy_.send_event(f(event));
+                        Ok(())
+                    })?;
+                }
+
+                if let Some((staging, decide, event)) = &downloads {
+                    download::register(
+                        &webview,
+                        staging,
+                        *decide,
+                        *event,
+                        event_loop_proxy.clone(),
+                        policy,
+                    )?;
+                } else if let Some(folder) = &download_folder {
+                    download::save_to(&webview, folder, policy)?;
+                }
+
+                // Crashed processes
+                let closed_ = closed.clone();
+                let controller_weak_ = controller_weak.clone();
+                let pending_weak_ = pending_weak.clone();
+                let calls_weak_ = calls_weak.clone();
+                let source_weak_ = source_weak.clone();
+                let recovered_url_ = recovered_url.clone();
+                let recovery_ = recovery.clone();
+                let event_loop_proxy_ = event_loop_proxy.clone();
+                webview.add_process_failed(move |webview, args| {
+                    if let Some(f) = process_failed_fn {
+                        f(&webview, &args)?;
+                    }
+                    let failure = ProcessFailure::from(args.get_process_failed_kind()?);
+                    match failure {
+                        ProcessFailure::RenderProcessExited if auto_recover => webview.reload()?,
+
+                        // Messages to the dead controller would be lost
+                        ProcessFailure::BrowserProcessExited => {
+                            let was_closed = closed_.replace(true);
+                            if let Some(controller_rc) = controller_weak_.upgrade() {
+                                controller_rc.borrow_mut().take();
+                            }
+                            if let Some(pending_rc) = pending_weak_.upgrade() {
+                                pending_rc.borrow_mut().clear();
+                            }
+                            if let Some(calls) = calls_weak_.upgrade() {
+                                calls.cancel_all();
+                            }
+
+                            // Rebuilt with a new environment, unless it was
+                            // closed, the messages sent meanwhile wait for the
+                            // new controller
+                            let recover = recovery_.borrow().as_ref().and_then(Weak::upgrade);
+                            if let (Some(recover), false) = (recover, was_closed) {
+                                if let Some(source_rc) = source_weak_.upgrade() {
+                                    *recovered_url_.borrow_mut() = source_rc.borrow().clone();
+                                }
+                                closed_.set(false);
+                                recover();
+                            }
+                        }
+                        _ => (),
+                    }
+                    if let Some(f) = process_failure_fn {
+                        let _ = event_loop_proxy_.send_event(f(failure));
+                    }
+                    Ok(())
+                })?;
+
+                // Show the window after event trigger
+                let window_weak_ = window_weak.clone();
+                let controller_weak_ = controller_weak.clone();
+                let layout_ = layout.clone();
+                let do_it = move || -> Result<(), Error> {
+                    if let (Some(controller_rc), Some(window_rc)) =
+                        (controller_weak_.upgrade(), window_weak_.upgrade())
+                    {
+                        if let Some(controller) = controller_rc.borrow().as_ref() {
+                            show::show(
+                                controller,
+                                &window_rc,
+                                owns_window,
+                                focus_webview,
+                                &layout_,
+                                &title,
+                            )?;
+                        }
+                    }
+                    Ok(())
+                };
+                let mut dom_content_loaded = None;
+
+                // Failed navigations, the error page has no content loading
+                // or DOMContentLoaded of its own to show the window
+                let show_on_failed = match show_on {
+                    ShowWebview::OnContentLoading | ShowWebview::OnDomContentLoaded => {
+                        show_on_failure
+                    }
+                    _ => false,
+                };
+                if navigation_failed_fn.is_some() || show_on_failed {
+                    let do_it = do_it.clone();
+                    let event_loop_proxy_ = event_loop_proxy.clone();
+                    let started = Rc::new(RefCell::new(None));
+                    let started_ = started.clone();
+                    webview.add_navigation_starting(move |_webview, args| {
+                        *started_.borrow_mut() = Some((args.get_navigation_id()?, args.get_uri()?));
+                        Ok(())
+                    })?;
+                    webview.add_navigation_completed(move |webview, args| {
+                        if args.get_is_success()? {
+                            return Ok(());
+                        }
+                        if show_on_failed {
+                            policy.handler("Showing the webview failed", do_it())?;
+                        }
+                        if let Some(f) = navigation_failed_fn {
+                            let navigation_id = args.get_navigation_id()?;
+                            let url = match started.borrow_mut().take() {
+                                Some((id, url)) if id == navigation_id => url,
+                                _ => webview.get_source()?,
+                            };
+                            let _ = event_loop_proxy_.send_event(f(NavigationFailed {
+                                navigation_id,
+                                url,
+                                status: args.get_web_error_status()?,
+                            }));
+                        }
+                        Ok(())
+                    })?;
+                }
+
+                match show_on {
+                    ShowWebview::Immediately | ShowWebview::Manual => {}
+                    ShowWebview::OnNavigationCompleted => {
+                        webview.add_navigation_completed(move |_, args| {
+                            if !show_on_failure && !args.get_is_success()? {
+                                return Ok(());
+                            }
+                            policy.handler("Showing the webview failed", do_it())
+                        })?;
+                    }
+                    ShowWebview::OnContentLoading => {
+                        webview.add_content_loading(move |_, _args| {
+                            policy.handler("Showing the webview failed", do_it())
+                        })?;
+                    }
+                    ShowWebview::OnDomContentLoaded => {
+                        webview
+                            .add_script_to_execute_on_document_created(show::SCRIPT, |_| Ok(()))?;
+                        dom_content_loaded = Some(show::DomContentLoaded {
+                            on_loaded: move || {
+                                let _ = policy.handler("Showing the webview failed", do_it());
+                            },
+                        });
+                    }
+                }
+
+                // Webview requested a close?
+                let window_weak_ = window_weak.clone();
+                let event_loop_proxy_ = event_loop_proxy.clone();
+                let inbound_ = inbound.clone();
+                webview.add_window_close_requested(move |_webview| {
+                    if window_weak_.upgrade().is_none() {
+                        return Ok(());
+                    }
+                    match &close_requested {
+                        Some(OnCloseRequested::Call(handler)) => handler(),
+                        Some(OnCloseRequested::SendEvent(event)) => {
+                            let event = event.clone();
+                            let proxy = event_loop_proxy_.clone();
+                            let send = move || {
+                                let _ = proxy.send_event(event);
+                            };
+                            if pause_lifecycle_events {
+                                inbound_.deliver(send);
+                            } else {
+                                send();
+                            }
+                        }
+                        None => {}
+                    }
+                    Ok(())
+                })?;
+
+                // Navigations decided by the url
+                if let Some((filter, include_initial)) = navigation_filter {
+                    let initial = Cell::new(!include_initial);
+                    webview.add_navigation_starting(move |_webview, args| {
+                        if initial.replace(false) {
+                            return Ok(());
+                        }
+                        let uri = args.get_uri()?;
+                        match filter(&uri) {
+                            NavigationDecision::Allow => {}
+                            NavigationDecision::Cancel => args.put_cancel(true)?,
+                            NavigationDecision::OpenExternal => {
+                                args.put_cancel(true)?;
+                                external::open(&uri);
+                            }
+                        }
+                        Ok(())
+                    })?;
+                }
+
+                // Links opened in the system
+                let system_links = external_links.clone();
+                if !external_links.is_empty() {
+                    let external_links_ = external_links.clone();
+                    webview.add_navigation_starting(move |_webview, args| {
+                        let uri = args.get_uri()?;
+                        if external_links_.is_external(&uri) {
+                            args.put_cancel(true)?;
+                            external::open(&uri);
+                        }
+                        Ok(())
+                    })?;
+                    webview.add_new_window_requested(move |_webview, args| {
+                        let uri = args.get_uri()?;
+                        if external_links.is_external(&uri) {
+                            args.put_handled(true)?;
+                            external::open(&uri);
+                        }
+                        Ok(())
+                    })?;
+                }
+
+                // New windows, handled synchronously so that no deferral is
+                // needed, the popup is not created for the handled requests
+                if let Some(policy) = new_window {
+                    let event_loop_proxy_ = event_loop_proxy.clone();
+                    webview.add_new_window_requested(move |webview, args| {
+                        if args.get_handled()? {
+                            return Ok(());
+                        }
+                        let uri = args.get_uri()?;
+                        args.put_handled(true)?;
+                        match &policy {
+                            NewWindowPolicy::SuppressAndNavigateSelf => webview.navigate(&uri)?,
+                            NewWindowPolicy::OpenInSystemBrowser => {
+                                if system_links.may_open(&uri) {
+                                    external::open(&uri);
+                                }
+                            }
+                            NewWindowPolicy::Block => {}
+                            NewWindowPolicy::OpenWebview(event) => {
+                                let _ = event_loop_proxy_.send_event(event(&uri));
+                            }
+                        }
+                        Ok(())
+                    })?;
+                }
+
+                // Restore the navigation scoped settings when leaving the
+                // origin, before the navigation so that settings such as the
+                // scripting are in place for the new document
+                webview.add_navigation_starting(move |webview, args| {
+                    if let Some(temp_settings_rc) = temp_settings_weak.upgrade() {
+                        let uri = args.get_uri()?;
+                        let mut temp_settings = temp_settings_rc.borrow_mut();
+                        let leaving = match temp_settings.as_ref() {
+                            Some(t) => t.origin != settings::url_origin(&uri),
+                            None => false,
+                        };
+                        if leaving {
+                            if let Some(t) = temp_settings.take() {
+                                t.previous.apply(&webview.get_settings()?)?;
+                            }
+                        }
+                    }
+                    Ok(())
+                })?;
+
+                // Routes
+                if !routes.is_empty() {
+                    let event_loop_proxy = event_loop_proxy.clone();
+                    let inbound_ = inbound.clone();
+                    webview.add_navigation_starting(move |_webview, args| {
+                        if let Some(event) = routes.resolve(&args.get_uri()?) {
+                            let proxy = event_loop_proxy.clone();
+                            let send = move || {
+                                let _ = proxy.send_event(event);
+                            };
+                            if pause_lifecycle_events {
+                                inbound_.deliver(send);
+                            } else {
+                                send();
+                            }
+                        }
+                        Ok(())
+                    })?;
+                }
+
+                // Messages of the outbox to the loaded page
+                if let Some(outbox) = outbox.clone() {
+                    let middlewares_ = middlewares.clone();
+                    let codec_ = codec.clone();
+                    webview.add_navigation_completed(move |webview, args| {
+                        if !args.get_is_success()? {
+                            return Ok(());
+                        }
+                        let unacknowledged = outbox.unacknowledged();
+                        policy.each(
+                            "Outbox message was not delivered",
+                            unacknowledged,
+                            |(seq, msg)| -> Result<(), Error> {
+                                let envelope = outbox::Envelope { seq: *seq, msg };
+                                let v = serde_json::to_value(&envelope)?;
+                                post_value(&webview, &middlewares_, &*codec_, v)
+                            },
+                        );
+                        Ok(())
+                    })?;
+                }
+
+                // Message passing
+                let middlewares_ = middlewares.clone();
+                let codec_ = codec.clone();
+                let outbox_ = outbox.clone();
+                let fetch_interceptor = fetch_handler.map(|handler| fetch::FetchInterceptor {
+                    handler,
+                    controller: controller_weak.clone(),
+                    middlewares: middlewares.clone(),
+                });
+                let formatter = format_locale.map(|locale| format::Formatter {
+                    locale,
+                    controller: controller_weak.clone(),
+                });
+                let native_menu = native_menus.map(|limits| menu::NativeMenu {
+                    limits,
+                    controller: controller_weak.clone(),
+                    window: window_weak.clone(),
+                    event: native_menu_fn.map(|f| (f, event_loop_proxy.clone())),
+                });
+                let favicon_changed = if favicon_fn.is_some() || favicon_window_icon {
+                    Some(favicon::FaviconChanged {
+                        window: Some((window_weak.clone(), icon.clone()))
+                            .filter(|_| favicon_window_icon),
+                        event: favicon_fn.map(|f| (f, event_loop_proxy.clone())),
+                    })
+                } else {
+                    None
+                };
+                let drag_window = if frameless {
+                    Some(drag::DragWindow {
+                        window: window_weak.clone(),
+                    })
+                } else {
+                    None
+                };
+                let pong = watchdog.map(|watchdog| watchdog::Pong { watchdog });
+                let drag_started = match (cross_window_drag, drag_start) {
+                    (Some(max_bytes), Some(started)) => Some(dnd::DragStart {
+                        started,
+                        max_bytes,
+                        policy,
+                    }),
+                    _ => None,
+                };
+                webview.add_web_message_received(move |_webview, args| {
+                    let message = args.try_get_web_message_as_string()?;
+
+                    let calls = calls_weak.upgrade();
+                    let mut builtin: Vec<&dyn MessageMiddleware> = Vec::new();
+                    if let Some(calls) = &calls {
+                        builtin.push(&**calls);
+                    }
+                    if let Some(fetch_interceptor) = &fetch_interceptor {
+                        builtin.push(fetch_interceptor);
+                    }
+                    if let Some(formatter) = &formatter {
+                        builtin.push(formatter);
+                    }
+                    if let Some(native_menu) = &native_menu {
+                        builtin.push(native_menu);
+                    }
+                    if let Some(favicon_changed) = &favicon_changed {
+                        builtin.push(favicon_changed);
+                    }
+                    if let Some(drag_window) = &drag_window {
+                        builtin.push(drag_window);
+                    }
+                    if let Some(dom_content_loaded) = &dom_content_loaded {
+                        builtin.push(dom_content_loaded);
+                    }
+                    if let Some(outbox) = &outbox_ {
+                        builtin.push(&**outbox);
+                    }
+                    if let Some(pong) = &pong {
+                        builtin.push(pong);
+                    }
+                    if let Some(drag_started) = &drag_started {
+                        builtin.push(drag_started);
+                    }
+                    let message = match middlewares_.inbound_forgiving(message, &builtin, policy) {
+                        Some(message) => message,
+                        None => return Ok(()),
+                    };
+
+                    if let Some(raw_message_fn) = raw_message_fn {
+                        inbound.deliver(move || raw_message_fn(message));
+                        return Ok(());
+                    }
+
+                    let msg = codec::decode_forgiving::<MsgFromWebView>(
+                        &*codec_,
+                        message,
+                        message_error_fn,
+                        policy,
+                    );
+                    if let Some(msg) = msg {
+                        let proxy = event_loop_proxy.clone();
+                        inbound.deliver(move || msg.pass_to_event_loop_proxy(&proxy));
+                    }
+
+                    Ok(())
+                })?;
+
+                if let Some(webview_with_fn) = webview_with {
+                    webview_with_fn(&webview)?;
+                }
+                let recovered = recovered_url.borrow_mut().take();
+                match (recovered, &content) {
+                    (Some(url), _) => webview.navigate(&url)?,
+                    (None, Some(InitialContent::Url(url))) => webview.navigate(url)?,
+                    (None, Some(InitialContent::Html(html))) => webview.navigate_to_string(html)?,
+                    (None, None) => {}
+                }
+
+                // Window was focused before the controller existed
+                if focus_webview && show_on == ShowWebview::Immediately {
+                    controller.move_focus(webview2::MoveFocusReason::Programmatic)?;
+                }
+
+                // Theme changes before the controller existed were not emulated
+                if let Some(window) = window_weak.upgrade() {
+                    emulate_theme(&controller, window.theme())?;
+                }
+
+                if let Some(controller_rc) = controller_weak.upgrade() {
+                    let mut controller_cell = controller_rc.borrow_mut();
+                    *controller_cell = Some(controller);
+                }
+
+                // Deliver the messages sent before the controller was ready
+                if let Some(pending_rc) = pending_weak.upgrade() {
+                    let queued: Vec<_> = pending_rc.borrow_mut().drain(..).collect();
+                    policy.each("Queued message was not delivered", queued, |msg| {
+                        post_value(&webview, &middlewares, &*codec, msg.clone())
+                    });
+                }
+
+                if let Some(event) = &ready_event {
+                    let _ = event_loop_proxy.send_event(event.clone());
+                }
+
+                Ok(())
+            });
+            if result.is_err() {
+                color.borrow_mut().take();
+            }
+            result
+        };
+        let create = Rc::new(create);
+        let failed_ = failed.clone();
+        let created = move |env: Result<&webview2::Environment, &Error>| match env {
+            Ok(env) => {
+                let env = env.clone();
+                let create = create.clone();
+                let failed = failed_.clone();
+                background::create(background_color, move |color| {
+                    if let Err(err) = create(&env, color) {
+                        failed("Controller was not created", &err);
+                    }
+                });
+            }
+            Err(err) => failed_("WebView2 environment failed", err),
+        };
+        match &self.environment {
+            Some(environment) => environment.with(created)?,
+            None => {
+                let browser_executable_path = self.browser_executable_path.clone();
+                let browser_arguments = self.browser_arguments.clone();
+                let build_environment = move || -> Result<(), Error> {
+                    let mut builder = webview2::EnvironmentBuilder::new();
+                    if let Some(path) = &browser_executable_path {
+                        builder = builder.with_browser_executable_folder(path);
+                    }
+                    if let Some(args) = &browser_arguments {
+                        builder = builder.with_additional_browser_arguments(args);
+                    }
+                    let created = created.clone();
+                    builder
+                        .build(move |env| {
+                            created(env.map_err(environment::build_error).as_ref());
+                            Ok(())
+                        })
+                        .map_err(environment::build_error)
+                };
+                build_environment()?;
+
+                // Held by the wrapper, the process failure handler has a weak
+                if self.auto_recover {
+                    let recover: Rc<dyn Fn()> = Rc::new(move || {
+                        if let Err(err) = build_environment() {
+                            failed("WebView2 environment failed", &err);
+                        }
+                    });
+                    *recovery.borrow_mut() = Some(Rc::downgrade(&recover));
+                    webview.recover = Some(recover);
+                }
+            }
+        }
+        Ok(webview)
+    }
+
+    /// Send the event to the event loop once the controller is created, from
+    /// then on the wrapper is usable, e.g. `navigate` and `webview_with`
+    ///
+    /// The messages sent before are delivered before the event. Sent again
+    /// for each build, e.g. each `WebViewOptional::show` that builds the
+    /// window.
+    pub fn on_ready(mut self, event: EventLoopType) -> Self {
+        self.ready_event = Some(event);
+        self
+    }
+
+    /// Keep the window of `build_optional` alive when it's closed, and only
+    /// hide it
+    ///
+    /// The page keeps its state, e.g. the scroll, the forms and the scripts,
+    /// and the next `WebViewOptional::show` shows it again instead of
+    /// building it. `WebViewOptional::destroy` closes it for good.
+    pub fn optional_keep_alive(mut self) -> Self {
+        self.optional_keep_alive = true;
+        self
+    }
+
+    /// Build optional window
+    ///
+    /// This window does not exist until it's shown, after closing it, it needs
+    /// to be shown again.
+    pub fn build_optional(
+        &self,
+        _event_loop: &EventLoop<EventLoopType>,
+    ) -> WebViewOptional<EventLoopType, MsgToWebView, MsgFromWebView> {
+        WebViewOptional::new(self.clone())
+    }
+}
+
+pub struct WebViewWrapper<MsgToWebView>
+where
+    MsgToWebView: Serialize + 'static,
+{
+    msg_to_webview_type: PhantomData<MsgToWebView>,
+
+    // Controller persists the webview, while it's alive, the webview is shown
+    controller: Rc<RefCell<Option<webview2::Controller>>>,
+
+    // Messages and calls sent before the controller was created
+    pending: Rc<RefCell<VecDeque<serde_json::Value>>>,
+
+    // Calls waiting for the reply from the page
+    calls: Rc<rpc::Calls>,
+    middlewares: Rc<middleware::Chain>,
+    codec: Rc<dyn MessageCodec>,
+
+    // Sent messages until the page acknowledges them, `durable_outbox`
+    outbox: Option<Rc<outbox::Outbox>>,
+    focus_webview: bool,
+
+    // Page messages on their way to the event loop
+    inbound: Rc<inbound::Inbound>,
+
+    // Settings to restore when the navigation leaves the temporary origin
+    temp_settings: Rc<RefCell<Option<settings::TemporarySettings>>>,
+
+    // Closed explicitly with `close`
+    closed: Rc<Cell<bool>>,
+
+    // Url and title of the page, updated on their changes
+    source: Rc<RefCell<Option<String>>>,
+    document_title: Rc<RefCell<Option<String>>>,
+
+    // Can go back and forward, after the first navigation
+    history: Rc<Cell<Option<(bool, bool)>>>,
+    files: Rc<files::Grants>,
+    #[cfg(feature = "cdp")]
+    tracer: Rc<trace::Tracer>,
+    window_state_store: Rc<dyn WindowStateStore>,
+
+    // Saved on the window events, with the key
+    window_state_persistence: Option<(WindowStatePersistence, Rc<str>)>,
+
+    // Runs the timers, with `WebViewBuilder::scheduler`, until the last
+    // clone is dropped
+    scheduled: Option<Rc<scheduler::Registration>>,
+
+    // Builds the environment and the controller again after the browser
+    // process exits, with `WebViewBuilder::auto_recover`
+    recover: Option<Rc<dyn Fn()>>,
+
+    // Resize and move gestures, with `interaction_hints`
+    interaction: Option<Rc<interaction::Detector>>,
+    interaction_fn: Option<fn(Interaction)>,
+
+    // Pings of the page, with `WebViewBuilder::watchdog`
+    watchdog: Option<Rc<watchdog::Watchdog>>,
+
+    // Drag started by the page, with `WebViewBuilder::cross_window_drag`
+    drag_start: Option<Rc<RefCell<Option<serde_json::Value>>>>,
+    layout: Rc<layout::LayoutHistory>,
+    bounds: Rc<Cell<BoundsMode>>,
+
+    // Created by the builder, not by the caller
+    owns_window: bool,
+    policy: strict::Policy,
+    foreign_windows: Rc<strict::ForeignWindows>,
+    title: Rc<show::DeferredTitle>,
+    tag: Option<Rc<str>>,
+    window: Rc<Window>,
+}
+
+// Derive would require `MsgToWebView: Clone`, clones share the controller
+impl<MsgToWebView> Clone for WebViewWrapper<MsgToWebView>
+where
+    MsgToWebView: Serialize + 'static,
+{
+    fn clone(&self) -> Self {
+        WebViewWrapper {
+            msg_to_webview_type: PhantomData,
+            controller: self.controller.clone(),
+            pending: self.pending.clone(),
+            calls: self.calls.clone(),
+            middlewares: self.middlewares.clone(),
+            codec: self.codec.clone(),
+            outbox: self.outbox.clone(),
+            focus_webview: self.focus_webview,
+            inbound: self.inbound.clone(),
+            temp_settings: self.temp_settings.clone(),
+            closed: self.closed.clone(),
+            source: self.source.clone(),
+            document_title: self.document_title.clone(),
+            history: self.history.clone(),
+            files: self.files.clone(),
+            #[cfg(feature = "cdp")]
+            tracer: self.tracer.clone(),
+            window_state_store: self.window_state_store.clone(),
+            window_state_persistence: self.window_state_persistence.clone(),
+            scheduled: self.scheduled.clone(),
+            recover: self.recover.clone(),
+            interaction: self.interaction.clone(),
+            interaction_fn: self.interaction_fn,
+            watchdog: self.watchdog.clone(),
+            drag_start: self.drag_start.clone(),
+            layout: self.layout.clone(),
+            bounds: self.bounds.clone(),
+            owns_window: self.owns_window,
+            policy: self.policy,
+            foreign_windows: self.foreign_windows.clone(),
+            title: self.title.clone(),
+            tag: self.tag.clone(),
+            window: self.window.clone(),
+        }
+    }
+}
+
+/// Creation of the webview failed, the policy gets the error and the webview
+/// is closed
+fn fail_creation(
+    policy: strict::Policy,
+    context: &str,
+    err: &dyn Debug,
+    closed: &Cell<bool>,
+    pending: &Weak<RefCell<VecDeque<serde_json::Value>>>,
+    calls: &Weak<rpc::Calls>,
+) {
+    policy.fail(context, &err);
+    closed.set(true);
+    if let Some(pending_rc) = pending.upgrade() {
+        pending_rc.borrow_mut().clear();
+    }
+    if let Some(calls) = calls.upgrade() {
+        calls.cancel_all();
+    }
+}
+
+fn post_value(
+    webview: &webview2::WebView,
+    middlewares: &middleware::Chain,
+    codec: &dyn MessageCodec,
+    msg: serde_json::Value,
+) -> Result<(), Error> {
+    let msgstr = codec.encode(msg)?;
+    if let Some(msgstr) = middlewares.outbound(msgstr)? {
+        if codec.is_json() {
+            webview.post_web_message_as_json(&msgstr)?;
+        } else {
+            webview.post_web_message_as_string(&msgstr)?;
+        }
+    }
+    Ok(())
+}
+
+impl<MsgToWebView> WebViewWrapper<MsgToWebView>
+where
+    MsgToWebView: Debug + Serialize + 'static + Clone,
+{
+    /// Pass message to the WebView
+    ///
+    /// Messages sent before the controller is created are queued, and
+    /// delivered in order once it's ready. With
+    /// `WebViewBuilder::durable_outbox` they are delivered once the page has
+    /// loaded.
+    pub fn send_msg(&self, m: MsgToWebView) -> Result<(), Error> {
+        self.in_context("send_msg", || self.send_value(serde_json::to_value(&m)?))
+    }
+
+    fn send_value(&self, msg: serde_json::Value) -> Result<(), Error> {
+        if let Some(outbox) = &self.outbox {
+            return self.send_durable(outbox, msg);
+        }
+        let c = self.controller.borrow_mut();
+        if let Some(controller) = c.as_ref() {
+            let webview = controller.get_webview()?;
+            post_value(&webview, &self.middlewares, &*self.codec, msg)?;
+        } else if self.closed.get() {
+            return Err(Error::WebviewClosed);
+        } else {
+            self.pending.borrow_mut().push_back(msg);
+        }
+        Ok(())
+    }
+
+    fn send_durable(&self, outbox: &outbox::Outbox, msg: serde_json::Value) -> Result<(), Error> {
+        if self.closed.get() {
+            return Err(Error::WebviewClosed);
+        }
+        let seq = outbox.push(&msg)?;
+        if let Some(controller) = self.controller.borrow().as_ref() {
+            let webview = controller.get_webview()?;
+            let envelope = serde_json::to_value(&outbox::Envelope { seq, msg: &msg })?;
+            post_value(&webview, &self.middlewares, &*self.codec, envelope)?;
+        }
+        Ok(())
+    }
+
+    /// Pass message to the WebView and wait for the reply
+    ///
+    /// Message is posted as `{"id": 1, "call": msg}`, and the page must reply
+    /// by posting `{"id": 1, "reply": msg}` back as a JSON string. The reply
+    /// is deserialized to `R`, usually the `MsgFromWebView` type.
+    ///
+    /// Calls are sent like `send_msg`, queued until the controller is ready.
+    /// The call is cancelled with `Error::CallCancelled` if the page
+    /// navigates away or the webview closes before the reply.
+    pub fn call<R: DeserializeOwned>(
+        &self,
+        m: MsgToWebView,
+    ) -> impl Future<Output = Result<R, Error>> {
+        let (id, reply) = self.calls.register();
+        let result = serde_json::to_value(&rpc::CallEnvelope { id, call: &m })
+            .map_err(Error::from)
+            .and_then(|msg| self.send_value(msg));
+        if let Err(err) = result {
+            self.calls
+                .fail(id, err.with_context(self.error_context("call")));
+        }
+        reply
+    }
+
+    /// Is matching window?
+    pub fn is_window(&self, window_id: &WindowId) -> bool {
+        window_id == &self.window.id()
+    }
+
+    /// The window hosting the webview
+    pub fn window(&self) -> &Window {
+        &self.window
+    }
+
+    /// Drag started by the page since the last call, with
+    /// `WebViewBuilder::cross_window_drag`
+    pub(crate) fn take_drag(&self) -> Option<serde_json::Value> {
+        self.drag_start
+            .as_ref()
+            .and_then(|started| started.borrow_mut().take())
+    }
+
+    /// Bounds of the webview in the screen coordinates, in physical pixels
+    pub(crate) fn screen_rect(&self) -> Option<Rect> {
+        let origin = self.window.inner_position().ok()?;
+        let rect = self.bounds.get().rect(self.window.inner_size());
+        Some(Rect {
+            left: origin.x + rect.left,
+            top: origin.y + rect.top,
+            right: origin.x + rect.right,
+            bottom: origin.y + rect.bottom,
+        })
+    }
+
+    /// Post the drop of the `WebViewBuilder::cross_window_drag` to the page
+    pub(crate) fn post_drop(&self, payload: serde_json::Value) -> Result<(), Error> {
+        // Not borrowed while posting, the handlers may use the controller
+        let controller = self.controller.borrow().clone();
+        let controller = controller.ok_or(Error::ControllerNotCreated)?;
+        let value = serde_json::to_value(&dnd::Envelope {
+            message: dnd::Message::Drop(payload),
+        })?;
+        post_value(
+            &controller.get_webview()?,
+            &self.middlewares,
+            &JsonCodec,
+            value,
+        )
+    }
+
+    /// Raw handle of the window hosting the webview
+    pub fn hwnd(&self) -> HWND {
+        self.window.hwnd() as HWND
+    }
+
+    /// Id of the window hosting the webview
+    pub fn window_id(&self) -> WindowId {
+        self.window.id()
+    }
+
+    /// Close the webview and hide the window
+    ///
+    /// WebView2 processes keep running until the controller is closed, this
+    /// releases them without waiting for the wrapper to be dropped. The
+    /// window itself is destroyed when the wrapper is dropped.
+    pub fn close(&self) -> Result<(), Error> {
+        self.in_context("close", || {
+            self.closed.set(true);
+            self.pending.borrow_mut().clear();
+            self.calls.cancel_all();
+            if let Some(registration) = &self.scheduled {
+                registration.unregister();
+            }
+            let controller = self.controller.borrow_mut().take();
+            if self.owns_window {
+                self.window.set_visible(false);
+            }
+            if let Some(controller) = controller {
+                controller.close()?;
+                self.layout
+                    .record(LayoutTrigger::Closed, LayoutChange::Visible(false));
+            }
+            Ok(())
+        })
+    }
+
+    /// Latest bounds and visibility transitions, recorded only with
+    /// `WebViewBuilder::debug_layout`
+    pub fn layout_history(&self) -> Vec<LayoutTransition> {
+        self.layout.transitions()
+    }
+
+    /// HTML page of the `layout_history`, the artifacts highlighted
+    ///
+    /// ```ignore
+    /// diagnostics.navigate_to_string(&webview.layout_diagnostics_page())?;
+    /// ```
+    pub fn layout_diagnostics_page(&self) -> String {
+        layout::diagnostics_page(&self.layout.transitions())
+    }
+
+    /// Move the webview to the rectangle of the client area, in physical
+    /// pixels
+    ///
+    /// The bounds stay fixed when the window is resized, until changed again.
+    /// Can be called before the controller is created.
+    pub fn set_bounds(&self, rect: Rect) -> Result<(), Error> {
+        self.in_context("set_bounds", || {
+            self.bounds.set(BoundsMode::Fixed(rect));
+            if let Some(controller) = self.controller.borrow().as_ref() {
+                controller.put_bounds(rect.into())?;
+                self.layout
+                    .record(LayoutTrigger::SetBounds, LayoutChange::Bounds(rect));
+            }
+            Ok(())
+        })
+    }
+
+    /// Show the webview and the window, e.g. with `ShowWebview::Manual`
+    ///
+    /// Fails with `ControllerNotCreated` until the controller is ready.
+    pub fn show(&self) -> Result<(), Error> {
+        self.in_context("show", || {
+            let c = self.controller.borrow();
+            let controller = c.as_ref().ok_or(Error::ControllerNotCreated)?;
+            show::show(
+                controller,
+                &self.window,
+                self.owns_window,
+                self.focus_webview,
+                &self.layout,
+                &self.title,
+            )
+        })
+    }
+
+    /// Show or hide the webview without destroying it, the page keeps its
+    /// state
+    ///
+    /// The window is shown or hidden too, unless it's owned by the caller.
+    pub fn set_visible(&self, visible: bool) -> Result<(), Error> {
+        self.in_context("set_visible", || {
+            if let Some(controller) = self.controller.borrow().as_ref() {
+                controller.put_is_visible(visible)?;
+                self.layout
+                    .record(LayoutTrigger::SetVisible, LayoutChange::Visible(visible));
+            }
+            if let Some(watchdog) = &self.watchdog {
+                if visible {
+                    watchdog.resume(Instant::now());
+                } else {
+                    watchdog.pause();
+                }
+                self.schedule();
+            }
+            if self.owns_window {
+                if visible {
+                    self.title.apply(&self.window);
+                }
+                self.window.set_visible(visible);
+            }
+            Ok(())
+        })
+    }
+
+    /// Is the window visible, or the webview in a window owned by the
+    /// caller?
+    pub fn is_visible(&self) -> bool {
+        if self.owns_window {
+            unsafe { IsWindowVisible(self.hwnd()) != 0 }
+        } else {
+            let c = self.controller.borrow();
+            c.as_ref().map_or(false, |controller| {
+                controller.get_is_visible().unwrap_or(false)
+            })
+        }
+    }
+
+    /// Save the position and the size of the window for
+    /// `WebViewBuilder::restore_window_state`, e.g. on `Moved` and `Resized`
+    ///
+    /// Minimized windows are not saved, their position is off the screen.
+    pub fn save_window_state(&self, key: &str) -> Result<(), Error> {
+        self.in_context("save_window_state", || match self.window_state(key) {
+            Some(state) => self.window_state_store.save(key, &state),
+            None => Ok(()),
+        })
+    }
+
+    /// State of the window now, `None` if it's minimized
+    fn window_state(&self, key: &str) -> Option<WindowState> {
+        if unsafe { IsIconic(self.hwnd()) } != 0 {
+            return None;
+        }
+
+        // Maximized window keeps the placement it's restored to
+        let maximized = unsafe { IsZoomed(self.hwnd()) } != 0;
+        if maximized {
+            if let Some(state) = self.window_state_store.load(key) {
+                return Some(WindowState { maximized, ..state });
+            }
+        }
+        let position = self.window.outer_position().ok()?;
+        let size = self.window.inner_size();
+        Some(WindowState {
+            x: position.x,
+            y: position.y,
+            width: size.width,
+            height: size.height,
+            maximized,
+        })
+    }
+
+    /// Record the state for `WebViewBuilder::persist_window_state`, written
+    /// at once when the window is closing
+    fn persist_window_state(&self, changed: bool, closing: bool) -> Result<(), Error> {
+        let (persistence, key) = match &self.window_state_persistence {
+            Some(persistence) => persistence,
+            None => return Ok(()),
+        };
+        if changed || closing {
+            if let Some(state) = self.window_state(key) {
+                persistence.record(key, state, Instant::now());
+            }
+        }
+        if closing {
+            self.in_context("persist_window_state", || persistence.flush())?;
+        }
+        Ok(())
+    }
+
+    /// End the resize and move gestures that have gone quiet, see
+    /// `WebViewBuilder::interaction_hints`, write the window states of
+    /// `WebViewBuilder::persist_window_state` that have settled, end the
+    /// traces of `capture_trace` that have run their duration, and ping the
+    /// page for the `WebViewBuilder::watchdog`
+    ///
+    /// Returns when to poll again, e.g. for the `ControlFlow::WaitUntil`,
+    /// `None` when nothing is in progress. Not needed with
+    /// `WebViewBuilder::scheduler`.
+    pub fn poll(&self) -> Result<Option<Instant>, Error> {
+        self.in_context("poll", || {
+            let deadline = self.timers().poll(Instant::now())?;
+            self.schedule();
+            Ok(deadline)
+        })
+    }
+
+    fn timers(&self) -> timers::Timers {
+        timers::Timers {
+            interaction: self.interaction.clone(),
+            interaction_fn: self.interaction_fn,
+            controller: Rc::downgrade(&self.controller),
+            window_state_persistence: self.window_state_persistence.clone(),
+            watchdog: self.watchdog.clone(),
+            #[cfg(feature = "cdp")]
+            tracer: self.tracer.clone(),
+        }
+    }
+
+    /// Arm the scheduler for the deadline of the timers, after they may have
+    /// changed
+    fn schedule(&self) {
+        if let Some(registration) = &self.scheduled {
+            registration.arm(self.timers().deadline());
+        }
+    }
+
+    /// Record a performance trace of the page to the file, e.g. of the jank
+    /// reported by a user
+    ///
+    /// The trace ends at the first `poll` after the duration, or by the
+    /// `WebViewBuilder::scheduler`, and `on_done` gets the stats once the
+    /// file is written. Empty `categories` record the browser's default
+    /// categories. Fails with `Error::TraceBusy` if a trace of this webview
+    /// is in progress.
+    #[cfg(feature = "cdp")]
+    pub fn capture_trace(
+        &self,
+        duration: Duration,
+        path: impl AsRef<Path>,
+        categories: &[&str],
+        on_done: impl FnOnce(Result<TraceStats, Error>) + 'static,
+    ) -> Result<(), Error> {
+        self.in_context("capture_trace", || {
+            if self.tracer.is_busy() {
+                return Err(Error::TraceBusy);
+            }
+            let c = self.controller.borrow();
+            let controller = c.as_ref().ok_or(Error::ControllerNotCreated)?;
+            let webview = controller.get_webview()?;
+            self.tracer.add_receivers(&webview)?;
+            let params = serde_json::to_string(&trace::StartParams {
+                categories: categories.join(","),
+                transfer_mode: "ReportEvents",
+            })?;
+            self.tracer.begin(
+                path.as_ref().to_owned(),
+                Instant::now() + duration,
+                Box::new(on_done),
+                self.error_context("capture_trace"),
+            )?;
+            let started =
+                webview.call_dev_tools_protocol_method("Tracing.start", &params, |_| Ok(()));
+            if let Err(err) = started {
+                self.tracer.abort();
+                return Err(err.into());
+            }
+            self.schedule();
+            Ok(())
+        })
+    }
+
+    /// Resize the window, this is not taken as a resize gesture of the user
+    pub fn set_inner_size(&self, size: impl Into<Size>) {
+        let size = size.into().to_physical::<u32>(self.window.scale_factor());
+        if let Some(detector) = &self.interaction {
+            if size != self.window.inner_size() {
+                detector.expect_resize();
+            }
+        }
+        self.window.set_inner_size(size);
+    }
+
+    /// Move the window, this is not taken as a move gesture of the user
+    pub fn set_outer_position(&self, position: impl Into<Position>) {
+        let position = position
+            .into()
+            .to_physical::<i32>(self.window.scale_factor());
+        if let Some(detector) = &self.interaction {
+            if self.window.outer_position().ok() != Some(position) {
+                detector.expect_move();
+            }
+        }
+        self.window.set_outer_position(position);
+    }
+
+    /// Position of the IME composition window for the native input of the
+    /// host window
+    ///
+    /// When the webview has the focus its own child window takes the IME
+    /// input, and the browser places the composition window at the caret.
+    pub fn set_ime_position(&self, position: impl Into<Position>) {
+        self.window.set_ime_position(position);
+    }
+
+    /// Bring the window to the foreground
+    pub fn bring_to_front(&self) {
+        // How come winit does not have setting focus action? I noticed that
+        // winapi call SetFocus does not work always, but instead
+        // SetForegroundWindow did work.
+        unsafe { SetForegroundWindow(self.hwnd()) };
+    }
+
+    /// Move the keyboard focus into the webview
+    ///
+    /// Without this the key events go to the window, not to the page. To
+    /// focus an element of the page, call `focus()` of the element too.
+    pub fn focus(&self) -> Result<(), Error> {
+        self.in_context("focus", || {
+            let c = self.controller.borrow();
+            let controller = c.as_ref().ok_or(Error::ControllerNotCreated)?;
+            controller.move_focus(webview2::MoveFocusReason::Programmatic)?;
+            Ok(())
+        })
+    }
+
+    /// Open the DevTools window
+    ///
+    /// Fails with the webview2 error if the dev tools are disabled in the
+    /// settings. WebView2 has no way to close the window, the user closes
+    /// it.
+    pub fn open_dev_tools(&self) -> Result<(), Error> {
+        self.in_context("open_dev_tools", || {
+            let c = self.controller.borrow();
+            let controller = c.as_ref().ok_or(Error::ControllerNotCreated)?;
+            controller.get_webview()?.open_dev_tools_window()?;
+            Ok(())
+        })
+    }
+
+    /// Buffer the page messages instead of passing them to the event loop,
+    /// e.g. while a modal native dialog runs its own message loop
+    ///
+    /// The window events are not affected. Pauses nest, each needs its
+    /// `resume_inbound`. See `WebViewBuilder::inbound_buffer` for the size
+    /// of the buffer.
+    pub fn pause_inbound(&self) {
+        self.inbound.pause();
+    }
+
+    /// Undo a `pause_inbound`, the last one delivers the buffered messages in
+    /// order
+    pub fn resume_inbound(&self) {
+        self.inbound.resume();
+    }
+
+    /// Pause the page messages until the guard is dropped
+    ///
+    /// ```ignore
+    /// let file = {
+    ///     let _paused = webview.pause_inbound_guard();
+    ///     rfd::FileDialog::new().pick_file()
+    /// };
+    /// ```
+    pub fn pause_inbound_guard(&self) -> InboundPauseGuard {
+        InboundPauseGuard::new(self.inbound.clone())
+    }
+
+    /// Url of the page, `None` for `about:blank`
+    ///
+    /// While the controller is borrowed, e.g. in a `webview_with` closure,
+    /// the url of the last source change is returned.
+    pub fn current_url(&self) -> Result<Option<String>, Error> {
+        self.in_context("current_url", || {
+            let url = match self.controller.try_borrow() {
+                Ok(c) => {
+                    let controller = c.as_ref().ok_or(Error::ControllerNotCreated)?;
+                    controller.get_webview()?.get_source()?
+                }
+                Err(_) => self
+                    .source
+                    .borrow()
+                    .clone()
+                    .ok_or(Error::ControllerNotCreated)?,
+            };
+            Ok(if url == "about:blank" {
+                None
+            } else {
+                Some(url)
+            })
+        })
+    }
+
+    /// Is there a previous page in the history, as of the last history
+    /// change
+    pub fn can_go_back(&self) -> bool {
+        self.history.get().map_or(false, |(back, _)| back)
+    }
+
+    /// Is there a next page in the history, as of the last history change
+    pub fn can_go_forward(&self) -> bool {
+        self.history.get().map_or(false, |(_, forward)| forward)
+    }
+
+    /// Title of the page from the last title change, `None` before the
+    /// first change
+    pub fn document_title(&self) -> Option<String> {
+        self.document_title.borrow().clone()
+    }
+
+    /// Title of the page asked from the webview
+    pub fn fetch_document_title(&self) -> Result<String, Error> {
+        self.in_context("fetch_document_title", || {
+            let c = self.controller.borrow();
+            let controller = c.as_ref().ok_or(Error::ControllerNotCreated)?;
+            Ok(controller.get_webview()?.get_document_title()?)
+        })
+    }
+
+    /// Navigate to the url
+    ///
+    /// Fails with `ControllerNotCreated` before the controller is ready.
+    pub fn navigate(&self, url: &str) -> Result<(), Error> {
+        self.in_context("navigate", || {
+            let c = self.controller.borrow();
+            let controller = c.as_ref().ok_or(Error::ControllerNotCreated)?;
+            controller.get_webview()?.navigate(url)?;
+            Ok(())
+        })
+    }
+
+    /// Navigate to the HTML document given as a string
+    pub fn navigate_to_string(&self, html: &str) -> Result<(), Error> {
+        self.in_context("navigate_to_string", || {
+            let c = self.controller.borrow();
+            let controller = c.as_ref().ok_or(Error::ControllerNotCreated)?;
+            controller.get_webview()?.navigate_to_string(html)?;
+            Ok(())
+        })
+    }
+
+    /// Reload the current page
+    pub fn reload(&self) -> Result<(), Error> {
+        self.in_context("reload", || {
+            let c = self.controller.borrow();
+            let controller = c.as_ref().ok_or(Error::ControllerNotCreated)?;
+            controller.get_webview()?.reload()?;
+            Ok(())
+        })
+    }
+
+    /// Stop the navigation and the loading of the page
+    pub fn stop(&self) -> Result<(), Error> {
+        self.in_context("stop", || {
+            let c = self.controller.borrow();
+            let controller = c.as_ref().ok_or(Error::ControllerNotCreated)?;
+            controller.get_webview()?.stop()?;
+            Ok(())
+        })
+    }
+
+    /// Go back in the history, `Ok(false)` if there's no previous page
+    pub fn go_back(&self) -> Result<bool, Error> {
+        self.in_context("go_back", || {
+            let c = self.controller.borrow();
+            let controller = c.as_ref().ok_or(Error::ControllerNotCreated)?;
+            let webview = controller.get_webview()?;
+            if !webview.get_can_go_back()? {
+                return Ok(false);
+            }
+            webview.go_back()?;
+            Ok(true)
+        })
+    }
+
+    /// Go forward in the history, `Ok(false)` if there's no next page
+    pub fn go_forward(&self) -> Result<bool, Error> {
+        self.in_context("go_forward", || {
+            let c = self.controller.borrow();
+            let controller = c.as_ref().ok_or(Error::ControllerNotCreated)?;
+            let webview = controller.get_webview()?;
+            if !webview.get_can_go_forward()? {
+                return Ok(false);
+            }
+            webview.go_forward()?;
+            Ok(true)
+        })
+    }
+
+    /// Navigate with temporary settings, e.g. to an untrusted page
+    ///
+    /// The previous settings are restored when a later navigation leaves the
+    /// origin of the `url` (including the back button and the redirects), or
+    /// when `restore_settings` is called.
+    pub fn with_navigation_settings(&self, temp: WebViewSettings, url: &str) -> Result<(), Error> {
+        self.in_context("with_navigation_settings", || {
+            self.restore_settings()?;
+            let c = self.controller.borrow();
+            let controller = c.as_ref().ok_or(Error::ControllerNotCreated)?;
+            let webview = controller.get_webview()?;
+
+            // Applied before navigating, some settings take effect only on
+            // navigation
+            let previous = temp.apply(&webview.get_settings()?)?;
+            *self.temp_settings.borrow_mut() = Some(settings::TemporarySettings {
+                origin: settings::url_origin(url).to_owned(),
+                previous,
+            });
+            webview.navigate(url)?;
+            Ok(())
+        })
+    }
+
+    /// Restore the settings changed by `with_navigation_settings`
+    pub fn restore_settings(&self) -> Result<(), Error> {
+        self.in_context("restore_settings", || {
+            let temp_settings = self.temp_settings.borrow_mut().take();
+            if let Some(temp_settings) = temp_settings {
+                let c = self.controller.borrow();
+                let controller = c.as_ref().ok_or(Error::ControllerNotCreated)?;
+                temp_settings
+                    .previous
+                    .apply(&controller.get_webview()?.get_settings()?)?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Change the frame rate limit set with `WebViewBuilder::max_fps`, `None`
+    /// restores the full rate
+    ///
+    /// Applies to the current document, new documents start with the limit
+    /// given to the builder. Has no effect without the builder limit.
+    pub fn set_max_fps(&self, max_fps: Option<u32>) -> Result<(), Error> {
+        self.in_context("set_max_fps", || {
+            let c = self.controller.borrow();
+            let controller = c.as_ref().ok_or(Error::ControllerNotCreated)?;
+            controller
+                .get_webview()?
+                .execute_script(&throttle::set_script(max_fps), |_| Ok(()))?;
+            Ok(())
+        })
+    }
+
+    /// Call the webview instance
+    pub fn webview_with(&self, mut cb: impl FnMut(&webview2::WebView)) -> Result<(), Error> {
+        self.in_context("webview_with", || {
+            let c = self.controller.borrow_mut();
+            if let Some(controller) = c.as_ref() {
+                let mut webview = controller.get_webview()?;
+                cb(&mut webview);
+                Ok(())
+            } else {
+                Err(Error::ControllerNotCreated)
+            }
+        })
+    }
+
+    /// Flush the session data (cookies, local storage) towards the disk
+    ///
+    /// WebView2 has no API to force the browser process to write the profile
+    /// to the disk, Chromium commits cookies and DOM storage lazily and on
+    /// shutdown. This is the best effort: it does a round-trip through the
+    /// renderer so that the storage writes the page has made are handed over
+    /// to the browser process before `cb` is called. Only closing the
+    /// controller guarantees that everything is on the disk.
+    pub fn flush_session(&self, cb: impl FnOnce(Result<(), Error>) + 'static) {
+        self.script_result(
+            "flush_session",
+            "void 0",
+            move |r: Result<serde_json::Value, Error>| cb(r.map(|_| ())),
+        );
+    }
+
+    /// Bounding rectangle of the selected text in the page, `None` when
+    /// nothing is selected
+    ///
+    /// The rectangle is in the logical pixels relative to the client area of
+    /// the window, e.g. for placing a native tooltip next to the selection.
+    pub fn selection_rect(&self, cb: impl FnOnce(Result<Option<LogicalRect>, Error>) + 'static) {
+        let geometry = self.in_context("selection_rect", || -> Result<(Rect, f64), Error> {
+            let c = self.controller.borrow();
+            let controller = c.as_ref().ok_or(Error::ControllerNotCreated)?;
+            Ok((
+                controller.get_bounds()?.into(),
+                controller.get_zoom_factor()?,
+            ))
+        });
+        let (bounds, zoom) = match geometry {
+            Ok(geometry) => geometry,
+            Err(err) => return cb(Err(err)),
+        };
+
+        // CSS pixels are logical pixels scaled by the zoom, the controller
+        // bounds are physical pixels
+        let scale = self.window.scale_factor();
+        let script = r#"(() => {
+            const s = window.getSelection();
+            if (!s || s.rangeCount === 0 || s.isCollapsed) return null;
+            const r = s.getRangeAt(0).getBoundingClientRect();
+            return [r.left, r.top, r.width, r.height];
+        })()"#;
+        self.script_result(
+            "selection_rect",
+            script,
+            move |r: Result<Option<[f64; 4]>, Error>| {
+                cb(r.map(|rect| {
+                    rect.map(|[x, y, width, height]| LogicalRect {
+                        x: bounds.left as f64 / scale + x * zoom,
+                        y: bounds.top as f64 / scale + y * zoom,
+                        width: width * zoom,
+                        height: height * zoom,
+                    })
+                }))
+            },
+        );
+    }
+
+    /// Scrollable size of the page in the CSS pixels, as
+    /// `(scrollWidth, scrollHeight)` of the document element
+    ///
+    /// The size is measured at the moment the script runs, a page that is
+    /// still loading or laying out may grow later. An empty document
+    /// measures `(0.0, 0.0)`.
+    pub fn content_size(&self, cb: impl FnOnce(Result<(f64, f64), Error>) + 'static) {
+        let script = r#"(() => {
+            const e = document.documentElement;
+            return e ? [e.scrollWidth, e.scrollHeight] : [0, 0];
+        })()"#;
+        self.script_result("content_size", script, move |r: Result<[f64; 2], Error>| {
+            cb(r.map(|[width, height]| (width, height)))
+        });
+    }
+
+    /// Serve the file to the page, e.g. for an `<img>` or a `<video>` of a
+    /// file the user picked
+    ///
+    /// The page gets only an unguessable url, the file is served with the
+    /// range requests so that the videos can seek. The grant is
+    /// revoked when the `GrantedUrl` is dropped or the webview navigates to
+    /// another page, unless it's made `persistent`.
+    pub fn grant_file(&self, path: impl AsRef<Path>) -> GrantedUrl {
+        self.files.grant(path.as_ref())
+    }
+
+    /// Print the page to a PDF file, `cb` is called when the file is written
+    ///
+    /// The printing is asynchronous, it can't be waited for on the thread of
+    /// the event loop. The page is printed as for the `print` media.
+    pub fn print_to_pdf(
+        &self,
+        path: impl AsRef<Path>,
+        settings: &PdfSettings,
+        cb: impl FnOnce(Result<(), Error>) + 'static,
+    ) {
+        self.dev_tools_file(
+            "print_to_pdf",
+            "Page.printToPDF",
+            serde_json::to_string(settings),
+            path.as_ref().to_owned(),
+            cb,
+        );
+    }
+
+    /// Capture the visible part of the page to an image file, `cb` is called
+    /// when the file is written
+    pub fn capture_preview(
+        &self,
+        path: impl AsRef<Path>,
+        format: ImageFormat,
+        cb: impl FnOnce(Result<(), Error>) + 'static,
+    ) {
+        let context = self.error_context("capture_preview");
+        let path = path.as_ref().to_owned();
+        let cb = Rc::new(RefCell::new(Some(cb)));
+        let cb_ = cb.clone();
+        let context_ = context.clone();
+        let result = (|| -> Result<(), Error> {
+            let c = self.controller.borrow();
+            let controller = c.as_ref().ok_or(Error::ControllerNotCreated)?;
+            let stream = webview2::Stream::from_bytes(&[]);
+            let stream_ = stream.clone();
+            controller
+                .get_webview()?
+                .capture_preview(format.into(), stream, move |captured| {
+                    let write = || -> Result<(), Error> {
+                        captured?;
+                        capture::write(stream_, &path)
+                    };
+                    if let Some(cb) = cb_.borrow_mut().take() {
+                        cb(write().map_err(|err| err.with_context(context_)));
+                    }
+                    Ok(())
+                })?;
+            Ok(())
+        })();
+        if let Err(err) = result {
+            if let Some(cb) = cb.borrow_mut().take() {
+                cb(Err(err.with_context(context)));
+            }
+        }
+    }
+
+    /// Call the devtools method giving a base64 file, and write the file
+    fn dev_tools_file(
+        &self,
+        operation: &'static str,
+        method: &str,
+        params: Result<String, serde_json::Error>,
+        path: PathBuf,
+        cb: impl FnOnce(Result<(), Error>) + 'static,
+    ) {
+        let context = self.error_context(operation);
+        let cb = Rc::new(RefCell::new(Some(cb)));
+        let cb_ = cb.clone();
+        let context_ = context.clone();
+        let result = (|| -> Result<(), Error> {
+            let c = self.controller.borrow();
+            let controller = c.as_ref().ok_or(Error::ControllerNotCreated)?;
+            controller.get_webview()?.call_dev_tools_protocol_method(
+                method,
+                &params?,
+                move |json| {
+                    let write = || -> Result<(), Error> {
+                        let reply: pdf::DataReply = serde_json::from_str(&json)?;
+                        let bytes = pdf::decode_base64(&reply.data).ok_or(Error::PrintFailed)?;
+                        std::fs::write(&path, bytes)?;
+                        Ok(())
+                    };
+                    if let Some(cb) = cb_.borrow_mut().take() {
+                        cb(write().map_err(|err| err.with_context(context_)));
+                    }
+                    Ok(())
+                },
+            )?;
+            Ok(())
+        })();
+        if let Err(err) = result {
+            if let Some(cb) = cb.borrow_mut().take() {
+                cb(Err(err.with_context(context)));
+            }
+        }
+    }
+
+    /// Run the script and pass its JSON result to `cb`, also the errors
+    /// before the script runs are passed to `cb`
+    fn script_result<T: DeserializeOwned + 'static>(
+        &self,
+        operation: &'static str,
+        script: &str,
+        cb: impl FnOnce(Result<T, Error>) + 'static,
+    ) {
+        let context = self.error_context(operation);
+        let cb = Rc::new(RefCell::new(Some(cb)));
+        let cb_ = cb.clone();
+        let context_ = context.clone();
+        let result = (|| -> Result<(), Error> {
+            let c = self.controller.borrow();
+            let controller = c.as_ref().ok_or(Error::ControllerNotCreated)?;
+            controller
+                .get_webview()?
+                .execute_script(script, move |json| {
+                    if let Some(cb) = cb_.borrow_mut().take() {
+                        cb(serde_json::from_str(&json)
+                            .map_err(|err| Error::from(err).with_context(context_)));
+                    }
+                    Ok(())
+                })?;
+            Ok(())
+        })();
+        if let Err(err) = result {
+            if let Some(cb) = cb.borrow_mut().take() {
+                cb(Err(err.with_context(context)));
+            }
+        }
+    }
+
+    fn error_context(&self, operation: &'static str) -> ErrorContext {
+        ErrorContext {
+            tag: self.tag.as_deref().map(str::to_owned),
+            window_id: self.window.id(),
+            operation,
+        }
+    }
+
+    /// Run `f`, its error gets the context of the operation
+    fn in_context<T>(
+        &self,
+        operation: &'static str,
+        f: impl FnOnce() -> Result<T, Error>,
+    ) -> Result<T, Error> {
+        f().map_err(|err| err.with_context(self.error_context(operation)))
+    }
+
+    /// Handle Window Event
+    ///
+    /// Runs the side effects to keep the webview2 control happy, you must plug
+    /// this in to the main event loop.
+    pub fn handle_window_event(&self, t: &WindowEvent, window_id: &WindowId) -> Result<(), Error> {
         if !self.is_window(window_id) {
+            self.foreign_event(window_id);
             return Ok(());
         }
-        let controller_maybe = self.controller.borrow_mut();
-        let controller = controller_maybe
-            .as_ref()
-            .ok_or(Error::ControllerNotCreated)?;
+        let operation = match t {
+            WindowEvent::Moved(_) => "notify_parent_window_position_changed on Moved",
+            WindowEvent::Focused(_) => "move_focus on Focused",
+            WindowEvent::Resized(_) => "put_bounds on Resized",
+            WindowEvent::ScaleFactorChanged { .. } => "put_bounds on ScaleFactorChanged",
+            WindowEvent::ThemeChanged(_) => "emulate_theme on ThemeChanged",
+            _ => "handle_window_event",
+        };
+        let persisted = self.persist_window_state(
+            matches!(t, WindowEvent::Moved(_) | WindowEvent::Resized(_)),
+            matches!(t, WindowEvent::CloseRequested | WindowEvent::Destroyed),
+        );
+        let handled = self.in_context(operation, || {
+            let controller_maybe = self.controller.borrow_mut();
+            let controller = controller_maybe
+                .as_ref()
+                .ok_or(Error::ControllerNotCreated)?;
+
+            match t {
+                WindowEvent::Moved(_) => {
+                    controller.notify_parent_window_position_changed()?;
+                    self.timers()
+                        .interaction(controller, |d| d.moved(Instant::now()))?;
+                }
+
+                WindowEvent::Focused(true) if self.focus_webview => {
+                    controller.move_focus(webview2::MoveFocusReason::Programmatic)?;
+                }
+
+                WindowEvent::Resized(new_size) => {
+                    self.resize(controller, LayoutTrigger::Resized, *new_size)?;
+                    self.timers()
+                        .interaction(controller, |d| d.resized(Instant::now()))?;
+                }
 
-        match t {
-            WindowEvent::Moved(_) => {
-                controller.notify_parent_window_position_changed()?;
+                // Moved to a monitor with a different DPI
+                WindowEvent::ScaleFactorChanged { new_inner_size, .. } => {
+                    self.resize(
+                        controller,
+                        LayoutTrigger::ScaleFactorChanged,
+                        **new_inner_size,
+                    )?;
+                }
+
+                WindowEvent::ThemeChanged(theme) => {
+                    emulate_theme(controller, *theme)?;
+                }
+                _ => (),
+            };
+            Ok(())
+        });
+        self.schedule();
+        handled.and(persisted)
+    }
+
+    /// Handle the window events of a frame at once
+    ///
+    /// Same as calling `handle_window_event` for each event, but the
+    /// controller is updated once: only the last size counts, and the moves
+    /// are notified once. Events of the other windows are skipped.
+    ///
+    /// The outcome is given also when updating the webview fails, e.g. with
+    /// `Error::ControllerNotCreated` before it's ready, so that a close
+    /// request isn't lost. That's why the error is returned next to the
+    /// outcome instead of in it, the outcome stays a plain `Copy` value that
+    /// can be compared.
+    pub fn handle_window_events<'a>(
+        &self,
+        events: impl IntoIterator<Item = (&'a WindowEvent<'a>, &'a WindowId)>,
+    ) -> (EventBatchOutcome, Result<(), Error>) {
+        let mut batch = batch::Batch::default();
+        let mut interaction = None;
+        let now = Instant::now();
+        for (event, window_id) in events {
+            if !self.is_window(window_id) {
+                self.foreign_event(window_id);
+                continue;
+            }
+            batch.push(event);
+            if let Some(d) = &self.interaction {
+                match event {
+                    WindowEvent::Moved(_) => interaction = d.moved(now).or(interaction),
+                    WindowEvent::Resized(_) => interaction = d.resized(now).or(interaction),
+                    _ => (),
+                }
             }
+        }
+        let outcome = batch.outcome;
+        if outcome.events == 0 {
+            return (outcome, Ok(()));
+        }
+        let persisted =
+            self.persist_window_state(batch.moved || batch.size.is_some(), outcome.close_requested);
 
-            WindowEvent::Resized(new_size) => {
-                let r = RECT {
-                    left: 0,
-                    top: 0,
-                    right: new_size.width as i32,
-                    bottom: new_size.height as i32,
-                };
-                controller.put_bounds(r)?;
+        let handled = self.in_context("handle_window_events", || {
+            let controller_maybe = self.controller.borrow();
+            let controller = controller_maybe
+                .as_ref()
+                .ok_or(Error::ControllerNotCreated)?;
+            batch.dispatch(
+                &mut ControllerDispatch {
+                    wrapper: self,
+                    controller,
+                },
+                self.focus_webview,
+            )?;
+            if interaction.is_some() {
+                self.timers().interaction(controller, |_| interaction)?;
             }
-            _ => (),
-        };
+            Ok(())
+        });
+        self.schedule();
+        (outcome, persisted.and(handled))
+    }
+
+    fn foreign_event(&self, window_id: &WindowId) {
+        self.foreign_windows
+            .event(self.policy, self.window.id(), *window_id);
+    }
+
+    /// Bounds for the new size of the client area
+    fn resize(
+        &self,
+        controller: &webview2::Controller,
+        trigger: LayoutTrigger,
+        size: PhysicalSize<u32>,
+    ) -> Result<(), Error> {
+        let r = self.bounds.get().rect(size);
+        controller.put_bounds(r.into())?;
+        self.layout.record(trigger, LayoutChange::Bounds(r));
+        Ok(())
+    }
+}
+
+/// Controller calls of `handle_window_events`
+struct ControllerDispatch<'a, MsgToWebView> {
+    wrapper: &'a WebViewWrapper<MsgToWebView>,
+    controller: &'a webview2::Controller,
+}
+
+impl<'a, MsgToWebView> batch::Dispatch for ControllerDispatch<'a, MsgToWebView>
+where
+    MsgToWebView: Debug + Serialize + 'static + Clone,
+{
+    fn moved(&mut self) -> Result<(), Error> {
+        self.controller.notify_parent_window_position_changed()?;
+        Ok(())
+    }
+
+    fn resize(&mut self, trigger: LayoutTrigger, size: PhysicalSize<u32>) -> Result<(), Error> {
+        self.wrapper.resize(self.controller, trigger, size)
+    }
+
+    fn theme(&mut self, theme: Theme) -> Result<(), Error> {
+        emulate_theme(self.controller, theme)
+    }
+
+    fn focus(&mut self) -> Result<(), Error> {
+        self.controller
+            .move_focus(webview2::MoveFocusReason::Programmatic)?;
         Ok(())
     }
 }
 
+// This version of WebView2 has no preferred color scheme setting, the
+// devtools protocol emulates the media feature instead
+fn emulate_theme(controller: &webview2::Controller, theme: Theme) -> Result<(), Error> {
+    let scheme = match theme {
+        Theme::Light => "light",
+        Theme::Dark => "dark",
+    };
+    controller.get_webview()?.call_dev_tools_protocol_method(
+        "Emulation.setEmulatedMedia",
+        &format!(
+            r#"{{"features":[{{"name":"prefers-color-scheme","value":"{}"}}]}}"#,
+            scheme
+        ),
+        |_| Ok(()),
+    )?;
+    Ok(())
+}
+
 pub struct WebViewOptional<EventLoopType, MsgToWebView, MsgFromWebView>
 where
     EventLoopType: 'static + Clone,
@@ -395,6 +3683,12 @@ where
     // builder: Box<Fn() -> WebViewBuilder<EventLoopType, MsgToWebView, MsgFromWebView> + 'static>,
     builder: WebViewBuilder<EventLoopType, MsgToWebView, MsgFromWebView>,
     instance: Rc<RefCell<Option<WebViewWrapper<MsgToWebView>>>>,
+
+    // Messages of `try_send_msg` while the window is not open
+    queued: RefCell<VecDeque<MsgToWebView>>,
+
+    // Instance is hidden, with `WebViewBuilder::optional_keep_alive`
+    hidden: Cell<bool>,
 }
 
 impl<EventLoopType, MsgToWebView, MsgFromWebView>
@@ -410,6 +3704,8 @@ where
         WebViewOptional {
             builder,
             instance: Rc::new(RefCell::new(None)),
+            queued: RefCell::new(VecDeque::new()),
+            hidden: Cell::new(false),
         }
     }
     /// Pass message to the WebView
@@ -422,6 +3718,41 @@ where
         }
     }
 
+    /// Pass message to the WebView, or queue it for the next `show` if the
+    /// window is not open
+    ///
+    /// The queued messages are sent in order when the window is built, before
+    /// the messages sent after it. A message that fails to send stays queued
+    /// with the ones after it, they are tried again on the next `show` or
+    /// `try_send_msg`.
+    pub fn try_send_msg(&self, m: MsgToWebView) -> Result<(), Error> {
+        let value = self.instance.borrow();
+        if let Some(value) = value.as_ref() {
+            if let Err(err) = self.send_queued(value) {
+                self.queued.borrow_mut().push_back(m);
+                return Err(err);
+            }
+            value.send_msg(m)
+        } else {
+            self.queued.borrow_mut().push_back(m);
+            Ok(())
+        }
+    }
+
+    // One at a time, the failed message and the rest stay queued
+    fn send_queued(&self, instance: &WebViewWrapper<MsgToWebView>) -> Result<(), Error> {
+        loop {
+            let m = match self.queued.borrow_mut().pop_front() {
+                Some(m) => m,
+                None => return Ok(()),
+            };
+            if let Err(err) = instance.send_msg(m.clone()) {
+                self.queued.borrow_mut().push_front(m);
+                return Err(err);
+            }
+        }
+    }
+
     /// Is matching window?
     pub fn is_window(&self, window_id: &WindowId) -> bool {
         let value = self.instance.borrow();
@@ -442,24 +3773,199 @@ where
         }
     }
 
+    /// Is the window open?
+    pub fn is_open(&self) -> bool {
+        self.instance.borrow().is_some() && !self.hidden.get()
+    }
+
+    /// Close the window, it's created again by the next `show`
+    ///
+    /// With `WebViewBuilder::optional_keep_alive` the window is only hidden.
+    pub fn hide(&mut self) {
+        if !self.builder.optional_keep_alive {
+            *self.instance.borrow_mut() = None;
+            return;
+        }
+        if let Some(instance) = self.instance.borrow().as_ref() {
+            if let Err(err) = self.hide_instance(instance) {
+                strict::Policy::new(self.builder.strict).fail("Hiding the webview failed", &err);
+            }
+        }
+    }
+
+    /// Close the window and the webview, also the kept alive ones
+    pub fn destroy(&mut self) -> Result<(), Error> {
+        self.hidden.set(false);
+        match self.instance.borrow_mut().take() {
+            Some(instance) => instance.close(),
+            None => Ok(()),
+        }
+    }
+
+    fn hide_instance(&self, instance: &WebViewWrapper<MsgToWebView>) -> Result<(), Error> {
+        self.hidden.set(true);
+        instance.set_visible(false)
+    }
+
+    /// Open the DevTools window
+    pub fn open_dev_tools(&self) -> Result<(), Error> {
+        let value = self.instance.borrow();
+        if let Some(value) = value.as_ref() {
+            value.open_dev_tools()
+        } else {
+            Err(Error::WebviewNotShown)
+        }
+    }
+
+    /// Title of the page, `None` if not open, see
+    /// `WebViewWrapper::document_title`
+    pub fn document_title(&self) -> Option<String> {
+        let value = self.instance.borrow();
+        value.as_ref().and_then(|value| value.document_title())
+    }
+
+    /// Title of the page asked from the webview
+    pub fn fetch_document_title(&self) -> Result<String, Error> {
+        let value = self.instance.borrow();
+        if let Some(value) = value.as_ref() {
+            value.fetch_document_title()
+        } else {
+            Err(Error::WebviewNotShown)
+        }
+    }
+
+    /// Print the page to a PDF file, `cb` gets `WebviewNotShown` if not open
+    pub fn print_to_pdf(
+        &self,
+        path: impl AsRef<Path>,
+        settings: &PdfSettings,
+        cb: impl FnOnce(Result<(), Error>) + 'static,
+    ) {
+        match self.instance.borrow().as_ref() {
+            Some(value) => value.print_to_pdf(path, settings, cb),
+            None => cb(Err(Error::WebviewNotShown)),
+        }
+    }
+
+    /// Capture the page to an image file, `cb` gets `WebviewNotShown` if not
+    /// open
+    pub fn capture_preview(
+        &self,
+        path: impl AsRef<Path>,
+        format: ImageFormat,
+        cb: impl FnOnce(Result<(), Error>) + 'static,
+    ) {
+        match self.instance.borrow().as_ref() {
+            Some(value) => value.capture_preview(path, format, cb),
+            None => cb(Err(Error::WebviewNotShown)),
+        }
+    }
+
+    /// Record a performance trace, see `WebViewWrapper::capture_trace`
+    #[cfg(feature = "cdp")]
+    pub fn capture_trace(
+        &self,
+        duration: Duration,
+        path: impl AsRef<Path>,
+        categories: &[&str],
+        on_done: impl FnOnce(Result<TraceStats, Error>) + 'static,
+    ) -> Result<(), Error> {
+        match self.instance.borrow().as_ref() {
+            Some(value) => value.capture_trace(duration, path, categories, on_done),
+            None => Err(Error::WebviewNotShown),
+        }
+    }
+
+    /// Navigate to the url, see `WebViewWrapper::navigate`
+    pub fn navigate(&self, url: &str) -> Result<(), Error> {
+        let value = self.instance.borrow();
+        if let Some(value) = value.as_ref() {
+            value.navigate(url)
+        } else {
+            Err(Error::WebviewNotShown)
+        }
+    }
+
+    /// Navigate to the HTML document given as a string
+    pub fn navigate_to_string(&self, html: &str) -> Result<(), Error> {
+        let value = self.instance.borrow();
+        if let Some(value) = value.as_ref() {
+            value.navigate_to_string(html)
+        } else {
+            Err(Error::WebviewNotShown)
+        }
+    }
+
+    /// Reload the current page
+    pub fn reload(&self) -> Result<(), Error> {
+        let value = self.instance.borrow();
+        if let Some(value) = value.as_ref() {
+            value.reload()
+        } else {
+            Err(Error::WebviewNotShown)
+        }
+    }
+
+    /// Stop the navigation and the loading of the page
+    pub fn stop(&self) -> Result<(), Error> {
+        let value = self.instance.borrow();
+        if let Some(value) = value.as_ref() {
+            value.stop()
+        } else {
+            Err(Error::WebviewNotShown)
+        }
+    }
+
+    /// Go back in the history, see `WebViewWrapper::go_back`
+    pub fn go_back(&self) -> Result<bool, Error> {
+        let value = self.instance.borrow();
+        if let Some(value) = value.as_ref() {
+            value.go_back()
+        } else {
+            Err(Error::WebviewNotShown)
+        }
+    }
+
+    /// Go forward in the history, see `WebViewWrapper::go_forward`
+    pub fn go_forward(&self) -> Result<bool, Error> {
+        let value = self.instance.borrow();
+        if let Some(value) = value.as_ref() {
+            value.go_forward()
+        } else {
+            Err(Error::WebviewNotShown)
+        }
+    }
+
+    /// Build the window, or bring it to the front if it's already shown
     pub fn show(
         &mut self,
         event_loop: &EventLoopWindowTarget<EventLoopType>,
         proxy: &EventLoopProxy<EventLoopType>,
-    ) {
+    ) -> Result<(), Error> {
         let mut value = self.instance.borrow_mut();
         match value.as_ref() {
             Some(instance) => {
-                // How come winit does not have setting focus action? I noticed
-                // that winapi call SetFocus does not work always, but instead
-                // SetForegroundWindow did work.
-                unsafe { SetForegroundWindow(instance.window.hwnd() as HWND) };
+                if self.hidden.replace(false) {
+                    instance.set_visible(true)?;
+                }
+                instance.bring_to_front();
+                if instance.focus_webview {
+                    if let Err(err) = instance.focus() {
+                        strict::Policy::new(self.builder.strict)
+                            .fail("Focusing the webview failed", &err);
+                    }
+                }
+                self.send_queued(instance)?;
             }
             None => {
                 let builder = self.builder.clone();
-                *value = Some(builder.build_with_proxy(event_loop, proxy).unwrap());
+                let instance = value.get_or_insert(builder.build_with_proxy(event_loop, proxy)?);
+
+                // Waits in the wrapper until the controller is created
+                self.send_queued(instance)?;
             }
         }
+        Ok(())
     }
 
     pub fn handle_window_event(
@@ -471,8 +3977,13 @@ where
         if let Some(instance) = value.as_ref() {
             if instance.is_window(window_id) {
                 if let WindowEvent::CloseRequested = event {
+                    // Saves the window state before the window is gone
+                    let handled = instance.handle_window_event(&event, window_id);
+                    if self.builder.optional_keep_alive {
+                        return handled.and(self.hide_instance(instance));
+                    }
                     *value = None;
-                    return Ok(());
+                    return handled;
                 }
             }
             instance.handle_window_event(&event, window_id)
@@ -480,4 +3991,70 @@ where
             Err(Error::WebviewNotShown)
         }
     }
+
+    /// Handle the window events of a frame at once, see
+    /// `WebViewWrapper::handle_window_events`
+    pub fn handle_window_events<'a>(
+        &mut self,
+        events: impl IntoIterator<Item = (&'a WindowEvent<'a>, &'a WindowId)>,
+    ) -> (EventBatchOutcome, Result<(), Error>) {
+        let mut value = self.instance.borrow_mut();
+        if let Some(instance) = value.as_ref() {
+            let (outcome, handled) = instance.handle_window_events(events);
+            if outcome.close_requested {
+                if self.builder.optional_keep_alive {
+                    return (outcome, handled.and(self.hide_instance(instance)));
+                }
+                *value = None;
+            }
+            (outcome, handled)
+        } else {
+            (EventBatchOutcome::default(), Err(Error::WebviewNotShown))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn channel() -> (Rc<RefCell<VecDeque<serde_json::Value>>>, Rc<rpc::Calls>) {
+        let pending = Rc::new(RefCell::new(VecDeque::new()));
+        pending.borrow_mut().push_back(serde_json::json!("queued"));
+        (pending, Rc::new(rpc::Calls::new(Rc::new(JsonCodec))))
+    }
+
+    #[test]
+    fn strict_creation_failure_panics() {
+        let (pending, calls) = channel();
+        let closed = Cell::new(false);
+        let message = strict::panic_message(|| {
+            fail_creation(
+                strict::Policy::new(true),
+                "Controller was not created",
+                &"error",
+                &closed,
+                &Rc::downgrade(&pending),
+                &Rc::downgrade(&calls),
+            );
+        })
+        .unwrap();
+        assert!(message.starts_with("Controller was not created"));
+    }
+
+    #[test]
+    fn lenient_creation_failure_closes_the_webview() {
+        let (pending, calls) = channel();
+        let closed = Cell::new(false);
+        fail_creation(
+            strict::Policy::new(false),
+            "Controller was not created",
+            &"error",
+            &closed,
+            &Rc::downgrade(&pending),
+            &Rc::downgrade(&calls),
+        );
+        assert!(closed.get());
+        assert!(pending.borrow().is_empty());
+    }
 }
@@ -0,0 +1,32 @@
+//! Navigation lifecycle for the loading indicators
+
+/// Stage of a navigation, the id tells the overlapping navigations apart
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum LoadingEvent {
+    Started {
+        navigation_id: u64,
+    },
+
+    /// The new document has started loading
+    ContentLoading {
+        navigation_id: u64,
+    },
+
+    /// Also the failed and the cancelled navigations complete
+    Completed {
+        navigation_id: u64,
+        is_success: bool,
+    },
+}
+
+/// Navigation that didn't complete, e.g. the host was not found
+///
+/// The webview shows the browser's error page for it.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct NavigationFailed {
+    pub navigation_id: u64,
+
+    /// Url that was navigated to
+    pub url: String,
+    pub status: webview2::WebErrorStatus,
+}
@@ -0,0 +1,179 @@
+//! Webviews of the app by name
+
+use crate::dnd::{self, CrossWindowDrop};
+use crate::{Error, Rect, WebViewWrapper};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use winit::event::WindowEvent;
+use winit::window::WindowId;
+
+/// Named webviews, the window events are passed to the webviews of the
+/// window
+///
+/// ```ignore
+/// let mut webviews = WebViewManager::new();
+/// webviews.add("main", main);
+/// webviews.add("sidebar", sidebar);
+///
+/// event_loop.run(move |event, _, _| match event {
+///     Event::WindowEvent { event, window_id } => {
+///         let _ = webviews.handle_window_event(&event, &window_id);
+///     }
+///     Event::UserEvent(AppEvent::Select(item)) => {
+///         let _ = webviews.send_to("sidebar", MsgToWebView::Show(item));
+///     }
+///     _ => (),
+/// });
+/// ```
+pub struct WebViewManager<MsgToWebView>
+where
+    MsgToWebView: Serialize + 'static,
+{
+    webviews: HashMap<String, WebViewWrapper<MsgToWebView>>,
+
+    // Drag of `WebViewBuilder::cross_window_drag` in progress
+    drag: Option<dnd::Drag>,
+}
+
+impl<MsgToWebView> WebViewManager<MsgToWebView>
+where
+    MsgToWebView: Debug + Serialize + 'static + Clone,
+{
+    pub fn new() -> Self {
+        WebViewManager {
+            webviews: HashMap::new(),
+            drag: None,
+        }
+    }
+
+    /// Add the webview, the previous webview of the name is returned
+    pub fn add(
+        &mut self,
+        name: &str,
+        webview: WebViewWrapper<MsgToWebView>,
+    ) -> Option<WebViewWrapper<MsgToWebView>> {
+        self.webviews.insert(name.to_owned(), webview)
+    }
+
+    /// Remove the webview, it's not closed, e.g. `close` it to destroy it
+    /// before it's dropped
+    pub fn remove(&mut self, name: &str) -> Option<WebViewWrapper<MsgToWebView>> {
+        self.webviews.remove(name)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&WebViewWrapper<MsgToWebView>> {
+        self.webviews.get(name)
+    }
+
+    /// Name of a webview in the window
+    pub fn find_window(&self, window_id: &WindowId) -> Option<&str> {
+        self.webviews
+            .iter()
+            .find(|(_, webview)| webview.is_window(window_id))
+            .map(|(name, _)| name.as_str())
+    }
+
+    /// Pass the event to the webviews of the window, also to the ones
+    /// sharing the window
+    ///
+    /// Every webview gets the event, the first error is returned.
+    pub fn handle_window_event(
+        &self,
+        event: &WindowEvent,
+        window_id: &WindowId,
+    ) -> Result<(), Error> {
+        let mut result = Ok(());
+        for webview in self.webviews.values() {
+            if webview.is_window(window_id) {
+                let handled = webview.handle_window_event(event, window_id);
+                if result.is_ok() {
+                    result = handled;
+                }
+            }
+        }
+        result
+    }
+
+    /// Pass the message to the named webview
+    pub fn send_to(&self, name: &str, m: MsgToWebView) -> Result<(), Error> {
+        self.webviews
+            .get(name)
+            .ok_or(Error::UnknownWebview)?
+            .send_msg(m)
+    }
+
+    /// Follow the drags of `WebViewBuilder::cross_window_drag`, the drop on
+    /// another webview of the manager is delivered to its page and returned
+    ///
+    /// Call this on each `MainEventsCleared`, and keep the event loop
+    /// polling while `is_dragging`, the webview of the drag holds the mouse
+    /// and the loop gets no events of it.
+    pub fn poll_drag(&mut self) -> Result<Option<CrossWindowDrop>, Error> {
+        // The starts during the drag are dropped, one mouse drags one item
+        for (name, webview) in &self.webviews {
+            if let Some(payload) = webview.take_drag() {
+                if self.drag.is_none() {
+                    self.drag = Some(dnd::Drag {
+                        from: name.clone(),
+                        payload,
+                    });
+                }
+            }
+        }
+        let drag = match self.drag.take() {
+            Some(drag) => drag,
+            None => return Ok(None),
+        };
+        let input = dnd::Input::read();
+        let target = if input.button_down {
+            None
+        } else {
+            self.webview_at(input.cursor)
+        };
+        match dnd::step(&drag.from, &input, target.as_deref()) {
+            dnd::Outcome::Dragging => {
+                self.drag = Some(drag);
+                Ok(None)
+            }
+            dnd::Outcome::Cancelled => Ok(None),
+            dnd::Outcome::Dropped(to) => {
+                self.webviews
+                    .get(&to)
+                    .ok_or(Error::UnknownWebview)?
+                    .post_drop(drag.payload)?;
+                Ok(Some(CrossWindowDrop {
+                    from_tag: drag.from,
+                    to_tag: to,
+                }))
+            }
+        }
+    }
+
+    /// Is a drag of `WebViewBuilder::cross_window_drag` in progress?
+    pub fn is_dragging(&self) -> bool {
+        self.drag.is_some()
+    }
+
+    /// Name of the visible webview at the screen point, the webviews of the
+    /// covered windows are skipped
+    fn webview_at(&self, point: (i32, i32)) -> Option<String> {
+        let root = dnd::root_window_at(point);
+        let targets: Vec<(&str, Rect)> = self
+            .webviews
+            .iter()
+            .filter(|(_, webview)| webview.hwnd() == root && webview.is_visible())
+            .filter_map(|(name, webview)| Some((name.as_str(), webview.screen_rect()?)))
+            .collect();
+        dnd::hit(point, &targets).map(str::to_owned)
+    }
+}
+
+impl<MsgToWebView> Default for WebViewManager<MsgToWebView>
+where
+    MsgToWebView: Debug + Serialize + 'static + Clone,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
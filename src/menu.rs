@@ -0,0 +1,331 @@
+//! Native context menus of the page
+//!
+//! The shim exposes `window.host.menu.show(items, x, y)`, it returns a
+//! promise of the chosen id, or `null` if the menu was dismissed. The items
+//! are `{"id": "copy", "label": "Copy", "enabled": true, "checked": false}`,
+//! `{"separator": true}`, or submenus with the `items`. The coordinates are
+//! the CSS pixels of the page, e.g. `clientX` and `clientY` of the
+//! `contextmenu` event. The page asks with `{"__showMenu": {"id": 1,
+//! "items": [...], "x": 10, "y": 20}}`, and the host answers with
+//! `{"__menuReply": {"id": 1, "choice": "copy", "shown": true}}`. Menus over
+//! the limits of the builder are not shown, and the promise rejects.
+
+use crate::middleware::{InboundCtx, MessageMiddleware, MiddlewareAction};
+use crate::Error;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::iter::once;
+use std::ptr::{null, null_mut};
+use std::rc::Weak;
+use winapi::shared::windef::{HMENU, HWND, POINT};
+use winapi::um::winuser::{
+    AppendMenuW, ClientToScreen, CreatePopupMenu, DestroyMenu, TrackPopupMenuEx, MF_CHECKED,
+    MF_GRAYED, MF_POPUP, MF_SEPARATOR, MF_STRING, TPM_LEFTALIGN, TPM_RETURNCMD, TPM_RIGHTBUTTON,
+    TPM_TOPALIGN,
+};
+use winit::event_loop::EventLoopProxy;
+use winit::platform::windows::WindowExtWindows;
+use winit::window::Window;
+
+#[derive(Deserialize)]
+struct MenuEnvelope {
+    #[serde(rename = "__showMenu")]
+    request: MenuRequest,
+}
+
+#[derive(Deserialize)]
+struct MenuRequest {
+    id: u64,
+    items: Vec<MenuItem>,
+    x: f64,
+    y: f64,
+}
+
+#[derive(Deserialize)]
+struct MenuItem {
+    #[serde(default)]
+    id: String,
+    #[serde(default)]
+    label: String,
+    #[serde(default = "enabled")]
+    enabled: bool,
+    #[serde(default)]
+    checked: bool,
+    #[serde(default)]
+    separator: bool,
+    #[serde(default)]
+    items: Vec<MenuItem>,
+}
+
+fn enabled() -> bool {
+    true
+}
+
+#[derive(Serialize)]
+struct ReplyEnvelope<'a> {
+    #[serde(rename = "__menuReply")]
+    reply: MenuReply<'a>,
+}
+
+#[derive(Serialize)]
+struct MenuReply<'a> {
+    id: u64,
+    choice: Option<&'a str>,
+    shown: bool,
+}
+
+pub(crate) const SCRIPT: &str = r#"(() => {
+    const pending = new Map();
+    let nextId = 0;
+    window.chrome.webview.addEventListener("message", e => {
+        const reply = e.data && e.data.__menuReply;
+        if (!reply) return;
+        const p = pending.get(reply.id);
+        if (!p) return;
+        pending.delete(reply.id);
+        if (reply.shown) {
+            p.resolve(reply.choice);
+        } else {
+            p.reject(new Error("The host did not show the menu"));
+        }
+    });
+    window.host = window.host || {};
+    window.host.menu = {
+        show: (items, x, y) => new Promise((resolve, reject) => {
+            const id = nextId++;
+            pending.set(id, { resolve, reject });
+            window.chrome.webview.postMessage(
+                JSON.stringify({ __showMenu: { id, items, x: +x, y: +y } })
+            );
+        }),
+    };
+})();"#;
+
+/// Limits of the menus the page can ask for
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct Limits {
+    pub max_depth: usize,
+    pub max_items: usize,
+}
+
+impl Limits {
+    /// Is the menu within the limits, the depth counts the top level menu
+    /// and the items count also the separators and the submenus
+    fn allow(&self, items: &[MenuItem]) -> bool {
+        depth(items) <= self.max_depth && count(items) <= self.max_items
+    }
+}
+
+/// Built-in middleware showing the menus of the shim
+pub(crate) struct NativeMenu<E: 'static> {
+    pub limits: Limits,
+    pub controller: Weak<RefCell<Option<webview2::Controller>>>,
+    pub window: Weak<Window>,
+
+    /// Chosen id to the event loop, `None` if the menu was dismissed
+    pub event: Option<(fn(Option<String>) -> E, EventLoopProxy<E>)>,
+}
+
+impl<E: 'static> NativeMenu<E> {
+    fn show(&self, request: &MenuRequest) -> Result<(), Error> {
+        let controller_rc = self
+            .controller
+            .upgrade()
+            .ok_or(Error::ControllerNotCreated)?;
+
+        // Not borrowed in the modal loop of the menu, the window events keep
+        // coming
+        let controller = controller_rc
+            .borrow()
+            .clone()
+            .ok_or(Error::ControllerNotCreated)?;
+        let window = self.window.upgrade().ok_or(Error::WebviewClosed)?;
+
+        let choice = if self.limits.allow(&request.items) {
+            Some(track(&controller, &window, request)?)
+        } else {
+            None
+        };
+
+        let reply = ReplyEnvelope {
+            reply: MenuReply {
+                id: request.id,
+                choice: choice.as_ref().and_then(|c| c.as_deref()),
+                shown: choice.is_some(),
+            },
+        };
+        let msgstr = &serde_json::to_string(&reply)?;
+        controller.get_webview()?.post_web_message_as_json(msgstr)?;
+
+        if let (Some(choice), Some((event, proxy))) = (choice, &self.event) {
+            let _ = proxy.send_event(event(choice));
+        }
+        Ok(())
+    }
+}
+
+impl<E: 'static> MessageMiddleware for NativeMenu<E> {
+    fn inbound(&self, msg: InboundCtx) -> MiddlewareAction<InboundCtx> {
+        match serde_json::from_str::<MenuEnvelope>(&msg.message) {
+            Ok(envelope) => match self.show(&envelope.request) {
+                Ok(()) => MiddlewareAction::Consume,
+                Err(err) => MiddlewareAction::Fail(err),
+            },
+            Err(_) => MiddlewareAction::Continue(msg),
+        }
+    }
+}
+
+/// Show the menu at the point of the page until it's closed, the chosen id
+fn track(
+    controller: &webview2::Controller,
+    window: &Window,
+    request: &MenuRequest,
+) -> Result<Option<String>, Error> {
+    let hwnd = window.hwnd() as HWND;
+    let bounds = controller.get_bounds()?;
+    let scale = window.scale_factor();
+    let mut point = POINT {
+        x: bounds.left + (request.x * scale) as i32,
+        y: bounds.top + (request.y * scale) as i32,
+    };
+
+    let mut ids = Vec::new();
+    let command = unsafe {
+        ClientToScreen(hwnd, &mut point);
+        let menu = build(&request.items, &mut ids);
+        let command = TrackPopupMenuEx(
+            menu,
+            TPM_RETURNCMD | TPM_RIGHTBUTTON | TPM_LEFTALIGN | TPM_TOPALIGN,
+            point.x,
+            point.y,
+            hwnd,
+            null_mut(),
+        );
+        DestroyMenu(menu);
+        command
+    };
+
+    // The menu took the focus from the webview
+    controller.move_focus(webview2::MoveFocusReason::Programmatic)?;
+
+    // Commands are the indices of the ids plus one, zero is the dismissal
+    Ok((command as usize)
+        .checked_sub(1)
+        .and_then(|i| ids.get(i).cloned()))
+}
+
+/// Menu of the items, the ids of the commands are pushed to `ids`
+unsafe fn build(items: &[MenuItem], ids: &mut Vec<String>) -> HMENU {
+    let menu = CreatePopupMenu();
+    for item in items {
+        if item.separator {
+            AppendMenuW(menu, MF_SEPARATOR, 0, null());
+            continue;
+        }
+        let label: Vec<u16> = item.label.encode_utf16().chain(once(0)).collect();
+        let mut flags = MF_STRING;
+        if !item.enabled {
+            flags |= MF_GRAYED;
+        }
+        if item.checked {
+            flags |= MF_CHECKED;
+        }
+        if item.items.is_empty() {
+            ids.push(item.id.clone());
+            AppendMenuW(menu, flags, ids.len(), label.as_ptr());
+        } else {
+            let submenu = build(&item.items, ids);
+            AppendMenuW(menu, flags | MF_POPUP, submenu as usize, label.as_ptr());
+        }
+    }
+    menu
+}
+
+fn depth(items: &[MenuItem]) -> usize {
+    let submenus = items.iter().filter(|i| !i.items.is_empty());
+    1 + submenus.map(|i| depth(&i.items)).max().unwrap_or(0)
+}
+
+fn count(items: &[MenuItem]) -> usize {
+    items.len() + items.iter().map(|i| count(&i.items)).sum::<usize>()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn items(json: &str) -> Vec<MenuItem> {
+        serde_json::from_str(json).unwrap()
+    }
+
+    /// Submenus nested `levels` deep under the top level menu
+    fn nested(levels: usize) -> Vec<MenuItem> {
+        let mut json = r#"[{"id": "leaf", "label": "Leaf"}]"#.to_string();
+        for _ in 0..levels {
+            json = format!(r#"[{{"label": "Sub", "items": {}}}]"#, json);
+        }
+        items(&json)
+    }
+
+    const LIMITS: Limits = Limits {
+        max_depth: 3,
+        max_items: 5,
+    };
+
+    #[test]
+    fn depth_counts_the_levels_of_the_submenus() {
+        assert_eq!(depth(&[]), 1);
+        assert_eq!(depth(&nested(0)), 1);
+        assert_eq!(depth(&nested(2)), 3);
+        let mixed = items(
+            r#"[
+                {"id": "a", "label": "A"},
+                {"label": "Sub", "items": [{"label": "Deep", "items": [{"id": "b"}]}]},
+                {"label": "Other", "items": [{"id": "c"}]}
+            ]"#,
+        );
+        assert_eq!(depth(&mixed), 3);
+    }
+
+    #[test]
+    fn count_includes_the_separators_and_the_submenus() {
+        let menu = items(
+            r#"[
+                {"id": "a", "label": "A"},
+                {"separator": true},
+                {"label": "Sub", "items": [{"id": "b"}, {"id": "c"}]}
+            ]"#,
+        );
+        assert_eq!(count(&menu), 5);
+        assert_eq!(count(&nested(2)), 3);
+    }
+
+    #[test]
+    fn menus_under_and_at_the_limits_are_allowed() {
+        assert!(LIMITS.allow(&nested(1)));
+        assert!(LIMITS.allow(&nested(2)));
+        let five = items(r#"[{"id": "1"}, {"id": "2"}, {"id": "3"}, {"id": "4"}, {"id": "5"}]"#);
+        assert!(LIMITS.allow(&five));
+    }
+
+    #[test]
+    fn menus_over_the_limits_are_refused() {
+        assert!(!LIMITS.allow(&nested(3)));
+        let six = items(
+            r#"[{"id": "1"}, {"id": "2"}, {"id": "3"}, {"id": "4"}, {"id": "5"}, {"id": "6"}]"#,
+        );
+        assert!(!LIMITS.allow(&six));
+
+        // Nested items count too
+        let nested_six = items(
+            r#"[{"label": "Sub", "items": [{"id": "1"}, {"id": "2"}, {"id": "3"}, {"id": "4"}, {"id": "5"}]}]"#,
+        );
+        assert!(!LIMITS.allow(&nested_six));
+    }
+
+    #[test]
+    fn deeply_nested_menu_is_refused() {
+        assert!(!LIMITS.allow(&nested(50)));
+    }
+}
@@ -0,0 +1,328 @@
+//! Middlewares see every message going through the channel
+//!
+//! The order is fixed. Outbound: message is encoded with the `MessageCodec`,
+//! then user middlewares run in the registration order, then it's posted to
+//! the page. Inbound: user middlewares run in the registration order, then
+//! the built-in middlewares (replies to `WebViewWrapper::call`, fetch calls
+//! of the `on_fetch` shim, format requests of the `format_locale` shim, menus
+//! of the `native_menus` shim, favicons of the `on_favicon_changed` shim,
+//! drags of the `frameless` shim, the DOMContentLoaded of
+//! `ShowWebview::OnDomContentLoaded`, the acknowledgements of the
+//! `durable_outbox`, the answers to the pings of the `watchdog`), and then it's handed to the raw message handler or
+//! decoded with the `MessageCodec` to the `MsgFromWebView` and passed to the
+//! event loop proxy.
+
+use crate::strict;
+use crate::Error;
+use std::rc::Rc;
+
+/// Serialized message going to the page
+#[derive(Clone, Debug)]
+pub struct OutboundCtx {
+    pub message: String,
+}
+
+/// Raw message coming from the page
+#[derive(Clone, Debug)]
+pub struct InboundCtx {
+    pub message: String,
+}
+
+#[derive(Debug)]
+pub enum MiddlewareAction<T> {
+    /// Pass the (possibly modified) message to the next middleware
+    Continue(T),
+
+    /// Stop processing, the middleware took care of the message
+    Consume,
+
+    /// Stop processing with an error
+    Fail(Error),
+}
+
+pub trait MessageMiddleware {
+    fn outbound(&self, msg: OutboundCtx) -> MiddlewareAction<OutboundCtx> {
+        MiddlewareAction::Continue(msg)
+    }
+
+    fn inbound(&self, msg: InboundCtx) -> MiddlewareAction<InboundCtx> {
+        MiddlewareAction::Continue(msg)
+    }
+}
+
+#[derive(Clone, Default)]
+pub(crate) struct Chain {
+    middlewares: Vec<Rc<dyn MessageMiddleware>>,
+}
+
+impl Chain {
+    pub fn push(&mut self, middleware: Rc<dyn MessageMiddleware>) {
+        self.middlewares.push(middleware);
+    }
+
+    /// Run the outbound message through the chain, `None` if consumed
+    pub fn outbound(&self, message: String) -> Result<Option<String>, Error> {
+        let mut msg = OutboundCtx { message };
+        for middleware in &self.middlewares {
+            msg = match middleware.outbound(msg) {
+                MiddlewareAction::Continue(msg) => msg,
+                MiddlewareAction::Consume => return Ok(None),
+                MiddlewareAction::Fail(err) => return Err(err),
+            };
+        }
+        Ok(Some(msg.message))
+    }
+
+    /// Run the inbound message through the chain, `None` if consumed
+    pub fn inbound(
+        &self,
+        message: String,
+        builtin: &[&dyn MessageMiddleware],
+    ) -> Result<Option<String>, Error> {
+        let mut msg = InboundCtx { message };
+        let middlewares = self
+            .middlewares
+            .iter()
+            .map(|m| m.as_ref() as &dyn MessageMiddleware);
+        for middleware in middlewares.chain(builtin.iter().copied()) {
+            msg = match middleware.inbound(msg) {
+                MiddlewareAction::Continue(msg) => msg,
+                MiddlewareAction::Consume => return Ok(None),
+                MiddlewareAction::Fail(err) => return Err(err),
+            };
+        }
+        Ok(Some(msg.message))
+    }
+
+    /// Run the message from the page through the chain, the failure goes to
+    /// the policy and drops the message
+    pub fn inbound_forgiving(
+        &self,
+        message: String,
+        builtin: &[&dyn MessageMiddleware],
+        policy: strict::Policy,
+    ) -> Option<String> {
+        match self.inbound(message, builtin) {
+            Ok(message) => message,
+            Err(err) => {
+                policy.fail("Middleware failed the message", &err);
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    // Appends its name to the message and to the log, or ends the chain
+    struct Step {
+        name: &'static str,
+        log: Rc<RefCell<Vec<&'static str>>>,
+        action: fn() -> Option<Error>,
+        consume: bool,
+    }
+
+    impl Step {
+        fn new(name: &'static str, log: &Rc<RefCell<Vec<&'static str>>>) -> Self {
+            Step {
+                name,
+                log: log.clone(),
+                action: || None,
+                consume: false,
+            }
+        }
+
+        fn consuming(mut self) -> Self {
+            self.consume = true;
+            self
+        }
+
+        fn failing(mut self) -> Self {
+            self.action = || Some(Error::OutboxFull);
+            self
+        }
+
+        fn run(&self, message: String) -> MiddlewareAction<String> {
+            self.log.borrow_mut().push(self.name);
+            if let Some(err) = (self.action)() {
+                return MiddlewareAction::Fail(err);
+            }
+            if self.consume {
+                return MiddlewareAction::Consume;
+            }
+            MiddlewareAction::Continue(format!("{}{}", message, self.name))
+        }
+    }
+
+    impl MessageMiddleware for Step {
+        fn outbound(&self, msg: OutboundCtx) -> MiddlewareAction<OutboundCtx> {
+            match self.run(msg.message) {
+                MiddlewareAction::Continue(message) => {
+                    MiddlewareAction::Continue(OutboundCtx { message })
+                }
+                MiddlewareAction::Consume => MiddlewareAction::Consume,
+                MiddlewareAction::Fail(err) => MiddlewareAction::Fail(err),
+            }
+        }
+
+        fn inbound(&self, msg: InboundCtx) -> MiddlewareAction<InboundCtx> {
+            match self.run(msg.message) {
+                MiddlewareAction::Continue(message) => {
+                    MiddlewareAction::Continue(InboundCtx { message })
+                }
+                MiddlewareAction::Consume => MiddlewareAction::Consume,
+                MiddlewareAction::Fail(err) => MiddlewareAction::Fail(err),
+            }
+        }
+    }
+
+    struct Passive;
+
+    impl MessageMiddleware for Passive {}
+
+    fn log() -> Rc<RefCell<Vec<&'static str>>> {
+        Rc::new(RefCell::new(Vec::new()))
+    }
+
+    #[test]
+    fn empty_chain_passes_the_message_as_is() {
+        let chain = Chain::default();
+        assert_eq!(chain.outbound("{}".into()).unwrap().as_deref(), Some("{}"));
+        assert_eq!(
+            chain.inbound("{}".into(), &[]).unwrap().as_deref(),
+            Some("{}")
+        );
+    }
+
+    #[test]
+    fn default_methods_continue() {
+        let mut chain = Chain::default();
+        chain.push(Rc::new(Passive));
+        assert_eq!(chain.outbound("a".into()).unwrap().as_deref(), Some("a"));
+        assert_eq!(
+            chain.inbound("a".into(), &[]).unwrap().as_deref(),
+            Some("a")
+        );
+    }
+
+    #[test]
+    fn outbound_runs_in_the_registration_order() {
+        let log = log();
+        let mut chain = Chain::default();
+        chain.push(Rc::new(Step::new("1", &log)));
+        chain.push(Rc::new(Step::new("2", &log)));
+        assert_eq!(chain.outbound("m".into()).unwrap().as_deref(), Some("m12"));
+        assert_eq!(*log.borrow(), vec!["1", "2"]);
+    }
+
+    #[test]
+    fn inbound_runs_the_user_middlewares_then_the_built_in() {
+        let log = log();
+        let mut chain = Chain::default();
+        chain.push(Rc::new(Step::new("user1", &log)));
+        chain.push(Rc::new(Step::new("user2", &log)));
+        let builtin1 = Step::new("builtin1", &log);
+        let builtin2 = Step::new("builtin2", &log);
+        let result = chain.inbound("m".into(), &[&builtin1, &builtin2]);
+        assert_eq!(
+            result.unwrap().as_deref(),
+            Some("muser1user2builtin1builtin2")
+        );
+        assert_eq!(
+            *log.borrow(),
+            vec!["user1", "user2", "builtin1", "builtin2"]
+        );
+    }
+
+    #[test]
+    fn consume_stops_the_chain() {
+        let log = log();
+        let mut chain = Chain::default();
+        chain.push(Rc::new(Step::new("1", &log).consuming()));
+        chain.push(Rc::new(Step::new("2", &log)));
+        assert_eq!(chain.outbound("m".into()).unwrap(), None);
+        assert_eq!(*log.borrow(), vec!["1"]);
+    }
+
+    #[test]
+    fn user_consume_skips_the_built_in() {
+        let log = log();
+        let mut chain = Chain::default();
+        chain.push(Rc::new(Step::new("user", &log).consuming()));
+        let builtin = Step::new("builtin", &log);
+        assert_eq!(chain.inbound("m".into(), &[&builtin]).unwrap(), None);
+        assert_eq!(*log.borrow(), vec!["user"]);
+    }
+
+    #[test]
+    fn built_in_consume_stops_the_chain() {
+        let log = log();
+        let builtin1 = Step::new("builtin1", &log).consuming();
+        let builtin2 = Step::new("builtin2", &log);
+        let chain = Chain::default();
+        assert_eq!(
+            chain.inbound("m".into(), &[&builtin1, &builtin2]).unwrap(),
+            None
+        );
+        assert_eq!(*log.borrow(), vec!["builtin1"]);
+    }
+
+    #[test]
+    fn fail_propagates_and_stops_the_chain() {
+        let log = log();
+        let mut chain = Chain::default();
+        chain.push(Rc::new(Step::new("1", &log).failing()));
+        chain.push(Rc::new(Step::new("2", &log)));
+        assert!(matches!(chain.outbound("m".into()), Err(Error::OutboxFull)));
+        let builtin = Step::new("builtin", &log);
+        assert!(matches!(
+            chain.inbound("m".into(), &[&builtin]),
+            Err(Error::OutboxFull)
+        ));
+        assert_eq!(*log.borrow(), vec!["1", "1"]);
+    }
+
+    #[test]
+    fn built_in_fail_propagates() {
+        let log = log();
+        let mut chain = Chain::default();
+        chain.push(Rc::new(Step::new("user", &log)));
+        let builtin = Step::new("builtin", &log).failing();
+        assert!(matches!(
+            chain.inbound("m".into(), &[&builtin]),
+            Err(Error::OutboxFull)
+        ));
+        assert_eq!(*log.borrow(), vec!["user", "builtin"]);
+    }
+
+    #[test]
+    fn strict_inbound_panics_on_failure() {
+        let log = log();
+        let chain = Chain::default();
+        let builtin = Step::new("builtin", &log).failing();
+        let message = strict::panic_message(|| {
+            chain.inbound_forgiving("m".into(), &[&builtin], strict::Policy::new(true));
+        })
+        .unwrap();
+        assert!(message.starts_with("Middleware failed the message"));
+    }
+
+    #[test]
+    fn lenient_inbound_drops_failed_message() {
+        let log = log();
+        let chain = Chain::default();
+        let policy = strict::Policy::new(false);
+        let builtin = Step::new("builtin", &log).failing();
+        assert_eq!(
+            chain.inbound_forgiving("m".into(), &[&builtin], policy),
+            None
+        );
+        assert_eq!(
+            chain.inbound_forgiving("m".into(), &[], policy).as_deref(),
+            Some("m")
+        );
+    }
+}
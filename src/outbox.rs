@@ -0,0 +1,430 @@
+//! Outgoing messages kept in a file until the page acknowledges them
+//!
+//! With `WebViewBuilder::durable_outbox` every `send_msg` is appended to the
+//! file before it's posted, and the page gets it as `{"__seq": 7, "msg":
+//! ...}`. The page acknowledges with `{"__ack": 7}`, the highest sequence it
+//! has got without gaps, and the acknowledged messages are removed from the
+//! file. The rest are posted again when a page has loaded, also after the
+//! host or the browser has crashed. The delivery is at least once, the page
+//! should skip the sequences it has already seen.
+//!
+//! The records are `[length u32][crc32 u32][sequence u64][message]`, little
+//! endian, the checksum covers the sequence and the message.
+
+use crate::middleware::{InboundCtx, MessageMiddleware, MiddlewareAction};
+use crate::strict;
+use crate::Error;
+use serde::{Deserialize, Serialize};
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+const HEADER_LEN: usize = 16;
+
+struct Record {
+    seq: u64,
+    message: String,
+}
+
+impl Record {
+    fn len(&self) -> u64 {
+        (HEADER_LEN + self.message.len()) as u64
+    }
+
+    fn write_to(&self, out: &mut Vec<u8>) {
+        let seq = self.seq.to_le_bytes();
+        let crc = crc32(&[&seq, self.message.as_bytes()]);
+        out.extend_from_slice(&(self.message.len() as u32).to_le_bytes());
+        out.extend_from_slice(&crc.to_le_bytes());
+        out.extend_from_slice(&seq);
+        out.extend_from_slice(self.message.as_bytes());
+    }
+}
+
+#[derive(Serialize)]
+pub(crate) struct Envelope<'a> {
+    #[serde(rename = "__seq")]
+    pub seq: u64,
+    pub msg: &'a serde_json::Value,
+}
+
+#[derive(Deserialize)]
+struct Ack {
+    #[serde(rename = "__ack")]
+    ack: u64,
+}
+
+/// Unacknowledged messages of a webview and their file
+pub(crate) struct Outbox {
+    path: PathBuf,
+    max_bytes: u64,
+    records: RefCell<VecDeque<Record>>,
+    next_seq: Cell<u64>,
+    size: Cell<u64>,
+}
+
+impl Outbox {
+    /// Read the messages left in the file, a corrupted file is emptied
+    ///
+    /// The corruption is given with the outbox, the caller decides how to
+    /// warn about it.
+    pub fn open(path: &Path, max_bytes: u64) -> Result<(Outbox, Option<Error>), Error> {
+        let (records, corrupted) = match std::fs::read(path) {
+            Ok(bytes) => match parse(&bytes) {
+                Some(records) => (records, None),
+                None => (VecDeque::new(), Some(Error::OutboxCorrupted)),
+            },
+            Err(err) if err.kind() == io::ErrorKind::NotFound => (VecDeque::new(), None),
+            Err(err) => return Err(err.into()),
+        };
+        let outbox = Outbox {
+            path: path.to_owned(),
+            max_bytes,
+            next_seq: Cell::new(records.back().map_or(1, |r| r.seq + 1)),
+            size: Cell::new(records.iter().map(Record::len).sum()),
+            records: RefCell::new(records),
+        };
+        if corrupted.is_some() {
+            outbox.rewrite()?;
+        }
+        Ok((outbox, corrupted))
+    }
+
+    /// Open the outbox of the builder, the emptied corrupted file goes to
+    /// the policy
+    pub fn open_forgiving(
+        path: &Path,
+        max_bytes: u64,
+        policy: strict::Policy,
+    ) -> Result<Outbox, Error> {
+        let (outbox, corrupted) = Outbox::open(path, max_bytes)?;
+        if let Some(err) = corrupted {
+            policy.fail("Outbox file was emptied", &err);
+        }
+        Ok(outbox)
+    }
+
+    /// Append the message to the file, its sequence number
+    pub fn push(&self, msg: &serde_json::Value) -> Result<u64, Error> {
+        let record = Record {
+            seq: self.next_seq.get(),
+            message: serde_json::to_string(msg)?,
+        };
+        if self.size.get() + record.len() > self.max_bytes {
+            return Err(Error::OutboxFull);
+        }
+        let mut bytes = Vec::new();
+        record.write_to(&mut bytes);
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        file.write_all(&bytes)?;
+        file.sync_data()?;
+
+        self.next_seq.set(record.seq + 1);
+        self.size.set(self.size.get() + record.len());
+        let seq = record.seq;
+        self.records.borrow_mut().push_back(record);
+        Ok(seq)
+    }
+
+    /// Unacknowledged messages in the order they were sent
+    pub fn unacknowledged(&self) -> Vec<(u64, serde_json::Value)> {
+        self.records
+            .borrow()
+            .iter()
+            .filter_map(|r| Some((r.seq, serde_json::from_str(&r.message).ok()?)))
+            .collect()
+    }
+
+    /// Drop the messages up to the sequence from the file
+    fn ack(&self, seq: u64) -> Result<(), Error> {
+        {
+            let mut records = self.records.borrow_mut();
+            if records.front().map_or(true, |r| r.seq > seq) {
+                return Ok(());
+            }
+            while records.front().map_or(false, |r| r.seq <= seq) {
+                records.pop_front();
+            }
+        }
+        self.rewrite()
+    }
+
+    // Written next to the file and renamed over it, a crash leaves either
+    // the old or the new file
+    fn rewrite(&self) -> Result<(), Error> {
+        let mut bytes = Vec::new();
+        for record in self.records.borrow().iter() {
+            record.write_to(&mut bytes);
+        }
+        let mut tmp = self.path.clone().into_os_string();
+        tmp.push(".tmp");
+        let mut file = File::create(&tmp)?;
+        file.write_all(&bytes)?;
+        file.sync_data()?;
+        drop(file);
+        std::fs::rename(&tmp, &self.path)?;
+        self.size.set(bytes.len() as u64);
+        Ok(())
+    }
+}
+
+/// Built-in middleware consuming the acknowledgements of the page
+impl MessageMiddleware for Outbox {
+    fn inbound(&self, msg: InboundCtx) -> MiddlewareAction<InboundCtx> {
+        match serde_json::from_str::<Ack>(&msg.message) {
+            Ok(Ack { ack }) => match self.ack(ack) {
+                Ok(()) => MiddlewareAction::Consume,
+                Err(err) => MiddlewareAction::Fail(err),
+            },
+            Err(_) => MiddlewareAction::Continue(msg),
+        }
+    }
+}
+
+/// Records of the file, `None` if any of them is cut or doesn't match its
+/// checksum
+fn parse(mut bytes: &[u8]) -> Option<VecDeque<Record>> {
+    let u32_at = |b: &[u8], i: usize| {
+        let mut n = [0; 4];
+        n.copy_from_slice(&b[i..i + 4]);
+        u32::from_le_bytes(n)
+    };
+    let mut records = VecDeque::new();
+    while !bytes.is_empty() {
+        if bytes.len() < HEADER_LEN {
+            return None;
+        }
+        let len = u32_at(bytes, 0) as usize;
+        let crc = u32_at(bytes, 4);
+        let rest = &bytes[HEADER_LEN..];
+        if rest.len() < len {
+            return None;
+        }
+        let seq = &bytes[8..HEADER_LEN];
+        let message = &rest[..len];
+        if crc32(&[seq, message]) != crc {
+            return None;
+        }
+        let mut n = [0; 8];
+        n.copy_from_slice(seq);
+        records.push_back(Record {
+            seq: u64::from_le_bytes(n),
+            message: String::from_utf8(message.to_vec()).ok()?,
+        });
+        bytes = &rest[len..];
+    }
+    Some(records)
+}
+
+/// CRC-32 of the zip files, bit by bit, the records are small
+fn crc32(parts: &[&[u8]]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in parts.iter().flat_map(|p| p.iter()) {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    // Own file per test, the tests run in parallel
+    fn temp_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "webviewbuilder_win-outbox-{}-{}",
+            std::process::id(),
+            name
+        ));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    fn seqs(outbox: &Outbox) -> Vec<u64> {
+        outbox
+            .unacknowledged()
+            .into_iter()
+            .map(|(seq, _)| seq)
+            .collect()
+    }
+
+    #[test]
+    fn crc32_matches_the_zip_check_value() {
+        assert_eq!(crc32(&[b"1234", b"56789"]), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn reopen_after_push_replays_the_messages() {
+        let path = temp_path("reopen");
+        let (outbox, corrupted) = Outbox::open(&path, 1024).unwrap();
+        assert!(corrupted.is_none());
+        assert_eq!(outbox.push(&json!({"a": 1})).unwrap(), 1);
+        assert_eq!(outbox.push(&json!({"b": 2})).unwrap(), 2);
+
+        // Crash, nothing else than the file survives
+        drop(outbox);
+        let (outbox, corrupted) = Outbox::open(&path, 1024).unwrap();
+        assert!(corrupted.is_none());
+        assert_eq!(
+            outbox.unacknowledged(),
+            vec![(1, json!({"a": 1})), (2, json!({"b": 2}))]
+        );
+        assert_eq!(outbox.push(&json!({"c": 3})).unwrap(), 3);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn ack_removes_the_messages_from_the_file() {
+        let path = temp_path("ack");
+        let (outbox, _) = Outbox::open(&path, 1024).unwrap();
+        for n in 0..3 {
+            outbox.push(&json!(n)).unwrap();
+        }
+        outbox.ack(2).unwrap();
+        assert_eq!(seqs(&outbox), vec![3]);
+
+        let (outbox, corrupted) = Outbox::open(&path, 1024).unwrap();
+        assert!(corrupted.is_none());
+        assert_eq!(seqs(&outbox), vec![3]);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn ack_past_the_end_empties_the_file() {
+        let path = temp_path("ack-past-end");
+        let (outbox, _) = Outbox::open(&path, 1024).unwrap();
+        outbox.push(&json!(1)).unwrap();
+        outbox.push(&json!(2)).unwrap();
+        outbox.ack(10).unwrap();
+        assert!(outbox.unacknowledged().is_empty());
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), 0);
+
+        // The sequence keeps going up, the page skips the seen ones
+        assert_eq!(outbox.push(&json!(3)).unwrap(), 3);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn ack_before_the_first_message_keeps_everything() {
+        let path = temp_path("ack-before");
+        let (outbox, _) = Outbox::open(&path, 1024).unwrap();
+        outbox.push(&json!(1)).unwrap();
+        outbox.ack(0).unwrap();
+        assert_eq!(seqs(&outbox), vec![1]);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn truncated_tail_empties_the_file() {
+        let path = temp_path("truncated");
+        let (outbox, _) = Outbox::open(&path, 1024).unwrap();
+        outbox.push(&json!("first")).unwrap();
+        outbox.push(&json!("second")).unwrap();
+        drop(outbox);
+
+        // Crash in the middle of the append
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::write(&path, &bytes[..bytes.len() - 3]).unwrap();
+        let (outbox, corrupted) = Outbox::open(&path, 1024).unwrap();
+        assert!(matches!(corrupted, Some(Error::OutboxCorrupted)));
+        assert!(outbox.unacknowledged().is_empty());
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), 0);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn truncated_header_is_corrupted() {
+        let mut bytes = Vec::new();
+        Record {
+            seq: 1,
+            message: "1".into(),
+        }
+        .write_to(&mut bytes);
+        assert!(parse(&bytes).is_some());
+        assert!(parse(&bytes[..HEADER_LEN - 1]).is_none());
+    }
+
+    #[test]
+    fn flipped_checksum_byte_is_corrupted() {
+        let path = temp_path("checksum");
+        let (outbox, _) = Outbox::open(&path, 1024).unwrap();
+        outbox.push(&json!({"a": 1})).unwrap();
+        drop(outbox);
+
+        let mut bytes = std::fs::read(&path).unwrap();
+        bytes[4] ^= 0xFF;
+        std::fs::write(&path, &bytes).unwrap();
+        let (outbox, corrupted) = Outbox::open(&path, 1024).unwrap();
+        assert!(matches!(corrupted, Some(Error::OutboxCorrupted)));
+        assert!(outbox.unacknowledged().is_empty());
+
+        // Usable after emptying
+        assert_eq!(outbox.push(&json!(2)).unwrap(), 1);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn flipped_message_byte_is_corrupted() {
+        let mut bytes = Vec::new();
+        Record {
+            seq: 1,
+            message: "\"hello\"".into(),
+        }
+        .write_to(&mut bytes);
+        let last = bytes.len() - 2;
+        bytes[last] ^= 0x01;
+        assert!(parse(&bytes).is_none());
+    }
+
+    #[test]
+    fn push_past_max_bytes_is_full() {
+        let path = temp_path("full");
+        let (outbox, _) = Outbox::open(&path, HEADER_LEN as u64 + 1).unwrap();
+        outbox.push(&json!(1)).unwrap();
+        assert!(matches!(outbox.push(&json!(2)), Err(Error::OutboxFull)));
+        assert_eq!(seqs(&outbox), vec![1]);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    fn corrupted(name: &str) -> PathBuf {
+        let path = temp_path(name);
+        std::fs::write(&path, b"{not a record").unwrap();
+        path
+    }
+
+    #[test]
+    fn strict_open_panics_on_corrupted_file() {
+        let path = corrupted("strict-corrupted");
+        let message = strict::panic_message(|| {
+            let _ = Outbox::open_forgiving(&path, 1024, strict::Policy::new(true));
+        })
+        .unwrap();
+        assert!(message.starts_with("Outbox file was emptied"));
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn lenient_open_empties_corrupted_file() {
+        let path = corrupted("lenient-corrupted");
+        let outbox = Outbox::open_forgiving(&path, 1024, strict::Policy::new(false)).unwrap();
+        assert!(outbox.unacknowledged().is_empty());
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), 0);
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,80 @@
+//! Printing the page to a PDF file
+//!
+//! This version of WebView2 has no `PrintToPdf` (`ICoreWebView2_7`), the
+//! devtools protocol method `Page.printToPDF` prints the page instead. It
+//! works with every runtime version, but only for the top level page.
+
+use serde::{Deserialize, Serialize};
+
+/// Page setup of the PDF, the sizes are in inches
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PdfSettings {
+    pub landscape: bool,
+    pub print_background: bool,
+    pub scale: f64,
+    pub paper_width: f64,
+    pub paper_height: f64,
+    pub margin_top: f64,
+    pub margin_bottom: f64,
+    pub margin_left: f64,
+    pub margin_right: f64,
+}
+
+/// Same as the browser, US Letter with 1 cm margins
+impl Default for PdfSettings {
+    fn default() -> Self {
+        PdfSettings {
+            landscape: false,
+            print_background: false,
+            scale: 1.0,
+            paper_width: 8.5,
+            paper_height: 11.0,
+            margin_top: 0.4,
+            margin_bottom: 0.4,
+            margin_left: 0.4,
+            margin_right: 0.4,
+        }
+    }
+}
+
+/// Reply of the devtools methods giving a file
+#[derive(Deserialize)]
+pub(crate) struct DataReply {
+    // Base64 of the file
+    pub data: String,
+}
+
+/// Decode the standard base64 with the padding, `None` if it's invalid
+pub(crate) fn decode_base64(s: &str) -> Option<Vec<u8>> {
+    fn value(c: u8) -> Option<u32> {
+        match c {
+            b'A'..=b'Z' => Some((c - b'A') as u32),
+            b'a'..=b'z' => Some((c - b'a') as u32 + 26),
+            b'0'..=b'9' => Some((c - b'0') as u32 + 52),
+            b'+' => Some(62),
+            b'/' => Some(63),
+            _ => None,
+        }
+    }
+
+    let s = s.trim_end_matches('=').as_bytes();
+    let mut out = Vec::with_capacity(s.len() * 3 / 4);
+    for chunk in s.chunks(4) {
+        if chunk.len() == 1 {
+            return None;
+        }
+        let mut n = 0;
+        for (i, &c) in chunk.iter().enumerate() {
+            n |= value(c)? << (18 - 6 * i);
+        }
+        out.push((n >> 16) as u8);
+        if chunk.len() > 2 {
+            out.push((n >> 8) as u8);
+        }
+        if chunk.len() > 3 {
+            out.push(n as u8);
+        }
+    }
+    Some(out)
+}
@@ -0,0 +1,93 @@
+//! Permission requests answered later by the host, see
+//! `WebViewBuilder::defer_permission_requests`
+
+use crate::Error;
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+
+struct Deferred {
+    args: webview2::PermissionRequestedEventArgs,
+    deferral: webview2::Deferral,
+}
+
+// The page waits until the deferral completes, also when the host never
+// responds
+impl Drop for Deferred {
+    fn drop(&mut self) {
+        let _ = self.deferral.complete();
+    }
+}
+
+/// Permission request of a page waiting for the host, e.g. for its own
+/// native dialog
+///
+/// Dropping all the clones without `respond` leaves the request to the
+/// default prompt of WebView2.
+#[derive(Clone)]
+pub struct PermissionRequest {
+    uri: String,
+    kind: webview2::PermissionKind,
+    is_user_initiated: bool,
+    deferred: Rc<RefCell<Option<Deferred>>>,
+}
+
+impl PermissionRequest {
+    pub(crate) fn defer(
+        args: webview2::PermissionRequestedEventArgs,
+    ) -> Result<Self, webview2::Error> {
+        Ok(PermissionRequest {
+            uri: args.get_uri()?,
+            kind: args.get_permission_kind()?,
+            is_user_initiated: args.get_is_user_initiated()?,
+            deferred: Rc::new(RefCell::new(Some(Deferred {
+                deferral: args.get_deferral()?,
+                args,
+            }))),
+        })
+    }
+
+    /// Uri of the page asking
+    pub fn uri(&self) -> &str {
+        &self.uri
+    }
+
+    pub fn kind(&self) -> webview2::PermissionKind {
+        self.kind
+    }
+
+    /// Did the user's gesture start the request, e.g. a click?
+    pub fn is_user_initiated(&self) -> bool {
+        self.is_user_initiated
+    }
+
+    /// Answer the page, `None` leaves it to the default prompt, only the
+    /// first answer counts
+    pub fn respond(&self, decision: Option<webview2::PermissionState>) -> Result<(), Error> {
+        if let Some(deferred) = self.deferred.borrow_mut().take() {
+            if let Some(state) = decision {
+                deferred.args.put_state(state)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for PermissionRequest {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("PermissionRequest")
+            .field("uri", &self.uri)
+            .field("kind", &self.kind)
+            .field("is_user_initiated", &self.is_user_initiated)
+            .finish()
+    }
+}
+
+/// Same request, the clones are equal
+impl PartialEq for PermissionRequest {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.deferred, &other.deferred)
+    }
+}
+
+impl Eq for PermissionRequest {}
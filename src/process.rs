@@ -0,0 +1,30 @@
+//! Failures of the browser processes of the webview
+
+/// Process of the webview that failed, see
+/// `WebViewBuilder::on_process_failure`
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum ProcessFailure {
+    /// The page is blank until it's reloaded, `auto_recover` reloads it
+    RenderProcessExited,
+
+    /// The page doesn't respond, it may recover by itself
+    RenderProcessUnresponsive,
+
+    /// The controller is gone and the webview is closed, it must be built
+    /// again, `auto_recover` builds it
+    BrowserProcessExited,
+}
+
+impl From<webview2::ProcessFailedKind> for ProcessFailure {
+    fn from(kind: webview2::ProcessFailedKind) -> Self {
+        match kind {
+            webview2::ProcessFailedKind::RenderProcessExited => ProcessFailure::RenderProcessExited,
+            webview2::ProcessFailedKind::RenderProcessUnresponsive => {
+                ProcessFailure::RenderProcessUnresponsive
+            }
+            webview2::ProcessFailedKind::BrowserProcessExited => {
+                ProcessFailure::BrowserProcessExited
+            }
+        }
+    }
+}
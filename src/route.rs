@@ -0,0 +1,57 @@
+//! Navigations matching the url patterns are turned into event loop events
+
+/// Routes in the registration order, first matching pattern wins
+#[derive(Clone)]
+pub(crate) struct Routes<T> {
+    routes: Vec<(String, fn(&str) -> T)>,
+}
+
+impl<T> Routes<T> {
+    pub fn new() -> Self {
+        Routes { routes: Vec::new() }
+    }
+
+    pub fn push(&mut self, pattern: String, handler: fn(&str) -> T) {
+        self.routes.push((pattern, handler));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.routes.is_empty()
+    }
+
+    /// Event from the first route matching the url
+    pub fn resolve(&self, url: &str) -> Option<T> {
+        self.routes
+            .iter()
+            .find(|(pattern, _)| glob_match(pattern, url))
+            .map(|(_, handler)| handler(url))
+    }
+}
+
+/// Glob match where `*` matches any run of characters and `?` any single
+/// character
+pub(crate) fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+
+    // Position of the last star, and the text position it's matched up to
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, t));
+            p += 1;
+        } else if let Some((star_p, star_t)) = star {
+            // Let the star swallow one more character
+            p = star_p + 1;
+            t = star_t + 1;
+            star = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
@@ -0,0 +1,275 @@
+//! Request/response correlation on top of the message channel
+//!
+//! Calls are posted to the page in an envelope `{"id": 1, "call": <msg>}`, the
+//! page answers by posting `{"id": 1, "reply": <msg>}` back with the same id.
+//!
+//! The calls pass through the same path as `send_msg`, queued before the
+//! controller is ready and encoded with the codec, and the replies are
+//! decoded with the codec like the other messages of the page. The calls still waiting
+//! are cancelled when the page navigates away or the webview closes, their
+//! replies would never come.
+
+use crate::codec::MessageCodec;
+use crate::middleware::{InboundCtx, MessageMiddleware, MiddlewareAction};
+use crate::Error;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::future::Future;
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::task::{Context, Poll, Waker};
+
+#[derive(Serialize)]
+pub(crate) struct CallEnvelope<'a, T> {
+    pub id: u64,
+    pub call: &'a T,
+}
+
+#[derive(Deserialize)]
+struct ReplyEnvelope {
+    id: u64,
+    reply: serde_json::Value,
+}
+
+#[derive(Default)]
+struct ReplyState {
+    value: Option<Result<serde_json::Value, Error>>,
+    waker: Option<Waker>,
+}
+
+/// Sending half, resolves the future with `Error::CallCancelled` if it's
+/// dropped without a reply
+struct ReplySender {
+    state: Rc<RefCell<ReplyState>>,
+}
+
+impl ReplySender {
+    fn send(self, value: Result<serde_json::Value, Error>) {
+        self.state.borrow_mut().value = Some(value);
+    }
+}
+
+impl Drop for ReplySender {
+    fn drop(&mut self) {
+        let waker = {
+            let mut state = self.state.borrow_mut();
+            if state.value.is_none() {
+                state.value = Some(Err(Error::CallCancelled));
+            }
+            state.waker.take()
+        };
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+/// Future of the reply, must be polled in the thread running the event loop
+pub(crate) struct ReplyFuture<R> {
+    state: Rc<RefCell<ReplyState>>,
+    reply_type: PhantomData<R>,
+}
+
+impl<R: DeserializeOwned> Future for ReplyFuture<R> {
+    type Output = Result<R, Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let mut state = self.state.borrow_mut();
+        match state.value.take() {
+            Some(value) => Poll::Ready(value.and_then(|v| Ok(serde_json::from_value(v)?))),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+/// Calls waiting for the reply
+pub(crate) struct Calls {
+    next_id: Cell<u64>,
+    pending: RefCell<HashMap<u64, ReplySender>>,
+    codec: Rc<dyn MessageCodec>,
+}
+
+impl Calls {
+    pub fn new(codec: Rc<dyn MessageCodec>) -> Calls {
+        Calls {
+            next_id: Cell::new(0),
+            pending: RefCell::new(HashMap::new()),
+            codec,
+        }
+    }
+
+    /// Reserve an id for a new call
+    pub fn register<R>(&self) -> (u64, ReplyFuture<R>) {
+        let id = self.next_id.get();
+        self.next_id.set(id.wrapping_add(1));
+        let state = Rc::new(RefCell::new(ReplyState::default()));
+        self.pending.borrow_mut().insert(
+            id,
+            ReplySender {
+                state: state.clone(),
+            },
+        );
+        (
+            id,
+            ReplyFuture {
+                state,
+                reply_type: PhantomData,
+            },
+        )
+    }
+
+    /// Resolve the call with an error, e.g. if the sending failed
+    pub fn fail(&self, id: u64, err: Error) {
+        let sender = self.pending.borrow_mut().remove(&id);
+        if let Some(sender) = sender {
+            sender.send(Err(err));
+        }
+    }
+
+    /// Resolve the waiting calls with `Error::CallCancelled`
+    pub fn cancel_all(&self) {
+        let pending = std::mem::take(&mut *self.pending.borrow_mut());
+        drop(pending);
+    }
+
+    /// Try to resolve a call from the message, returns true if the message
+    /// was a reply to a pending call
+    fn resolve(&self, message: &str) -> bool {
+        let reply = match self
+            .codec
+            .decode(message)
+            .and_then(serde_json::from_value::<ReplyEnvelope>)
+        {
+            Ok(reply) => reply,
+            Err(_) => return false,
+        };
+        let sender = self.pending.borrow_mut().remove(&reply.id);
+        match sender {
+            Some(sender) => {
+                sender.send(Ok(reply.reply));
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl MessageMiddleware for Calls {
+    fn inbound(&self, msg: InboundCtx) -> MiddlewareAction<InboundCtx> {
+        if self.resolve(&msg.message) {
+            MiddlewareAction::Consume
+        } else {
+            MiddlewareAction::Continue(msg)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec::JsonCodec;
+    use std::task::{RawWaker, RawWakerVTable};
+
+    fn noop_waker() -> Waker {
+        fn clone(_: *const ()) -> RawWaker {
+            RawWaker::new(std::ptr::null(), &VTABLE)
+        }
+        fn noop(_: *const ()) {}
+        static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, noop, noop, noop);
+        unsafe { Waker::from_raw(RawWaker::new(std::ptr::null(), &VTABLE)) }
+    }
+
+    fn poll<R: DeserializeOwned>(reply: &mut ReplyFuture<R>) -> Poll<Result<R, Error>> {
+        let waker = noop_waker();
+        Pin::new(reply).poll(&mut Context::from_waker(&waker))
+    }
+
+    #[test]
+    fn reply_resolves_the_call_with_the_same_id() {
+        let calls = Calls::new(Rc::new(JsonCodec));
+        let (first, mut first_reply) = calls.register::<u32>();
+        let (second, mut second_reply) = calls.register::<u32>();
+        assert_ne!(first, second);
+        assert!(poll(&mut first_reply).is_pending());
+
+        let message = format!(r#"{{"id": {}, "reply": 2}}"#, second);
+        assert!(calls.resolve(&message));
+        assert!(matches!(poll(&mut second_reply), Poll::Ready(Ok(2))));
+        assert!(poll(&mut first_reply).is_pending());
+
+        // Resolved only once
+        assert!(!calls.resolve(&message));
+    }
+
+    #[test]
+    fn other_messages_are_not_replies() {
+        let calls = Calls::new(Rc::new(JsonCodec));
+        let (_, mut reply) = calls.register::<u32>();
+        assert!(!calls.resolve("not json"));
+        assert!(!calls.resolve(r#"{"id": 0}"#));
+        assert!(!calls.resolve(r#"{"id": 5, "reply": 1}"#));
+        assert!(poll(&mut reply).is_pending());
+    }
+
+    #[test]
+    fn failed_call_resolves_with_the_error() {
+        let calls = Calls::new(Rc::new(JsonCodec));
+        let (id, mut reply) = calls.register::<u32>();
+        calls.fail(id, Error::WebviewClosed);
+        assert!(matches!(
+            poll(&mut reply),
+            Poll::Ready(Err(Error::WebviewClosed))
+        ));
+    }
+
+    #[test]
+    fn cancel_all_cancels_the_waiting_calls() {
+        let calls = Calls::new(Rc::new(JsonCodec));
+        let (_, mut first) = calls.register::<u32>();
+        let (_, mut second) = calls.register::<u32>();
+        calls.cancel_all();
+        assert!(matches!(
+            poll(&mut first),
+            Poll::Ready(Err(Error::CallCancelled))
+        ));
+        assert!(matches!(
+            poll(&mut second),
+            Poll::Ready(Err(Error::CallCancelled))
+        ));
+
+        // New calls after the cancel wait for their replies
+        let (id, mut third) = calls.register::<u32>();
+        assert!(poll(&mut third).is_pending());
+        assert!(calls.resolve(&format!(r#"{{"id": {}, "reply": 3}}"#, id)));
+        assert!(matches!(poll(&mut third), Poll::Ready(Ok(3))));
+    }
+
+    /// Reversed JSON, not parseable as JSON
+    struct ReversedCodec;
+
+    impl MessageCodec for ReversedCodec {
+        fn encode(&self, msg: serde_json::Value) -> Result<String, serde_json::Error> {
+            Ok(serde_json::to_string(&msg)?.chars().rev().collect())
+        }
+
+        fn decode(&self, msg: &str) -> Result<serde_json::Value, serde_json::Error> {
+            serde_json::from_str(&msg.chars().rev().collect::<String>())
+        }
+    }
+
+    #[test]
+    fn replies_are_decoded_with_the_codec() {
+        let calls = Calls::new(Rc::new(ReversedCodec));
+        let (id, mut reply) = calls.register::<u32>();
+        let json = format!(r#"{{"id": {}, "reply": 7}}"#, id);
+        assert!(!calls.resolve(&json));
+        let encoded: String = json.chars().rev().collect();
+        assert!(calls.resolve(&encoded));
+        assert!(matches!(poll(&mut reply), Poll::Ready(Ok(7))));
+    }
+}
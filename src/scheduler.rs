@@ -0,0 +1,264 @@
+//! Wakeups of the timed features for the event loop, see `Scheduler`
+
+use crate::Error;
+use std::cell::RefCell;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::rc::Rc;
+use std::time::Instant;
+use winit::event_loop::ControlFlow;
+
+type Run = Rc<dyn Fn(Instant) -> Result<Option<Instant>, Error>>;
+
+struct Task {
+    deadline: Option<Instant>,
+    run: Run,
+}
+
+#[derive(Default)]
+struct Inner {
+    // Entries that don't match the deadline of their task are stale, they
+    // are dropped as they come to the top
+    heap: BinaryHeap<Reverse<(Instant, u64)>>,
+    tasks: HashMap<u64, Task>,
+    next_token: u64,
+}
+
+impl Inner {
+    fn is_current(&self, deadline: Instant, token: u64) -> bool {
+        self.tasks
+            .get(&token)
+            .map_or(false, |task| task.deadline == Some(deadline))
+    }
+}
+
+/// Registered work of a `Scheduler`
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct TaskToken(u64);
+
+/// Deadlines of the webviews and the app in one place, for the
+/// `ControlFlow::WaitUntil`
+///
+/// The webviews built with `WebViewBuilder::scheduler` arm their gestures,
+/// window state writes, traces and watchdog pings here, instead of
+/// `WebViewWrapper::poll`.
+///
+/// ```ignore
+/// event_loop.run(move |event, _, control_flow| {
+///     if let Event::NewEvents(_) = event {
+///         let _ = scheduler.run_due(Instant::now());
+///     }
+///     // ...
+///     *control_flow = scheduler.control_flow();
+/// });
+/// ```
+#[derive(Clone, Default)]
+pub struct Scheduler {
+    inner: Rc<RefCell<Inner>>,
+}
+
+impl Scheduler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the work, it runs once it's armed and due
+    ///
+    /// `run` gets the time it's run at and returns its next deadline, `None`
+    /// disarms it.
+    pub fn register(
+        &self,
+        run: impl Fn(Instant) -> Result<Option<Instant>, Error> + 'static,
+    ) -> TaskToken {
+        let mut inner = self.inner.borrow_mut();
+        let token = inner.next_token;
+        inner.next_token += 1;
+        inner.tasks.insert(
+            token,
+            Task {
+                deadline: None,
+                run: Rc::new(run),
+            },
+        );
+        TaskToken(token)
+    }
+
+    /// Run the work at the deadline, it replaces the earlier deadline, `None`
+    /// disarms it
+    pub fn arm(&self, token: TaskToken, deadline: Option<Instant>) {
+        let mut inner = self.inner.borrow_mut();
+        let task = match inner.tasks.get_mut(&token.0) {
+            Some(task) if task.deadline != deadline => task,
+            _ => return,
+        };
+        task.deadline = deadline;
+        if let Some(deadline) = deadline {
+            inner.heap.push(Reverse((deadline, token.0)));
+        }
+    }
+
+    pub fn unregister(&self, token: TaskToken) {
+        self.inner.borrow_mut().tasks.remove(&token.0);
+    }
+
+    /// Earliest deadline of the armed work, `None` if nothing is armed
+    pub fn next_deadline(&self) -> Option<Instant> {
+        let mut inner = self.inner.borrow_mut();
+        while let Some(&Reverse((deadline, token))) = inner.heap.peek() {
+            if inner.is_current(deadline, token) {
+                return Some(deadline);
+            }
+            inner.heap.pop();
+        }
+        None
+    }
+
+    /// `ControlFlow::WaitUntil` the next deadline, or `ControlFlow::Wait`
+    pub fn control_flow(&self) -> ControlFlow {
+        match self.next_deadline() {
+            Some(deadline) => ControlFlow::WaitUntil(deadline),
+            None => ControlFlow::Wait,
+        }
+    }
+
+    /// Run the work that is due, each once
+    ///
+    /// All the due work runs, the first error is returned.
+    pub fn run_due(&self, now: Instant) -> Result<(), Error> {
+        let mut due = Vec::new();
+        {
+            let mut inner = self.inner.borrow_mut();
+            while let Some(&Reverse((deadline, token))) = inner.heap.peek() {
+                if deadline > now {
+                    break;
+                }
+                inner.heap.pop();
+                match inner.tasks.get_mut(&token) {
+                    Some(task) if task.deadline == Some(deadline) => {
+                        task.deadline = None;
+                        due.push((token, task.run.clone()));
+                    }
+                    _ => (),
+                }
+            }
+        }
+
+        // Not borrowed while running, the work may arm and register
+        let mut result = Ok(());
+        for (token, run) in due {
+            match run(now) {
+                Ok(deadline) => self.arm(TaskToken(token), deadline),
+                Err(err) => {
+                    if result.is_ok() {
+                        result = Err(err);
+                    }
+                }
+            }
+        }
+        result
+    }
+}
+
+/// Work of a webview, unregistered when dropped
+pub(crate) struct Registration {
+    scheduler: Scheduler,
+    token: TaskToken,
+}
+
+impl Registration {
+    pub fn new(
+        scheduler: &Scheduler,
+        run: impl Fn(Instant) -> Result<Option<Instant>, Error> + 'static,
+    ) -> Self {
+        Registration {
+            scheduler: scheduler.clone(),
+            token: scheduler.register(run),
+        }
+    }
+
+    pub fn arm(&self, deadline: Option<Instant>) {
+        self.scheduler.arm(self.token, deadline);
+    }
+
+    /// Unregister before the drop, e.g. when the webview is closed
+    pub fn unregister(&self) {
+        self.scheduler.unregister(self.token);
+    }
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        self.unregister();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::time::Duration;
+
+    fn counter(scheduler: &Scheduler) -> (Rc<Cell<u32>>, Registration) {
+        let runs = Rc::new(Cell::new(0));
+        let runs_ = runs.clone();
+        let registration = Registration::new(scheduler, move |_| {
+            runs_.set(runs_.get() + 1);
+            Ok(None)
+        });
+        (runs, registration)
+    }
+
+    #[test]
+    fn runs_the_armed_work_once_when_due() {
+        let scheduler = Scheduler::new();
+        let (runs, registration) = counter(&scheduler);
+        let now = Instant::now();
+        let deadline = now + Duration::from_millis(10);
+        registration.arm(Some(deadline));
+        assert_eq!(scheduler.next_deadline(), Some(deadline));
+        scheduler.run_due(now).unwrap();
+        assert_eq!(runs.get(), 0);
+        scheduler.run_due(deadline).unwrap();
+        scheduler.run_due(deadline).unwrap();
+        assert_eq!(runs.get(), 1);
+        assert_eq!(scheduler.next_deadline(), None);
+    }
+
+    #[test]
+    fn rearming_replaces_the_deadline() {
+        let scheduler = Scheduler::new();
+        let (runs, registration) = counter(&scheduler);
+        let now = Instant::now();
+        registration.arm(Some(now));
+        registration.arm(Some(now + Duration::from_secs(1)));
+        scheduler.run_due(now).unwrap();
+        assert_eq!(runs.get(), 0);
+        registration.arm(None);
+        assert_eq!(scheduler.next_deadline(), None);
+    }
+
+    #[test]
+    fn dropping_the_registration_unregisters() {
+        let scheduler = Scheduler::new();
+        let (runs, registration) = counter(&scheduler);
+        let now = Instant::now();
+        registration.arm(Some(now));
+        drop(registration);
+        assert_eq!(scheduler.next_deadline(), None);
+        scheduler.run_due(now).unwrap();
+        assert_eq!(runs.get(), 0);
+        assert!(scheduler.inner.borrow().tasks.is_empty());
+    }
+
+    #[test]
+    fn first_error_is_returned_and_the_rest_run() {
+        let scheduler = Scheduler::new();
+        let now = Instant::now();
+        let failing = Registration::new(&scheduler, |_| Err(Error::TraceBusy));
+        let (runs, registration) = counter(&scheduler);
+        failing.arm(Some(now));
+        registration.arm(Some(now));
+        assert!(matches!(scheduler.run_due(now), Err(Error::TraceBusy)));
+        assert_eq!(runs.get(), 1);
+    }
+}
@@ -0,0 +1,51 @@
+//! Posting messages to the webview from other threads
+
+use crate::Error;
+use winit::event_loop::EventLoopProxy;
+
+/// `Send` handle that posts messages to the webview through the event loop
+///
+/// The message is wrapped into an event of the event loop, which must pass
+/// it on to `send_msg`:
+///
+/// ```ignore
+/// let sender = WebViewSender::new(event_loop.create_proxy(), AppEvent::ToWebView);
+/// std::thread::spawn(move || sender.send(MsgToWebView::Progress(50)));
+///
+/// event_loop.run(move |event, _, _| match event {
+///     Event::UserEvent(AppEvent::ToWebView(m)) => {
+///         let _ = webview.send_msg(m);
+///     }
+///     _ => (),
+/// });
+/// ```
+pub struct WebViewSender<MsgToWebView, EventLoopType: 'static> {
+    proxy: EventLoopProxy<EventLoopType>,
+    wrap: fn(MsgToWebView) -> EventLoopType,
+}
+
+impl<MsgToWebView, EventLoopType: 'static> WebViewSender<MsgToWebView, EventLoopType> {
+    pub fn new(
+        proxy: EventLoopProxy<EventLoopType>,
+        wrap: fn(MsgToWebView) -> EventLoopType,
+    ) -> Self {
+        WebViewSender { proxy, wrap }
+    }
+
+    /// Send the message to the event loop, fails if the event loop has exited
+    pub fn send(&self, m: MsgToWebView) -> Result<(), Error> {
+        self.proxy
+            .send_event((self.wrap)(m))
+            .map_err(|_| Error::EventLoopClosed)
+    }
+}
+
+// Derive would require the message types to be Clone
+impl<MsgToWebView, EventLoopType: 'static> Clone for WebViewSender<MsgToWebView, EventLoopType> {
+    fn clone(&self) -> Self {
+        WebViewSender {
+            proxy: self.proxy.clone(),
+            wrap: self.wrap,
+        }
+    }
+}
@@ -0,0 +1,128 @@
+//! Navigation scoped settings, see `WebViewWrapper::with_navigation_settings`
+//!
+//! The temporary settings remember the values they replaced, and those are
+//! restored when a navigation leaves the origin of the temporary url.
+
+use webview2::Settings;
+
+/// Values for the webview settings, `None` leaves the setting untouched
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct WebViewSettings {
+    pub is_script_enabled: Option<bool>,
+    pub is_web_message_enabled: Option<bool>,
+    pub are_default_script_dialogs_enabled: Option<bool>,
+    pub is_status_bar_enabled: Option<bool>,
+    pub are_dev_tools_enabled: Option<bool>,
+    pub are_default_context_menus_enabled: Option<bool>,
+    pub are_host_objects_allowed: Option<bool>,
+    pub is_zoom_control_enabled: Option<bool>,
+    pub is_built_in_error_page_enabled: Option<bool>,
+}
+
+macro_rules! apply_settings {
+    ($self:ident, $settings:ident, $previous:ident; $($field:ident: $get:ident, $put:ident;)*) => {
+        $(
+            if let Some(value) = $self.$field {
+                $previous.$field = Some($settings.$get()?);
+                $settings.$put(value)?;
+            }
+        )*
+    };
+}
+
+impl WebViewSettings {
+    /// Apply the settings, returns the previous values of the changed ones
+    pub(crate) fn apply(&self, settings: &Settings) -> Result<WebViewSettings, webview2::Error> {
+        let mut previous = WebViewSettings::default();
+        apply_settings! { self, settings, previous;
+            is_script_enabled: get_is_script_enabled, put_is_script_enabled;
+            is_web_message_enabled: get_is_web_message_enabled, put_is_web_message_enabled;
+            are_default_script_dialogs_enabled: get_are_default_script_dialogs_enabled, put_are_default_script_dialogs_enabled;
+            is_status_bar_enabled: get_is_status_bar_enabled, put_is_status_bar_enabled;
+            are_dev_tools_enabled: get_are_dev_tools_enabled, put_are_dev_tools_enabled;
+            are_default_context_menus_enabled: get_are_default_context_menus_enabled, put_are_default_context_menus_enabled;
+            are_host_objects_allowed: get_are_host_objects_allowed, put_are_host_objects_allowed;
+            is_zoom_control_enabled: get_is_zoom_control_enabled, put_is_zoom_control_enabled;
+            is_built_in_error_page_enabled: get_is_built_in_error_page_enabled, put_is_built_in_error_page_enabled;
+        }
+        Ok(previous)
+    }
+}
+
+/// Settings applied for a single navigation
+pub(crate) struct TemporarySettings {
+    /// Origin of the navigation, settings are restored when leaving it
+    pub origin: String,
+
+    /// Values to restore
+    pub previous: WebViewSettings,
+}
+
+/// Origin part of the url, e.g. `https://example.com:8080`
+///
+/// Urls without an authority such as `about:blank` are returned as is.
+pub(crate) fn url_origin(url: &str) -> &str {
+    match url.find("://") {
+        Some(i) => {
+            let authority = &url[i + 3..];
+            match authority.find(|c| c == '/' || c == '?' || c == '#') {
+                Some(end) => &url[..i + 3 + end],
+                None => url,
+            }
+        }
+        None => url,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn origin_ends_at_the_path_query_or_fragment() {
+        assert_eq!(url_origin("https://example.com/a/b"), "https://example.com");
+        assert_eq!(url_origin("https://example.com?q=1"), "https://example.com");
+        assert_eq!(url_origin("https://example.com#top"), "https://example.com");
+        assert_eq!(url_origin("https://example.com"), "https://example.com");
+    }
+
+    #[test]
+    fn origin_keeps_the_port_and_the_scheme() {
+        assert_eq!(
+            url_origin("https://example.com:8080/path"),
+            "https://example.com:8080"
+        );
+        assert_ne!(
+            url_origin("http://example.com/"),
+            url_origin("https://example.com/")
+        );
+        assert_ne!(
+            url_origin("https://example.com/"),
+            url_origin("https://example.com:8443/")
+        );
+    }
+
+    #[test]
+    fn same_origin_for_the_pages_of_the_site() {
+        assert_eq!(
+            url_origin("https://login.example.com/authorize?client=1"),
+            url_origin("https://login.example.com/callback#token")
+        );
+        assert_ne!(
+            url_origin("https://login.example.com/"),
+            url_origin("https://example.com/")
+        );
+    }
+
+    #[test]
+    fn urls_without_an_authority_are_kept() {
+        assert_eq!(url_origin("about:blank"), "about:blank");
+        assert_eq!(url_origin("data:text/html,<p>"), "data:text/html,<p>");
+        assert_eq!(url_origin(""), "");
+    }
+
+    #[test]
+    fn file_urls_have_an_empty_host() {
+        assert_eq!(url_origin("file:///C:/app/index.html"), "file://");
+    }
+}
@@ -0,0 +1,164 @@
+//! Showing the webview, see `ShowWebview`
+
+use crate::layout::{LayoutChange, LayoutHistory, LayoutTrigger};
+use crate::middleware::{InboundCtx, MessageMiddleware, MiddlewareAction};
+use crate::Error;
+use std::cell::RefCell;
+use winapi::shared::windef::HWND;
+use winapi::um::winuser::{IsIconic, IsWindowVisible};
+use winit::platform::windows::WindowExtWindows;
+use winit::window::Window;
+
+/// This version of WebView2 has no DOMContentLoaded event, the page posts it
+/// as a message
+pub(crate) const SCRIPT: &str = r#"document.addEventListener("DOMContentLoaded", () => {
+    window.chrome.webview.postMessage('{"__domContentLoaded":true}');
+});"#;
+
+const MESSAGE: &str = r#"{"__domContentLoaded":true}"#;
+
+/// Built-in middleware consuming the DOMContentLoaded messages
+pub(crate) struct DomContentLoaded<F: Fn()> {
+    pub on_loaded: F,
+}
+
+impl<F: Fn()> MessageMiddleware for DomContentLoaded<F> {
+    fn inbound(&self, msg: InboundCtx) -> MiddlewareAction<InboundCtx> {
+        if msg.message == MESSAGE {
+            (self.on_loaded)();
+            MiddlewareAction::Consume
+        } else {
+            MiddlewareAction::Continue(msg)
+        }
+    }
+}
+
+/// Document title of a hidden window, set when the window is shown
+///
+/// Pages that keep changing the title, e.g. with notification counters,
+/// would otherwise wake up an invisible window on every change.
+#[derive(Default)]
+pub(crate) struct DeferredTitle {
+    title: RefCell<Option<String>>,
+}
+
+impl DeferredTitle {
+    pub fn set(&self, window: &Window, title: String) {
+        let hwnd = window.hwnd() as HWND;
+        let visible = unsafe { IsWindowVisible(hwnd) } != 0;
+        if let Some(title) = self.update(visible, title) {
+            window.set_title(&title);
+
+            // Minimized window shows the title only in the taskbar
+            if unsafe { IsIconic(hwnd) } == 0 {
+                window.request_redraw();
+            }
+        }
+    }
+
+    /// Set the latest title, before the window is shown
+    pub fn apply(&self, window: &Window) {
+        if let Some(title) = self.take() {
+            window.set_title(&title);
+        }
+    }
+
+    /// Title to set now, `None` if it waits for the window to be shown
+    fn update(&self, visible: bool, title: String) -> Option<String> {
+        let mut deferred = self.title.borrow_mut();
+        if visible {
+            // Older title must not replace this one when shown
+            deferred.take();
+            Some(title)
+        } else {
+            *deferred = Some(title);
+            None
+        }
+    }
+
+    fn take(&self) -> Option<String> {
+        self.title.borrow_mut().take()
+    }
+}
+
+/// Show the controller, and the window unless it's owned by the caller
+pub(crate) fn show(
+    controller: &webview2::Controller,
+    window: &Window,
+    owns_window: bool,
+    focus_webview: bool,
+    layout: &LayoutHistory,
+    title: &DeferredTitle,
+) -> Result<(), Error> {
+    controller.put_is_visible(true)?;
+    layout.record(LayoutTrigger::Shown, LayoutChange::Visible(true));
+    if owns_window {
+        title.apply(window);
+        window.set_visible(true);
+    }
+
+    // So that the first keystroke lands in the page
+    if focus_webview {
+        controller.move_focus(webview2::MoveFocusReason::Programmatic)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Titles set to the window, for the changes while hidden and visible
+    fn set_titles(title: &DeferredTitle, changes: &[(bool, &str)]) -> Vec<String> {
+        changes
+            .iter()
+            .filter_map(|&(visible, t)| title.update(visible, t.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn hidden_window_gets_only_the_latest_title_when_shown() {
+        let title = DeferredTitle::default();
+        for n in 1..=100 {
+            assert_eq!(title.update(false, format!("({}) Inbox", n)), None);
+        }
+        assert_eq!(title.take().as_deref(), Some("(100) Inbox"));
+        assert_eq!(title.take(), None);
+    }
+
+    #[test]
+    fn visible_window_gets_every_title() {
+        let title = DeferredTitle::default();
+        let set = set_titles(&title, &[(true, "a"), (true, "b"), (true, "c")]);
+        assert_eq!(set, vec!["a", "b", "c"]);
+        assert_eq!(title.take(), None);
+    }
+
+    #[test]
+    fn title_set_while_visible_replaces_the_deferred_one() {
+        let title = DeferredTitle::default();
+        let set = set_titles(&title, &[(false, "hidden"), (true, "visible")]);
+        assert_eq!(set, vec!["visible"]);
+        assert_eq!(title.take(), None);
+    }
+
+    #[test]
+    fn dom_content_loaded_message_is_consumed() {
+        let loaded = std::cell::Cell::new(0);
+        let middleware = DomContentLoaded {
+            on_loaded: || loaded.set(loaded.get() + 1),
+        };
+        let msg = InboundCtx {
+            message: MESSAGE.to_string(),
+        };
+        assert!(matches!(middleware.inbound(msg), MiddlewareAction::Consume));
+        let msg = InboundCtx {
+            message: r#"{"other":true}"#.to_string(),
+        };
+        assert!(matches!(
+            middleware.inbound(msg),
+            MiddlewareAction::Continue(_)
+        ));
+        assert_eq!(loaded.get(), 1);
+    }
+}
@@ -0,0 +1,190 @@
+//! Strict mode, see `WebViewBuilder::strict`
+
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::fmt::Debug;
+use winit::window::WindowId;
+
+/// Decides what happens on the conditions that are forgiven by default, all
+/// the forgiving paths go through this
+#[derive(Copy, Clone, Debug, Default)]
+pub(crate) struct Policy {
+    strict: bool,
+}
+
+impl Policy {
+    pub fn new(strict: bool) -> Self {
+        Policy { strict }
+    }
+
+    /// Error that has nowhere to go, e.g. in the webview2 event handlers
+    ///
+    /// Strict mode panics with the context, otherwise it's printed in the
+    /// debug builds.
+    pub fn fail(&self, context: &str, err: &impl Debug) {
+        if self.strict {
+            panic!("{}, error: {:?}", context, err);
+        }
+        #[cfg(debug_assertions)]
+        println!("{}, error: {:?}", context, err);
+    }
+
+    /// Suspicious condition that may as well be right, e.g. the layout
+    /// artifacts of `WebViewBuilder::debug_layout`
+    ///
+    /// Printed in the debug builds in both modes, strict mode doesn't panic.
+    pub fn warn(&self, context: &str, details: &impl Debug) {
+        #[cfg(debug_assertions)]
+        println!("{}, details: {:?}", context, details);
+        #[cfg(not(debug_assertions))]
+        let _ = (context, details);
+    }
+
+    /// Run `f` for each item, e.g. posting the queued messages, the errors
+    /// are handled with `fail` and don't stop the rest
+    pub fn each<T: Debug, E: Debug>(
+        &self,
+        context: &str,
+        items: impl IntoIterator<Item = T>,
+        mut f: impl FnMut(&T) -> Result<(), E>,
+    ) {
+        for item in items {
+            if let Err(err) = f(&item) {
+                self.fail(&format!("{}: {:?}", context, item), &err);
+            }
+        }
+    }
+
+    /// Result of a webview2 event handler, the errors are handled with
+    /// `fail`
+    pub fn handler(
+        &self,
+        context: &str,
+        result: Result<(), impl Debug>,
+    ) -> Result<(), webview2::Error> {
+        if let Err(err) = &result {
+            self.fail(context, err);
+        }
+        Ok(())
+    }
+}
+
+/// Windows whose events were given to a webview of another window
+///
+/// Passing every event to every webview is the intended usage, so the
+/// warning is given once per window.
+#[derive(Default)]
+pub(crate) struct ForeignWindows {
+    warned: RefCell<HashSet<WindowId>>,
+}
+
+impl ForeignWindows {
+    /// Warn about the event of the other window, true if it was warned now
+    pub fn event(&self, policy: Policy, own: WindowId, other: WindowId) -> bool {
+        if !self.warned.borrow_mut().insert(other) {
+            return false;
+        }
+        policy.warn(
+            "Webview skipped the events of another window",
+            &format_args!("webview {:?}, event {:?}", own, other),
+        );
+        true
+    }
+}
+
+/// Panic message of `f`, `None` if it didn't panic
+#[cfg(test)]
+pub(crate) fn panic_message(f: impl FnOnce()) -> Option<String> {
+    let payload = std::panic::catch_unwind(std::panic::AssertUnwindSafe(f)).err()?;
+    Some(*payload.downcast::<String>().unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lenient_fail_forgives() {
+        assert_eq!(
+            panic_message(|| Policy::new(false).fail("Context", &"error")),
+            None
+        );
+    }
+
+    #[test]
+    fn strict_fail_panics_with_the_context() {
+        let message = panic_message(|| Policy::new(true).fail("Context", &"error")).unwrap();
+        assert_eq!(message, "Context, error: \"error\"");
+    }
+
+    #[test]
+    fn lenient_handler_forgives() {
+        let result = Policy::new(false).handler("Context", Err::<(), _>("error"));
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn strict_handler_panics_with_the_context() {
+        let message = panic_message(|| {
+            let _ = Policy::new(true).handler("Context", Err::<(), _>("error"));
+        });
+        assert!(message.unwrap().starts_with("Context"));
+    }
+
+    #[test]
+    fn handler_passes_ok_in_both_modes() {
+        for &strict in &[false, true] {
+            let result = Policy::new(strict).handler("Context", Ok::<(), &str>(()));
+            assert!(result.is_ok());
+        }
+    }
+
+    fn post(sent: &mut Vec<u32>, m: &u32) -> Result<(), &'static str> {
+        if *m == 2 {
+            return Err("error");
+        }
+        sent.push(*m);
+        Ok(())
+    }
+
+    #[test]
+    fn lenient_each_keeps_going_after_a_failure() {
+        let mut sent = Vec::new();
+        Policy::new(false).each("Queued message", vec![1, 2, 3], |m| post(&mut sent, m));
+        assert_eq!(sent, vec![1, 3]);
+    }
+
+    #[test]
+    fn strict_each_panics_with_the_failed_item() {
+        let mut sent = Vec::new();
+        let message = panic_message(|| {
+            Policy::new(true).each("Queued message", vec![1, 2, 3], |m| post(&mut sent, m))
+        });
+        assert_eq!(message.unwrap(), "Queued message: 2, error: \"error\"");
+        assert_eq!(sent, vec![1]);
+    }
+
+    #[test]
+    fn warn_never_panics() {
+        for &strict in &[false, true] {
+            assert_eq!(
+                panic_message(|| Policy::new(strict).warn("Context", &1)),
+                None
+            );
+        }
+    }
+
+    #[test]
+    fn foreign_window_is_warned_once_in_both_modes() {
+        let (own, other) = unsafe { (WindowId::dummy(), WindowId::dummy()) };
+        for &strict in &[false, true] {
+            let foreign = ForeignWindows::default();
+            let policy = Policy::new(strict);
+            assert_eq!(
+                panic_message(|| assert!(foreign.event(policy, own, other))),
+                None
+            );
+            assert!(!foreign.event(policy, own, other));
+        }
+    }
+}
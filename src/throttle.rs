@@ -0,0 +1,54 @@
+//! Frame rate throttling
+//!
+//! Chromium has no switch to cap the frame rate of a page, and the emulation
+//! methods of the devtools protocol don't do it either. Most of the rendering
+//! work of the busy pages (canvas, WebGL, JS animations) is driven by the
+//! `requestAnimationFrame`, so the most effective way is to throttle it. CSS
+//! animations and videos are not affected. Hidden webviews are throttled by
+//! the browser itself.
+
+/// Shim that throttles `requestAnimationFrame` to `window.__webviewMaxFps`
+pub(crate) fn script(max_fps: u32) -> String {
+    format!(
+        r#"(() => {{
+    const raf = window.requestAnimationFrame.bind(window);
+    const caf = window.cancelAnimationFrame.bind(window);
+    const pending = new Map();
+    let nextId = 1;
+    let last = 0;
+    window.__webviewMaxFps = {};
+    window.requestAnimationFrame = cb => {{
+        const id = nextId++;
+        const fps = window.__webviewMaxFps;
+        const wait = fps ? last + 1000 / fps - performance.now() : 0;
+        const frame = () => pending.set(id, {{
+            raf: raf(t => {{
+                pending.delete(id);
+                last = t;
+                cb(t);
+            }}),
+        }});
+        if (wait > 0) {{
+            pending.set(id, {{ timeout: setTimeout(frame, wait) }});
+        }} else {{
+            frame();
+        }}
+        return id;
+    }};
+    window.cancelAnimationFrame = id => {{
+        const p = pending.get(id);
+        if (p) {{
+            clearTimeout(p.timeout);
+            if (p.raf) caf(p.raf);
+            pending.delete(id);
+        }}
+    }};
+}})();"#,
+        max_fps
+    )
+}
+
+/// Change the limit of the current document, `None` removes the limit
+pub(crate) fn set_script(max_fps: Option<u32>) -> String {
+    format!("window.__webviewMaxFps = {};", max_fps.unwrap_or(0))
+}
@@ -0,0 +1,111 @@
+//! Timed features of a webview, run by `WebViewWrapper::poll` or the
+//! `Scheduler`
+//!
+//! Holds only the parts of the wrapper the features need, and the controller
+//! weakly, so the scheduled work doesn't keep the window alive.
+
+use crate::{interaction, watchdog, Error, Interaction, WindowStatePersistence};
+use std::cell::RefCell;
+use std::rc::{Rc, Weak};
+use std::time::Instant;
+
+#[cfg(feature = "cdp")]
+use crate::trace;
+
+pub(crate) struct Timers {
+    pub interaction: Option<Rc<interaction::Detector>>,
+    pub interaction_fn: Option<fn(Interaction)>,
+    pub controller: Weak<RefCell<Option<webview2::Controller>>>,
+    pub window_state_persistence: Option<(WindowStatePersistence, Rc<str>)>,
+    pub watchdog: Option<Rc<watchdog::Watchdog>>,
+    #[cfg(feature = "cdp")]
+    pub tracer: Rc<trace::Tracer>,
+}
+
+impl Timers {
+    /// Run the features that are due, the next deadline
+    pub fn poll(&self, now: Instant) -> Result<Option<Instant>, Error> {
+        // Not borrowed while posting, the handlers may use the controller
+        let controller = self.controller.upgrade().and_then(|c| c.borrow().clone());
+
+        // Without the controller the features still end, the page just isn't
+        // told
+        match &controller {
+            Some(controller) => self.interaction(controller, |d| d.poll(now))?,
+            None => {
+                if let Some(detector) = &self.interaction {
+                    detector.poll(now);
+                }
+            }
+        }
+        #[cfg(feature = "cdp")]
+        {
+            if self.tracer.take_expired(now) {
+                if let Some(controller) = &controller {
+                    controller.get_webview()?.call_dev_tools_protocol_method(
+                        "Tracing.end",
+                        "{}",
+                        |_| Ok(()),
+                    )?;
+                }
+            }
+        }
+        if let Some((persistence, _)) = &self.window_state_persistence {
+            persistence.poll(now)?;
+        }
+        if let Some(watchdog) = &self.watchdog {
+            let step = watchdog.poll(now, controller.is_some());
+            if let Some(controller) = &controller {
+                let webview = controller.get_webview()?;
+                if let Some(seq) = step.ping {
+                    let msgstr = serde_json::to_string(&watchdog::Envelope { seq })?;
+                    webview.post_web_message_as_json(&msgstr)?;
+                }
+                if step.reload {
+                    webview.reload()?;
+                }
+            }
+        }
+        Ok(self.deadline())
+    }
+
+    /// Earliest deadline of the features, `None` when nothing is in progress
+    pub fn deadline(&self) -> Option<Instant> {
+        let interaction = self.interaction.as_ref().and_then(|d| d.deadline());
+        let persistence = self
+            .window_state_persistence
+            .as_ref()
+            .and_then(|(persistence, _)| persistence.deadline());
+        let watchdog = self.watchdog.as_ref().and_then(|w| w.deadline());
+        let deadline = interaction
+            .into_iter()
+            .chain(persistence)
+            .chain(watchdog)
+            .min();
+        #[cfg(feature = "cdp")]
+        let deadline = deadline.into_iter().chain(self.tracer.deadline()).min();
+        deadline
+    }
+
+    /// Tell the page and the hook if the gesture detector changed its state
+    pub fn interaction(
+        &self,
+        controller: &webview2::Controller,
+        f: impl FnOnce(&interaction::Detector) -> Option<Interaction>,
+    ) -> Result<(), Error> {
+        let changed = match &self.interaction {
+            Some(detector) => f(detector),
+            None => return Ok(()),
+        };
+        if let Some(interaction) = changed {
+            if let Some(interaction_fn) = self.interaction_fn {
+                interaction_fn(interaction);
+            }
+            let msgstr = serde_json::to_string(&interaction::Envelope { interaction })?;
+            controller
+                .get_webview()?
+                .post_web_message_as_json(&msgstr)?;
+        }
+        Ok(())
+    }
+}
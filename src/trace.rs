@@ -0,0 +1,198 @@
+//! Performance traces of the page, with the `cdp` feature
+//!
+//! The trace is recorded with the devtools protocol `Tracing.start` and
+//! `Tracing.end`. The events come in `Tracing.dataCollected` chunks and are
+//! written to the file as they come, the file is the JSON trace format of the
+//! Performance panel and `chrome://tracing`. `WebViewWrapper::poll` ends the
+//! trace once its duration has passed.
+
+use crate::{Error, ErrorContext};
+use serde::{Deserialize, Serialize};
+use std::cell::{Cell, RefCell};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::time::Instant;
+
+/// Outcome of a finished trace
+#[derive(Clone, Debug)]
+pub struct TraceStats {
+    pub path: PathBuf,
+    pub events: u64,
+
+    /// The browser's trace buffer overflowed, some events are missing
+    pub data_loss: bool,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct StartParams {
+    pub categories: String,
+    pub transfer_mode: &'static str,
+}
+
+#[derive(Deserialize)]
+struct DataCollected {
+    value: Vec<serde_json::Value>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct TracingComplete {
+    #[serde(default)]
+    data_loss_occurred: bool,
+}
+
+type OnDone = Box<dyn FnOnce(Result<TraceStats, Error>)>;
+
+struct Trace {
+    file: BufWriter<File>,
+    path: PathBuf,
+    events: u64,
+
+    // First error of the writing, reported when the trace completes
+    error: Option<Error>,
+
+    // None once `Tracing.end` is called
+    deadline: Option<Instant>,
+    on_done: OnDone,
+    context: ErrorContext,
+}
+
+/// Trace in progress of a webview, one at a time
+#[derive(Default)]
+pub(crate) struct Tracer {
+    trace: RefCell<Option<Trace>>,
+    receivers: Cell<bool>,
+}
+
+impl Tracer {
+    pub fn is_busy(&self) -> bool {
+        self.trace.borrow().is_some()
+    }
+
+    /// Listen to the trace events, once per webview
+    pub fn add_receivers(self: &Rc<Self>, webview: &webview2::WebView) -> Result<(), Error> {
+        if self.receivers.replace(true) {
+            return Ok(());
+        }
+        let weak = Rc::downgrade(self);
+        webview
+            .get_dev_tools_protocol_event_receiver("Tracing.dataCollected")?
+            .add_dev_tools_protocol_event_received(move |_webview, args| {
+                if let Some(tracer) = weak.upgrade() {
+                    tracer.collected(&args.get_parameter_object_as_json()?);
+                }
+                Ok(())
+            })?;
+        let weak = Rc::downgrade(self);
+        webview
+            .get_dev_tools_protocol_event_receiver("Tracing.tracingComplete")?
+            .add_dev_tools_protocol_event_received(move |_webview, args| {
+                if let Some(tracer) = weak.upgrade() {
+                    tracer.complete(&args.get_parameter_object_as_json()?);
+                }
+                Ok(())
+            })?;
+        Ok(())
+    }
+
+    /// Create the file of the trace, before it's started
+    pub fn begin(
+        &self,
+        path: PathBuf,
+        deadline: Instant,
+        on_done: OnDone,
+        context: ErrorContext,
+    ) -> Result<(), Error> {
+        let mut file = BufWriter::new(File::create(&path)?);
+        file.write_all(br#"{"traceEvents":["#)?;
+        *self.trace.borrow_mut() = Some(Trace {
+            file,
+            path,
+            events: 0,
+            error: None,
+            deadline: Some(deadline),
+            on_done,
+            context,
+        });
+        Ok(())
+    }
+
+    /// Drop the trace that didn't start, `on_done` is not called
+    pub fn abort(&self) {
+        if let Some(trace) = self.trace.borrow_mut().take() {
+            drop(trace.file);
+            let _ = std::fs::remove_file(&trace.path);
+        }
+    }
+
+    /// When the trace should be ended
+    pub fn deadline(&self) -> Option<Instant> {
+        self.trace.borrow().as_ref().and_then(|t| t.deadline)
+    }
+
+    /// Is it time to call `Tracing.end`? True only once per trace
+    pub fn take_expired(&self, now: Instant) -> bool {
+        match self.trace.borrow_mut().as_mut() {
+            Some(trace) if trace.deadline.map_or(false, |d| d <= now) => {
+                trace.deadline = None;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn collected(&self, json: &str) {
+        let mut t = self.trace.borrow_mut();
+        let trace = match t.as_mut() {
+            Some(trace) if trace.error.is_none() => trace,
+            _ => return,
+        };
+        let result = (|| -> Result<(), Error> {
+            let data: DataCollected = serde_json::from_str(json)?;
+            for event in data.value {
+                if trace.events > 0 {
+                    trace.file.write_all(b",")?;
+                }
+                serde_json::to_writer(&mut trace.file, &event)?;
+                trace.events += 1;
+            }
+            Ok(())
+        })();
+        if let Err(err) = result {
+            trace.error = Some(err);
+        }
+    }
+
+    fn complete(&self, json: &str) {
+        let trace = match self.trace.borrow_mut().take() {
+            Some(trace) => trace,
+            None => return,
+        };
+        let Trace {
+            mut file,
+            path,
+            events,
+            error,
+            on_done,
+            context,
+            ..
+        } = trace;
+        let result = (|| -> Result<TraceStats, Error> {
+            if let Some(err) = error {
+                return Err(err);
+            }
+            let complete: TracingComplete = serde_json::from_str(json)?;
+            file.write_all(b"]}")?;
+            file.flush()?;
+            Ok(TraceStats {
+                path,
+                events,
+                data_loss: complete.data_loss_occurred,
+            })
+        })();
+        on_done(result.map_err(|err| err.with_context(context)));
+    }
+}
@@ -0,0 +1,353 @@
+//! Watchdog of the page's scripts, see `WebViewBuilder::watchdog`
+//!
+//! A page stuck in its scripts, e.g. in an endless loop, doesn't crash and
+//! gives no event. Instead each period `WebViewWrapper::poll` or the
+//! `Scheduler` posts `{"__watchdog": n}`, and the shim answers it with the
+//! same message from the page's event loop. A ping that isn't answered
+//! within the period is a miss, each miss sends
+//! `WatchdogEvent::PageUnresponsive`, and the first answer after the misses
+//! sends `WatchdogEvent::PageResponsive`. The page's own message listeners
+//! see the pings too, they can be told apart by the `__watchdog` key.
+//!
+//! The pings pause while the webview is hidden, and start over for each
+//! navigation, so the loading of the next page is not a miss.
+
+use crate::middleware::{InboundCtx, MessageMiddleware, MiddlewareAction};
+use serde::{Deserialize, Serialize};
+use std::cell::Cell;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+/// Responsiveness of the page, see `WebViewBuilder::watchdog`
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum WatchdogEvent {
+    /// The ping was not answered in time, sent for each miss
+    PageUnresponsive {
+        /// Since the first unanswered ping
+        stalled: Duration,
+        misses: u32,
+    },
+
+    /// The page answered again after the misses
+    PageResponsive { stalled: Duration },
+}
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct Envelope {
+    #[serde(rename = "__watchdog")]
+    pub seq: u64,
+}
+
+pub(crate) const SCRIPT: &str = r#"(() => {
+    window.chrome.webview.addEventListener("message", e => {
+        const seq = e.data && e.data.__watchdog;
+        if (typeof seq === "number") {
+            window.chrome.webview.postMessage(JSON.stringify({ __watchdog: seq }));
+        }
+    });
+})();"#;
+
+/// What the poll wants done
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub(crate) struct Step {
+    /// Post the ping with the sequence number
+    pub ping: Option<u64>,
+
+    /// Reload the page, `reload_after` misses were reached
+    pub reload: bool,
+}
+
+pub(crate) struct Watchdog {
+    period: Duration,
+    reload_after: Option<u32>,
+    event: Box<dyn Fn(WatchdogEvent)>,
+    seq: Cell<u64>,
+
+    // Unanswered ping, with its sequence number
+    sent: Cell<Option<(u64, Instant)>>,
+
+    // The next ping after the answered one
+    next: Cell<Option<Instant>>,
+    misses: Cell<u32>,
+    stalled_since: Cell<Option<Instant>>,
+    paused: Cell<bool>,
+}
+
+impl Watchdog {
+    pub fn new(
+        period: Duration,
+        reload_after: Option<u32>,
+        now: Instant,
+        event: impl Fn(WatchdogEvent) + 'static,
+    ) -> Self {
+        Watchdog {
+            period,
+            reload_after,
+            event: Box::new(event),
+            seq: Cell::new(0),
+            sent: Cell::new(None),
+            next: Cell::new(Some(now + period)),
+            misses: Cell::new(0),
+            stalled_since: Cell::new(None),
+            paused: Cell::new(false),
+        }
+    }
+
+    /// Ping if it's time, and count the unanswered ping as a miss
+    ///
+    /// Without the page to ping the next ping just waits another period.
+    pub fn poll(&self, now: Instant, can_ping: bool) -> Step {
+        let mut step = Step::default();
+        if self.paused.get() {
+            return step;
+        }
+        match self.sent.get() {
+            Some((_, sent)) if now >= sent + self.period => {
+                let stalled_since = self.stalled_since.get().unwrap_or(sent);
+                self.stalled_since.set(Some(stalled_since));
+                let misses = self.misses.get() + 1;
+                self.misses.set(misses);
+                (self.event)(WatchdogEvent::PageUnresponsive {
+                    stalled: now.saturating_duration_since(stalled_since),
+                    misses,
+                });
+                step.reload = self
+                    .reload_after
+                    .map_or(false, |n| n > 0 && misses % n == 0);
+            }
+            Some(_) => return step,
+            None => match self.next.get() {
+                Some(next) if now >= next => (),
+                _ => return step,
+            },
+        }
+        if can_ping {
+            let seq = self.seq.get() + 1;
+            self.seq.set(seq);
+            self.sent.set(Some((seq, now)));
+            self.next.set(None);
+            step.ping = Some(seq);
+        } else {
+            self.sent.set(None);
+            self.next.set(Some(now + self.period));
+        }
+        step
+    }
+
+    /// The page answered the ping
+    ///
+    /// After the misses any answer will do, the stuck page answers the
+    /// queued pings once it's free.
+    pub fn pong(&self, seq: u64, now: Instant) {
+        let current = matches!(self.sent.get(), Some((sent, _)) if sent == seq);
+        if !current && self.misses.get() == 0 {
+            return;
+        }
+        // One ping per period
+        let next = self.sent.get().map_or(now, |(_, sent)| sent) + self.period;
+        self.sent.set(None);
+        self.next.set(Some(next));
+        if self.misses.replace(0) > 0 {
+            let stalled_since = self.stalled_since.take().unwrap_or(now);
+            (self.event)(WatchdogEvent::PageResponsive {
+                stalled: now.saturating_duration_since(stalled_since),
+            });
+        }
+    }
+
+    /// Stop pinging while the webview is hidden, the misses are kept
+    pub fn pause(&self) {
+        self.paused.set(true);
+        self.sent.set(None);
+    }
+
+    /// Ping again a period after this
+    pub fn resume(&self, now: Instant) {
+        self.paused.set(false);
+        self.restart(now);
+    }
+
+    /// Forget the unanswered ping, e.g. the navigation replaces the page
+    pub fn restart(&self, now: Instant) {
+        self.sent.set(None);
+        self.next.set(Some(now + self.period));
+    }
+
+    /// When the next poll is due, `None` while paused
+    pub fn deadline(&self) -> Option<Instant> {
+        if self.paused.get() {
+            return None;
+        }
+        match self.sent.get() {
+            Some((_, sent)) => Some(sent + self.period),
+            None => self.next.get(),
+        }
+    }
+}
+
+/// Built-in middleware taking the answers to the pings
+pub(crate) struct Pong {
+    pub watchdog: Rc<Watchdog>,
+}
+
+impl MessageMiddleware for Pong {
+    fn inbound(&self, msg: InboundCtx) -> MiddlewareAction<InboundCtx> {
+        match serde_json::from_str::<Envelope>(&msg.message) {
+            Ok(envelope) => {
+                self.watchdog.pong(envelope.seq, Instant::now());
+                MiddlewareAction::Consume
+            }
+            Err(_) => MiddlewareAction::Continue(msg),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    const PERIOD: Duration = Duration::from_millis(1000);
+
+    fn ms(start: Instant, ms: u64) -> Instant {
+        start + Duration::from_millis(ms)
+    }
+
+    fn watchdog(reload_after: Option<u32>) -> (Instant, Watchdog, Rc<RefCell<Vec<WatchdogEvent>>>) {
+        let start = Instant::now();
+        let events = Rc::new(RefCell::new(Vec::new()));
+        let events_ = events.clone();
+        let watchdog = Watchdog::new(PERIOD, reload_after, start, move |e| {
+            events_.borrow_mut().push(e)
+        });
+        (start, watchdog, events)
+    }
+
+    fn ping(seq: u64) -> Step {
+        Step {
+            ping: Some(seq),
+            reload: false,
+        }
+    }
+
+    #[test]
+    fn answered_pings_are_one_per_period() {
+        let (start, watchdog, events) = watchdog(None);
+        assert_eq!(watchdog.deadline(), Some(ms(start, 1000)));
+        assert_eq!(watchdog.poll(ms(start, 500), true), Step::default());
+        assert_eq!(watchdog.poll(ms(start, 1000), true), ping(1));
+        watchdog.pong(1, ms(start, 1010));
+        assert_eq!(watchdog.deadline(), Some(ms(start, 2000)));
+        assert_eq!(watchdog.poll(ms(start, 1500), true), Step::default());
+        assert_eq!(watchdog.poll(ms(start, 2000), true), ping(2));
+        assert!(events.borrow().is_empty());
+    }
+
+    #[test]
+    fn each_miss_is_reported_until_the_page_answers() {
+        let (start, watchdog, events) = watchdog(None);
+        assert_eq!(watchdog.poll(ms(start, 1000), true), ping(1));
+        assert_eq!(watchdog.poll(ms(start, 2000), true), ping(2));
+        assert_eq!(watchdog.poll(ms(start, 3000), true), ping(3));
+
+        // The stuck page answers the queued pings once it's free
+        watchdog.pong(1, ms(start, 3500));
+        watchdog.pong(2, ms(start, 3500));
+        assert_eq!(
+            *events.borrow(),
+            vec![
+                WatchdogEvent::PageUnresponsive {
+                    stalled: PERIOD,
+                    misses: 1
+                },
+                WatchdogEvent::PageUnresponsive {
+                    stalled: 2 * PERIOD,
+                    misses: 2
+                },
+                WatchdogEvent::PageResponsive {
+                    stalled: Duration::from_millis(2500)
+                },
+            ]
+        );
+        assert_eq!(watchdog.deadline(), Some(ms(start, 4000)));
+    }
+
+    #[test]
+    fn stale_answer_of_a_responsive_page_is_ignored() {
+        let (start, watchdog, events) = watchdog(None);
+        assert_eq!(watchdog.poll(ms(start, 1000), true), ping(1));
+        watchdog.pong(7, ms(start, 1010));
+        assert_eq!(watchdog.deadline(), Some(ms(start, 2000)));
+        assert!(events.borrow().is_empty());
+    }
+
+    #[test]
+    fn reloads_after_the_misses() {
+        let (start, watchdog, _) = watchdog(Some(2));
+        assert_eq!(watchdog.poll(ms(start, 1000), true), ping(1));
+        assert_eq!(watchdog.poll(ms(start, 2000), true), ping(2));
+        assert_eq!(
+            watchdog.poll(ms(start, 3000), true),
+            Step {
+                ping: Some(3),
+                reload: true
+            }
+        );
+        assert_eq!(watchdog.poll(ms(start, 4000), true), ping(4));
+    }
+
+    #[test]
+    fn paused_watchdog_doesnt_ping() {
+        let (start, watchdog, events) = watchdog(None);
+        assert_eq!(watchdog.poll(ms(start, 1000), true), ping(1));
+        watchdog.pause();
+        assert_eq!(watchdog.deadline(), None);
+        assert_eq!(watchdog.poll(ms(start, 5000), true), Step::default());
+        watchdog.resume(ms(start, 5000));
+        assert_eq!(watchdog.deadline(), Some(ms(start, 6000)));
+        assert_eq!(watchdog.poll(ms(start, 6000), true), ping(2));
+        assert!(events.borrow().is_empty());
+    }
+
+    #[test]
+    fn navigation_forgets_the_unanswered_ping() {
+        let (start, watchdog, events) = watchdog(None);
+        assert_eq!(watchdog.poll(ms(start, 1000), true), ping(1));
+        watchdog.restart(ms(start, 1500));
+        assert_eq!(watchdog.poll(ms(start, 2000), true), Step::default());
+        assert_eq!(watchdog.poll(ms(start, 2500), true), ping(2));
+        assert!(events.borrow().is_empty());
+    }
+
+    #[test]
+    fn without_the_page_the_ping_waits() {
+        let (start, watchdog, events) = watchdog(None);
+        assert_eq!(watchdog.poll(ms(start, 1000), false), Step::default());
+        assert_eq!(watchdog.deadline(), Some(ms(start, 2000)));
+        assert_eq!(watchdog.poll(ms(start, 2000), true), ping(1));
+        assert!(events.borrow().is_empty());
+    }
+
+    #[test]
+    fn middleware_takes_the_answers() {
+        let (start, watchdog, _) = watchdog(None);
+        let watchdog = Rc::new(watchdog);
+        assert_eq!(watchdog.poll(ms(start, 1000), true), ping(1));
+        let pong = Pong {
+            watchdog: watchdog.clone(),
+        };
+        let message = serde_json::to_string(&Envelope { seq: 1 }).unwrap();
+        assert_eq!(message, r#"{"__watchdog":1}"#);
+        assert!(matches!(
+            pong.inbound(InboundCtx { message }),
+            MiddlewareAction::Consume
+        ));
+        assert_eq!(watchdog.deadline(), Some(ms(start, 2000)));
+        assert!(matches!(
+            pong.inbound(InboundCtx {
+                message: r#"{"other":1}"#.into()
+            }),
+            MiddlewareAction::Continue(_)
+        ));
+    }
+}
@@ -0,0 +1,350 @@
+//! Window position and size saved across the runs
+
+use crate::Error;
+use serde::{Deserialize, Serialize};
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+use winit::dpi::{PhysicalPosition, PhysicalSize};
+use winit::event_loop::EventLoopWindowTarget;
+use winit::window::WindowBuilder;
+
+/// Outer position and inner size of the window, in the physical pixels
+///
+/// The position and the size of a maximized window are the ones it's
+/// restored to.
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct WindowState {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    #[serde(default)]
+    pub maximized: bool,
+}
+
+// Moves and resizes come in bursts, the file is written once they stop
+const DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Storage of the window states by the key, e.g. the registry or the config
+/// file of the app
+pub trait WindowStateStore {
+    fn load(&self, key: &str) -> Option<WindowState>;
+    fn save(&self, key: &str, state: &WindowState) -> Result<(), Error>;
+}
+
+/// Window states in a JSON file, by default
+/// `%APPDATA%\<name of the exe>\window_state.json`
+#[derive(Clone, Debug)]
+pub struct JsonFileStore {
+    path: PathBuf,
+}
+
+impl JsonFileStore {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        JsonFileStore { path: path.into() }
+    }
+
+    fn read(&self) -> HashMap<String, WindowState> {
+        std::fs::read_to_string(&self.path)
+            .ok()
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default()
+    }
+
+    /// Merge the states to the file, replaced atomically so that the file
+    /// of the last writer wins over the other processes
+    fn write(&self, changed: impl IntoIterator<Item = (String, WindowState)>) -> Result<(), Error> {
+        let mut states = self.read();
+        states.extend(changed);
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let mut tmp = self.path.clone().into_os_string();
+        tmp.push(".tmp");
+        std::fs::write(&tmp, serde_json::to_string_pretty(&states)?)?;
+        std::fs::rename(&tmp, &self.path)?;
+        Ok(())
+    }
+}
+
+impl Default for JsonFileStore {
+    fn default() -> Self {
+        let app = std::env::current_exe()
+            .ok()
+            .and_then(|exe| exe.file_stem().map(|s| s.to_owned()))
+            .unwrap_or_else(|| "webview".into());
+        let dir = std::env::var_os("APPDATA").map_or_else(PathBuf::new, PathBuf::from);
+        JsonFileStore::new(dir.join(app).join("window_state.json"))
+    }
+}
+
+impl WindowStateStore for JsonFileStore {
+    fn load(&self, key: &str) -> Option<WindowState> {
+        self.read().get(key).copied()
+    }
+
+    fn save(&self, key: &str, state: &WindowState) -> Result<(), Error> {
+        self.write(Some((key.to_owned(), *state)))
+    }
+}
+
+/// Window states of the app saved as the windows are moved, resized,
+/// maximized and closed, see `WebViewBuilder::persist_window_state`
+///
+/// The changes are written once the window has been still for a moment,
+/// on `WebViewWrapper::poll`, and right away when the window is closed. A
+/// missing or corrupted file gives the default placements.
+#[derive(Clone)]
+pub struct WindowStatePersistence {
+    store: Rc<JsonFileStore>,
+    changed: Rc<RefCell<HashMap<String, WindowState>>>,
+    deadline: Rc<Cell<Option<Instant>>>,
+}
+
+impl WindowStatePersistence {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        WindowStatePersistence {
+            store: Rc::new(JsonFileStore::new(path)),
+            changed: Rc::default(),
+            deadline: Rc::default(),
+        }
+    }
+
+    /// Write the changed states now, e.g. before the app exits
+    pub fn flush(&self) -> Result<(), Error> {
+        self.deadline.set(None);
+        let changed: Vec<_> = self.changed.borrow_mut().drain().collect();
+        if changed.is_empty() {
+            return Ok(());
+        }
+        self.store.write(changed)
+    }
+
+    pub(crate) fn record(&self, key: &str, state: WindowState, now: Instant) {
+        self.changed.borrow_mut().insert(key.to_owned(), state);
+        self.deadline.set(Some(now + DEBOUNCE));
+    }
+
+    /// When the changes have settled
+    pub(crate) fn deadline(&self) -> Option<Instant> {
+        self.deadline.get()
+    }
+
+    /// Write the changes once they have settled, when to poll again
+    pub(crate) fn poll(&self, now: Instant) -> Result<Option<Instant>, Error> {
+        match self.deadline.get() {
+            Some(deadline) if deadline <= now => {
+                self.flush()?;
+                Ok(None)
+            }
+            deadline => Ok(deadline),
+        }
+    }
+}
+
+impl WindowStateStore for WindowStatePersistence {
+    fn load(&self, key: &str) -> Option<WindowState> {
+        let changed = self.changed.borrow().get(key).copied();
+        changed.or_else(|| self.store.load(key))
+    }
+
+    fn save(&self, key: &str, state: &WindowState) -> Result<(), Error> {
+        self.changed.borrow_mut().remove(key);
+        self.store.save(key, state)
+    }
+}
+
+/// Place the window as saved, the position only if it's still on a monitor
+///
+/// A maximized window whose monitor is gone is maximized on the primary
+/// monitor.
+pub(crate) fn apply<T>(
+    window_builder: WindowBuilder,
+    state: WindowState,
+    event_loop: &EventLoopWindowTarget<T>,
+) -> WindowBuilder {
+    let window_builder = window_builder
+        .with_inner_size(PhysicalSize::new(state.width, state.height))
+        .with_maximized(state.maximized);
+    let monitors = event_loop
+        .available_monitors()
+        .map(|monitor| (monitor.position(), monitor.size()));
+    if on_monitor(&state, monitors) {
+        window_builder.with_position(PhysicalPosition::new(state.x, state.y))
+    } else {
+        window_builder
+    }
+}
+
+/// Does the window overlap any of the monitors
+fn on_monitor(
+    state: &WindowState,
+    mut monitors: impl Iterator<Item = (PhysicalPosition<i32>, PhysicalSize<u32>)>,
+) -> bool {
+    monitors.any(|(position, size)| {
+        state.x < position.x + size.width as i32
+            && state.y < position.y + size.height as i32
+            && state.x + state.width as i32 > position.x
+            && state.y + state.height as i32 > position.y
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Own file per test, the tests run in parallel
+    fn temp_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "webviewbuilder_win-window-state-{}-{}.json",
+            std::process::id(),
+            name
+        ));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    fn state(x: i32, y: i32, width: u32, height: u32) -> WindowState {
+        WindowState {
+            x,
+            y,
+            width,
+            height,
+            maximized: false,
+        }
+    }
+
+    // 1920x1080 primary and a 1280x1024 monitor on its left
+    fn monitors() -> impl Iterator<Item = (PhysicalPosition<i32>, PhysicalSize<u32>)> {
+        vec![
+            (PhysicalPosition::new(0, 0), PhysicalSize::new(1920, 1080)),
+            (
+                PhysicalPosition::new(-1280, 0),
+                PhysicalSize::new(1280, 1024),
+            ),
+        ]
+        .into_iter()
+    }
+
+    #[test]
+    fn json_file_round_trip() {
+        let path = temp_path("round-trip");
+        let store = JsonFileStore::new(&path);
+        let main = WindowState {
+            maximized: true,
+            ..state(10, 20, 800, 600)
+        };
+        let other = state(-1000, 100, 300, 200);
+        store.save("main", &main).unwrap();
+        store.save("other", &other).unwrap();
+        assert_eq!(store.load("main"), Some(main));
+
+        // Another instance of the store, e.g. the next run
+        let store = JsonFileStore::new(&path);
+        assert_eq!(store.load("main"), Some(main));
+        assert_eq!(store.load("other"), Some(other));
+        assert_eq!(store.load("missing"), None);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn missing_and_corrupted_files_give_defaults() {
+        let path = temp_path("corrupted");
+        let store = JsonFileStore::new(&path);
+        assert_eq!(store.load("main"), None);
+        std::fs::write(&path, "{ not json").unwrap();
+        assert_eq!(store.load("main"), None);
+
+        // Overwritten on the next save
+        store.save("main", &state(1, 2, 3, 4)).unwrap();
+        assert_eq!(store.load("main"), Some(state(1, 2, 3, 4)));
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn states_without_maximized_are_not_maximized() {
+        let path = temp_path("old-format");
+        std::fs::write(
+            &path,
+            r#"{"main": {"x": 1, "y": 2, "width": 3, "height": 4}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            JsonFileStore::new(&path).load("main"),
+            Some(state(1, 2, 3, 4))
+        );
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn persistence_writes_once_the_changes_settle() {
+        let path = temp_path("debounce");
+        let persistence = WindowStatePersistence::new(&path);
+        let now = Instant::now();
+        persistence.record("main", state(0, 0, 100, 100), now);
+        persistence.record("main", state(0, 0, 200, 200), now + DEBOUNCE / 2);
+        assert_eq!(persistence.load("main"), Some(state(0, 0, 200, 200)));
+
+        let deadline = now + DEBOUNCE / 2 + DEBOUNCE;
+        assert_eq!(persistence.deadline(), Some(deadline));
+        assert_eq!(persistence.poll(now + DEBOUNCE).unwrap(), Some(deadline));
+        assert!(!path.exists());
+        assert_eq!(persistence.poll(deadline).unwrap(), None);
+        assert_eq!(persistence.deadline(), None);
+        assert_eq!(
+            JsonFileStore::new(&path).load("main"),
+            Some(state(0, 0, 200, 200))
+        );
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn persistence_flush_and_save_write_right_away() {
+        let path = temp_path("flush");
+        let persistence = WindowStatePersistence::new(&path);
+        persistence.record("a", state(1, 1, 10, 10), Instant::now());
+        persistence.flush().unwrap();
+        assert_eq!(persistence.deadline(), None);
+        persistence.save("b", &state(2, 2, 20, 20)).unwrap();
+
+        let store = JsonFileStore::new(&path);
+        assert_eq!(store.load("a"), Some(state(1, 1, 10, 10)));
+        assert_eq!(store.load("b"), Some(state(2, 2, 20, 20)));
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn windows_on_a_monitor_keep_the_position() {
+        assert!(on_monitor(&state(100, 100, 800, 600), monitors()));
+        assert!(on_monitor(&state(-1000, 100, 800, 600), monitors()));
+
+        // Partly off screen, still reachable
+        assert!(on_monitor(&state(1800, 1000, 800, 600), monitors()));
+        assert!(on_monitor(&state(-100, -100, 800, 600), monitors()));
+    }
+
+    #[test]
+    fn windows_off_screen_lose_the_position() {
+        assert!(!on_monitor(&state(1920, 0, 800, 600), monitors()));
+        assert!(!on_monitor(&state(0, 1080, 800, 600), monitors()));
+        assert!(!on_monitor(&state(-2080, 0, 800, 600), monitors()));
+
+        // Below the shorter monitor on the left
+        assert!(!on_monitor(&state(-1000, 1024, 300, 200), monitors()));
+    }
+
+    #[test]
+    fn maximized_on_a_disconnected_monitor() {
+        // Saved on the monitor on the left, now only the primary is left
+        let saved = WindowState {
+            maximized: true,
+            ..state(-1000, 100, 800, 600)
+        };
+        let primary = monitors().take(1);
+        assert!(!on_monitor(&saved, primary));
+        assert!(on_monitor(&saved, monitors()));
+    }
+}